@@ -55,18 +55,12 @@ impl Architecture for Amd64 {
     }
 
     fn decode(reg: &Region, start: u64, cfg: &Self::Configuration) -> Result<Match<Self>> {
-        let data = reg.iter();
-        let mut buf: Vec<u8> = vec![];
-        let mut i = data.seek(start);
+        // longest instruction is 15 bytes
+        let mut cells = [None; 15];
+        let len = reg.read(start, &mut cells);
+        let buf = cells[0..len].iter().take_while(|c| c.is_some()).map(|c| c.unwrap()).collect::<Vec<u8>>();
         let p = start;
 
-        while let Some(Some(b)) = i.next() {
-            buf.push(b);
-            if buf.len() == 15 {
-                break;
-            }
-        }
-
         debug!("disass @ {:#x}: {:?}", p, buf);
 
         let ret = crate::disassembler::read(*cfg, &buf, p).and_then(
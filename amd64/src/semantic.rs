@@ -198,6 +198,13 @@ fn extend(a: &Rvalue, b: &Rvalue, sign_ext: bool) -> Result<(Rvalue, Rvalue, usi
                     x.clone()
                 }
             }
+            &Rvalue::Wide { ref words, ref size } => {
+                if *size != s {
+                    Rvalue::new_wide(words.clone(), s)
+                } else {
+                    x.clone()
+                }
+            }
         }
     };
 
@@ -438,6 +445,68 @@ fn write_reg(reg: &Rvalue, val: &Rvalue, _sz: usize) -> Result<Vec<Statement>> {
     }
 }
 
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+enum Bitwise {
+    And,
+    AndNot,
+    Or,
+    Xor,
+}
+
+/// Computes `a op b` over the whole width of the operands and writes the result to `dst`. For
+/// `Bitwise::AndNot` `a` is inverted before. Used for the SSE/AVX logic instructions, xor'ing or
+/// andn'ing a register with itself moves zero into `dst`. The legacy SSE encodings of the AVX
+/// instructions have no `a` operand (it's undefined), `dst` is used instead.
+fn vector_bitwise(op: Bitwise, dst: &Rvalue, a: &Rvalue, b: &Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    let a = if *a == Rvalue::Undefined { dst } else { a };
+    let (a, b, sz, mut stmts) = zero_extend(a, b)?;
+    let res = rreil_lvalue!{ res:sz };
+
+    match op {
+        Bitwise::Xor | Bitwise::AndNot if a == b => {
+            stmts.append(
+                &mut rreil!{
+                mov res:sz, [0]:sz;
+            }?
+            );
+        }
+        Bitwise::And => {
+            stmts.append(
+                &mut rreil!{
+                and res:sz, (a), (b);
+            }?
+            );
+        }
+        Bitwise::Or => {
+            stmts.append(
+                &mut rreil!{
+                or res:sz, (a), (b);
+            }?
+            );
+        }
+        Bitwise::Xor => {
+            stmts.append(
+                &mut rreil!{
+                xor res:sz, (a), (b);
+            }?
+            );
+        }
+        Bitwise::AndNot => {
+            let ones = Rvalue::new_wide(vec![0xffffffffffffffff; (sz + 63) / 64], sz);
+
+            stmts.append(
+                &mut rreil!{
+                xor res:sz, (a), (ones);
+                and res:sz, res:sz, (b);
+            }?
+            );
+        }
+    }
+
+    stmts.append(&mut write_reg(dst, &res.into(), sz)?);
+    Ok((stmts, JumpSpec::FallThru))
+}
+
 pub fn aaa() -> Result<(Vec<Statement>, JumpSpec)> {
     return Ok((vec![], JumpSpec::FallThru));
     /*  rreil!{
@@ -2352,11 +2421,11 @@ pub fn paddusb(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
 pub fn paddusw(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn pand(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn pand(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vector_bitwise(Bitwise::And, &a, &a, &b)
 }
-pub fn pandn(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn pandn(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vector_bitwise(Bitwise::AndNot, &a, &a, &b)
 }
 pub fn pcmpeqb(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
@@ -2385,8 +2454,8 @@ pub fn pmulhw(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
 pub fn pmullw(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn por(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn por(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vector_bitwise(Bitwise::Or, &a, &a, &b)
 }
 pub fn psraw(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
@@ -2451,8 +2520,8 @@ pub fn punpcklwd(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
 pub fn punpcklqdq(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn pxor(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn pxor(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vector_bitwise(Bitwise::Xor, &a, &a, &b)
 }
 
 // SSE 1
@@ -2462,11 +2531,11 @@ pub fn addps(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
 pub fn addss(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn andnps(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn andnps(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vector_bitwise(Bitwise::AndNot, &a, &a, &b)
 }
-pub fn andps(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn andps(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vector_bitwise(Bitwise::And, &a, &a, &b)
 }
 pub fn cmpps(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
@@ -2549,8 +2618,8 @@ pub fn mulps(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
 pub fn mulss(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn orps(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn orps(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vector_bitwise(Bitwise::Or, &a, &a, &b)
 }
 pub fn pavgb(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
@@ -2651,8 +2720,8 @@ pub fn unpckhps(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, Jump
 pub fn unpcklps(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn xorps(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn xorps(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vector_bitwise(Bitwise::Xor, &a, &a, &b)
 }
 
 // SSE 2
@@ -2662,11 +2731,11 @@ pub fn addpd(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
 pub fn addsd(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn andnpd(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn andnpd(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vector_bitwise(Bitwise::AndNot, &a, &a, &b)
 }
-pub fn andpd(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn andpd(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vector_bitwise(Bitwise::And, &a, &a, &b)
 }
 pub fn cflush(_: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
@@ -2812,8 +2881,8 @@ pub fn mulpd(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
 pub fn mulsd(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn orpd(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn orpd(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vector_bitwise(Bitwise::Or, &a, &a, &b)
 }
 pub fn pabsb(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
@@ -2893,8 +2962,8 @@ pub fn unpckhpd(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
 pub fn unpcklpd(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn xorpd(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn xorpd(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vector_bitwise(Bitwise::Xor, &a, &a, &b)
 }
 
 // SSE 4
@@ -3547,17 +3616,17 @@ pub fn vaesimc(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpS
 pub fn vaeskeygenassist(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn vandpd(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vandpd(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vector_bitwise(Bitwise::And, &a, &b, &c)
 }
-pub fn vandps(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vandps(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vector_bitwise(Bitwise::And, &a, &b, &c)
 }
-pub fn vandnpd(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vandnpd(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vector_bitwise(Bitwise::AndNot, &a, &b, &c)
 }
-pub fn vandnps(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vandnps(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vector_bitwise(Bitwise::AndNot, &a, &b, &c)
 }
 pub fn vblendpd(_: Rvalue, _: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
@@ -3721,11 +3790,11 @@ pub fn vmovss(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSp
 pub fn vmpsadbw(_: Rvalue, _: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn vorpd(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vorpd(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vector_bitwise(Bitwise::Or, &a, &b, &c)
 }
-pub fn vorps(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vorps(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vector_bitwise(Bitwise::Or, &a, &b, &c)
 }
 pub fn vpabsb(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
@@ -3775,11 +3844,11 @@ pub fn vpaddusw(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, Jump
 pub fn vpalignr(_: Rvalue, _: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn vpand(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vpand(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vector_bitwise(Bitwise::And, &a, &b, &c)
 }
-pub fn vpandn(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vpandn(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vector_bitwise(Bitwise::AndNot, &a, &b, &c)
 }
 pub fn vpavgb(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
@@ -3913,8 +3982,8 @@ pub fn vpmullw(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpS
 pub fn vpmuludq(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn vpor(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vpor(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vector_bitwise(Bitwise::Or, &a, &b, &c)
 }
 pub fn vpsadbw(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
@@ -4009,8 +4078,8 @@ pub fn vpuncklqdq(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, Ju
 pub fn vpuncklwd(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn vpxor(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vpxor(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vector_bitwise(Bitwise::Xor, &a, &b, &c)
 }
 pub fn vrcpps(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
@@ -4186,11 +4255,11 @@ pub fn vtestps(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
 pub fn vzeroall() -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn vxorps(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vxorps(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vector_bitwise(Bitwise::Xor, &a, &b, &c)
 }
-pub fn vxorpd(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vxorpd(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vector_bitwise(Bitwise::Xor, &a, &b, &c)
 }
 
 pub fn broadcastf128(_: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
@@ -22,7 +22,8 @@ extern crate panopticon_amd64;
 extern crate env_logger;
 
 use panopticon_amd64 as amd64;
-use panopticon_core::{Architecture, Region};
+use panopticon_core::{Architecture, Interpreter, Lvalue, Region, Rvalue};
+use std::borrow::Cow;
 use std::path::Path;
 
 #[test]
@@ -76,3 +77,36 @@ fn ia32_opcodes() {
         }
     }
 }
+
+#[test]
+fn vector_logic() {
+    let xmm = |name: &'static str| Lvalue::Variable { name: Cow::Borrowed(name), subscript: None, size: 128 };
+    let run = |bytes: Vec<u8>, a: Rvalue, b: Rvalue| -> Rvalue {
+        let reg = Region::wrap("com".to_string(), bytes);
+        let match_st = amd64::Amd64::decode(&reg, 0, &amd64::Mode::Long).unwrap();
+        let mut int = Interpreter::new();
+
+        int.assign(&xmm("XMM0"), a).unwrap();
+        int.assign(&xmm("XMM1"), b).unwrap();
+        for mne in match_st.mnemonics {
+            for stmt in mne.instructions.iter() {
+                int.execute(stmt).unwrap();
+            }
+        }
+
+        int.value("XMM0", None)
+    };
+    let a = Rvalue::new_wide(vec![0xff00ff00ff00ff00, 0x1234567812345678], 128);
+    let b = Rvalue::new_wide(vec![0x0ff00ff00ff00ff0, 0xffffffff00000000], 128);
+
+    // pxor xmm0, xmm0
+    assert_eq!(run(vec![0x66, 0x0f, 0xef, 0xc0], a.clone(), b.clone()), Rvalue::Constant { value: 0, size: 128 });
+    // xorps xmm0, xmm1
+    assert_eq!(run(vec![0x0f, 0x57, 0xc1], a.clone(), b.clone()), Rvalue::new_wide(vec![0xf0f0f0f0f0f0f0f0, 0xedcba98712345678], 128));
+    // pand xmm0, xmm1
+    assert_eq!(run(vec![0x66, 0x0f, 0xdb, 0xc1], a.clone(), b.clone()), Rvalue::new_wide(vec![0x0f000f000f000f00, 0x1234567800000000], 128));
+    // pandn xmm0, xmm1
+    assert_eq!(run(vec![0x66, 0x0f, 0xdf, 0xc1], a.clone(), b.clone()), Rvalue::new_wide(vec![0x00f000f000f000f0, 0xedcba98700000000], 128));
+    // orpd xmm0, xmm1
+    assert_eq!(run(vec![0x66, 0x0f, 0x56, 0xc1], a, b), Rvalue::new_wide(vec![0xfff0fff0fff0fff0, 0xffffffff12345678], 128));
+}
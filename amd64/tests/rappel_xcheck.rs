@@ -25,7 +25,7 @@ extern crate regex;
 extern crate quickcheck;
 
 use panopticon_amd64::{AddressingMethod, JumpSpec, MnemonicSpec, Opcode, Operand, OperandSpec, OperandType, read_spec_register, semantic, tables};
-use panopticon_core::{Lvalue, Result, Rvalue, Statement, execute};
+use panopticon_core::{Interpreter, Lvalue, Result, Rvalue, Statement};
 
 use quickcheck::{Arbitrary, Gen, TestResult, Testable};
 use std::borrow::Cow;
@@ -471,8 +471,6 @@ fn rappel_xcheck(
     use std::process::{Command, Stdio};
     use std::io::{Read, Write};
     use regex::Regex;
-    use std::collections::HashMap;
-    use std::borrow::Cow;
 
     println!("{:?}", start);
 
@@ -645,70 +643,38 @@ fn rappel_xcheck(
     assert_eq!(flags.len(), 6);
     println!("regs: {:?}", regs);
 
-    let mut ctx = HashMap::<Cow<'static, str>, u64>::new();
+    let mut int = Interpreter::new();
 
     for stmt in stmts {
-        let mut s = stmt.op;
-
-        for rv in s.operands_mut() {
-            *rv = if let &mut Rvalue::Variable { ref name, ref offset, ref size, .. } = rv {
-                if let Some(val) = ctx.get(name.as_ref()) {
-                    if *size < 64 {
-                        Rvalue::Constant {
-                            value: (*val >> *offset as usize) % (1 << *size),
-                            size: *size,
-                        }
-                    } else {
-                        Rvalue::Constant { value: (*val >> *offset), size: *size }
-                    }
-                } else {
-                    rv.clone()
-                }
-            } else {
-                rv.clone()
-            }
-        }
-
-        println!(
-            "{}",
-            Statement { assignee: stmt.assignee.clone(), op: s.clone() }
-        );
-
-        if let Lvalue::Variable { ref name, .. } = stmt.assignee {
-            let res = execute(s);
-            println!("\t-> {}", res);
+        println!("{}", stmt);
+        int.execute(&stmt)?;
 
-            match res {
-                Rvalue::Constant { ref value, .. } => {
-                    ctx.insert(name.clone(), *value);
-                }
-                Rvalue::Undefined => {
-                    ctx.remove(name);
-                }
-                _ => {}
+        if let Lvalue::Variable { ref name, ref subscript, .. } = stmt.assignee {
+            match int.variable(name, *subscript) {
+                Some(val) => println!("\t-> 0x{:x}", val),
+                None => println!("\t-> ?"),
             }
         }
     }
 
-    println!("{:?}", ctx);
-
     for (name, val) in regs {
-        let key = Cow::Owned(name.trim().clone().to_uppercase());
+        let key = name.trim().to_uppercase();
+        let soft = int.variable(&key, None);
 
-        if Some(val) != ctx.get(&key).map(|x| *x as u64) {
+        if Some(val) != soft {
             println!(
                 "{}:\n\tHardware = 0x{:x}\n\tSoftware = 0x{:x}",
                 key,
                 val,
-                ctx.get(&key).unwrap_or(&0)
+                soft.unwrap_or(0)
             );
             return Ok(false);
         }
     }
 
     for (name, val) in flags {
-        let key = Cow::Owned(name.trim().clone().to_uppercase());
-        let soft = ctx.get(&key).map(|x| *x as u64);
+        let key = name.trim().to_uppercase();
+        let soft = int.variable(&key, None);
 
         if soft.is_some() && Some(if val { 1 } else { 0 }) != soft {
             println!(
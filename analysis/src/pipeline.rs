@@ -18,8 +18,8 @@
 
 use futures::{Future, Sink, Stream, stream};
 use futures::sync::mpsc;
-use panopticon_core::{Architecture, CallTarget, Error, Function, Program, Result, Region, Rvalue};
-use panopticon_data_flow::ssa_convertion;
+use panopticon_core::{Architecture, Bound, CallTarget, DisassemblyOptions, Error, Function, Program, Result, Region, Rvalue};
+use panopticon_data_flow::{simplify, ssa_convertion};
 use std::collections::HashSet;
 use std::fmt::Debug;
 use std::thread;
@@ -28,10 +28,13 @@ use uuid::Uuid;
 use std::result;
 use parking_lot::{Mutex, RwLock};
 
+/// Disassembles all functions of `program` in `region` in parallel. Code outside of executable
+/// segments is only disassembled if `ignore_permissions` is set.
 pub fn analyze<A: Architecture + Debug + Sync + 'static>(
     program: Program,
     region: Region,
     config: A::Configuration,
+    ignore_permissions: bool,
 ) -> Result<Program>
 where
     A::Configuration: Debug + Sync,
@@ -42,6 +45,7 @@ where
     struct Init {
         name: Option<String>,
         entry: u64,
+        extent: Option<Bound>,
         uuid: Uuid,
     }
 
@@ -56,28 +60,36 @@ where
         .filter_map(
             |ct| match ct {
                 &CallTarget::Todo(Rvalue::Constant { value: entry, .. }, ref name, ref uuid) => {
-                    Some(Init { entry, name: name.clone(), uuid: *uuid })
+                    Some(Init { entry, name: name.clone(), extent: program.symbol_extent(entry), uuid: *uuid })
                 }
                 _ => None,
             }
         ).collect::<Vec<Init>>();
 
+    // imports have no code, calls to them are linked by `Program::insert`
+    let imports = program.imports.keys().cloned().collect::<HashSet<u64>>();
+
     // we now lock the program
     let program = Mutex::new(program);
 
     info!("begin first wave {}", functions.len());
-    functions.into_par_iter().for_each(| Init { entry, name, uuid }| {
+    functions.into_par_iter().for_each(| Init { entry, name, extent, uuid }| {
         let name = &name;
+        let options = DisassemblyOptions { extent: extent, ignore_permissions: ignore_permissions, ..DisassemblyOptions::default() };
         attempts.upsert(entry,
                         || {
-                            match Function::with_uuid::<A>(entry, &uuid, &region, name.clone(), config.clone()) {
+                            match Function::with_options::<A>(entry, &uuid, &region, name.clone(), config.clone(), &options) {
                                 Ok(mut f) => {
                                     for address in f.collect_call_addresses() {
-                                        targets.upsert(address, || { true }, |_| ());
+                                        if !imports.contains(&address) {
+                                            targets.upsert(address, || { true }, |_| ());
+                                        }
                                     }
+                                    simplify(&mut f);
                                     let _ = ssa_convertion(&mut f);
                                     {
                                         let mut program = program.lock();
+                                        program.attach_debug_info(&mut f);
                                         let _ = program.insert(f);
                                     }
                                     Ok(())
@@ -101,20 +113,25 @@ where
 
     info!("first wave done: success: {} failures: {} targets: {}", attempts.len(), *failures.read(), targets.len());
 
+    let options = DisassemblyOptions { ignore_permissions: ignore_permissions, ..DisassemblyOptions::default() };
     let mut targets = targets.into_iter().map(|(x, _)| x).collect::<Vec<u64>>();
     while !targets.is_empty() {
         info!("targets - ({})", targets.len());
         let new_targets = CHashMap::<u64, bool>::new();
         targets.into_par_iter().for_each(| address | {
             attempts.upsert(address, || {
-                match Function::new::<A>(address, &region, None, config.clone()) {
+                match Function::with_options::<A>(address, &Uuid::new_v4(), &region, None, config.clone(), &options) {
                     Ok(mut f) => {
                         for address in f.collect_call_addresses() {
-                            new_targets.upsert(address, || { true }, |_| ());
+                            if !imports.contains(&address) {
+                                new_targets.upsert(address, || { true }, |_| ());
+                            }
                         }
+                        simplify(&mut f);
                         let _ = ssa_convertion(&mut f);
                         {
                             let mut program = program.lock();
+                            program.attach_debug_info(&mut f);
                             let _ = program.insert(f);
                         }
                         Ok(())
@@ -130,15 +147,30 @@ where
     let mut program = program.into_inner();
     info!("Finished analysis: {} failures {}", attempts.len(), *failures.read());
     program.update_plt();
+
+    for function in program.functions() {
+        if let Some(extent) = program.symbol_extent(function.start()) {
+            if function.end() > extent.end {
+                warn!(
+                    "{} ends at {:#x}, past the end of its symbol at {:#x}",
+                    function.name,
+                    function.end(),
+                    extent.end
+                );
+            }
+        }
+    }
+
     Ok(program)
 }
 
 /// Starts disassembling insructions in `region` and puts them into `program`. Returns a stream of
-/// of newly discovered functions.
+/// of newly discovered functions. See `analyze` for `ignore_permissions`.
 pub fn pipeline<A: Architecture + Debug + 'static>(
     program: Arc<Program>,
     region: Region,
     config: A::Configuration,
+    ignore_permissions: bool,
 ) -> Box<Stream<Item = Function, Error = ()> + Send>
 where
     A::Configuration: Debug,
@@ -153,12 +185,15 @@ where
             for ct in program.call_graph.into_iter() {
                 match ct {
                     &CallTarget::Todo(Rvalue::Constant { value: entry, .. }, ref maybe_name, ref uuid) => {
+                        let options = DisassemblyOptions { extent: program.symbol_extent(entry), ignore_permissions: ignore_permissions, ..DisassemblyOptions::default() };
                         finished_functions.insert(entry);
-                        match Function::with_uuid::<A>(entry, uuid, &region, maybe_name.clone(), config.clone()) {
+                        match Function::with_options::<A>(entry, uuid, &region, maybe_name.clone(), config.clone(), &options) {
                             Ok(mut f) => {
                                 let addresses = f.collect_call_addresses();
                                 targets.extend_from_slice(&addresses);
+                                simplify(&mut f);
                                 let _ = ssa_convertion(&mut f);
+                                program.attach_debug_info(&mut f);
                                 let tx = tx.clone();
                                 tx.send_all(stream::iter(vec![Ok(f)])).wait().unwrap().0;
                             },
@@ -169,19 +204,22 @@ where
                 }
             }
 
+            let options = DisassemblyOptions { ignore_permissions: ignore_permissions, ..DisassemblyOptions::default() };
             while !targets.is_empty() {
                 info!("disassemble({}) {:?}", targets.len(), &targets);
                 let mut new_targets = Vec::new();
                 for address in targets.drain(..) {
                     info!("checking if {} is in {:?}", address, &finished_functions);
-                    if !finished_functions.contains(&address) {
+                    if !finished_functions.contains(&address) && !program.imports.contains_key(&address) {
                         finished_functions.insert(address);
                         info!("adding func_0x{:x}", address);
-                        match Function::new::<A>(address, &region, None, config.clone()) {
+                        match Function::with_options::<A>(address, &Uuid::new_v4(), &region, None, config.clone(), &options) {
                             Ok(mut f) => {
                                 let addresses = f.collect_call_addresses();
                                 new_targets.extend_from_slice(&addresses);
+                                simplify(&mut f);
                                 let _ = ssa_convertion(&mut f);
+                                program.attach_debug_info(&mut f);
                                 {
                                     let tx = tx.clone();
                                     tx.send_all(stream::iter(vec![Ok(f)])).wait().unwrap().0;
@@ -0,0 +1,45 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+extern crate panopticon_core;
+extern crate panopticon_amd64;
+extern crate panopticon_analysis;
+
+use panopticon_amd64 as amd64;
+use panopticon_analysis::analyze;
+use panopticon_core::{FunctionKind, loader};
+use std::path::Path;
+
+#[test]
+fn elf_plt_stubs() {
+    let (mut proj, _) = loader::load(Path::new("../test-data/libfoo.so")).unwrap();
+    let prog = proj.code.pop().unwrap();
+    let prog = analyze::<amd64::Amd64>(prog, proj.region().clone(), amd64::Mode::Long, false).unwrap();
+
+    // puts@plt: jmp [rip+0x2009c2], simplified before the stubs are matched
+    let stub = prog.find_function_by(|f| f.start() == 0x650).unwrap();
+
+    match stub.kind() {
+        &FunctionKind::Stub { ref name, plt_address } => {
+            assert_eq!(name, "puts");
+            assert_eq!(plt_address, 0x201018);
+        }
+        kind => panic!("puts@plt is {:?}", kind),
+    }
+    assert_eq!(stub.name, "puts@plt");
+}
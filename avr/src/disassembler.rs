@@ -35,8 +35,11 @@ impl Architecture for Avr {
     fn decode(reg: &Region, addr: u64, cfg: &Self::Configuration) -> Result<Match<Self>> {
         info!("disass @ {:x}", addr);
         let disass = syntax::disassembler();
+        // longest instructions are two words
+        let mut cells = [None; 4];
+        let len = reg.read(addr, &mut cells);
 
-        if let Some(st) = disass.next_match(&mut reg.iter().seek(addr), addr, cfg.clone()) {
+        if let Some(st) = disass.next_match(&mut cells[0..len].iter().cloned(), addr, cfg.clone()) {
             info!("    res: {:?}", st);
             Ok(st.into())
         } else {
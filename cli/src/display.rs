@@ -2,7 +2,7 @@ use std::io::Write;
 use termcolor::WriteColor;
 use termcolor::Color::*;
 
-use panopticon_core::{Function, BasicBlock, Mnemonic, MnemonicFormatToken, Operation, Program, Rvalue, Result, Statement};
+use panopticon_core::{Assignment, DebugVariable, ExpressionBuilder, Function, BasicBlock, Lvalue, Mnemonic, MnemonicFormatToken, Operation, Program, Rvalue, Result, Statement};
 
 macro_rules! color_bold {
     ($fmt:ident, $color:ident, $str:expr) => ({
@@ -35,6 +35,42 @@ pub fn print_rreil<W: Write + WriteColor>(fmt: &mut W, bbs: &[&BasicBlock]) -> R
     Ok(())
 }
 
+/// Prints the RREIL of each mnemonic in a basic block with single-use SSA definitions folded into expression trees
+pub fn print_expressions<W: Write + WriteColor>(fmt: &mut W, function: &Function, bbs: &[&BasicBlock]) -> Result<()> {
+    let builder = ExpressionBuilder::new(function);
+
+    color_bold!(fmt, White, "Expressions")?;
+    writeln!(fmt, ":")?;
+    for bb in bbs {
+        for (mnemonic, assignments) in bb.mnemonics().iter().zip(builder.basic_block(bb)) {
+            print_address_and_mnemonic(fmt, mnemonic)?;
+            for assignment in assignments.iter() {
+                print_assignment(fmt, assignment)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Print colored expression tree assignment
+pub fn print_assignment<W: Write + WriteColor>(fmt: &mut W, assignment: &Assignment) -> Result<()> {
+    write!(fmt, "{: <8}  ", "")?;
+    match assignment.assignee {
+        Lvalue::Variable { ref name, subscript: Some(subscript), .. } => {
+            color_bold!(fmt, White, format!("{}_{}", name, subscript))?;
+            color_bold!(fmt, Green, " = ")?;
+        },
+        Lvalue::Variable { ref name, subscript: None, .. } => {
+            color_bold!(fmt, White, name)?;
+            color_bold!(fmt, Green, " = ")?;
+        },
+        Lvalue::Undefined => {}
+    }
+    color!(fmt, White, assignment.expression)?;
+    writeln!(fmt, "")?;
+    Ok(())
+}
+
 /// Prints an address and its corresponding mnemonic at that address
 pub fn print_address_and_mnemonic<W: Write + WriteColor>(fmt: &mut W, mnemonic: &Mnemonic) -> Result<()> {
     color_bold!(fmt, White, format!("{:8x}", mnemonic.area.start as usize))?;
@@ -328,12 +364,38 @@ pub fn print_function<W: Write + WriteColor>(fmt: &mut W, function: &Function, b
     write!(fmt, "{:0>8x} <", function.start())?;
     color_bold!(fmt, Yellow, function.name)?;
     writeln!(fmt, ">:")?;
+    if let Some(info) = function.debug_info() {
+        print_debug_variables(fmt, "param", &info.parameters)?;
+        print_debug_variables(fmt, "local", &info.locals)?;
+        for inlined in info.inlined.iter() {
+            write!(fmt, "  ; inlined ")?;
+            color!(fmt, Yellow, inlined.name.clone().unwrap_or("?".to_string()))?;
+            match inlined.bound {
+                Some(ref b) => writeln!(fmt, " at {:#x}..{:#x}", b.start, b.end)?,
+                None => writeln!(fmt)?,
+            }
+        }
+    }
     for bb in bbs {
         print_basic_block(fmt, &bb, program)?;
     }
     Ok(())
 }
 
+/// Prints the source-level names of parameters or local variables and where they live
+fn print_debug_variables<W: Write + WriteColor>(fmt: &mut W, kind: &str, vars: &[DebugVariable]) -> Result<()> {
+    for var in vars {
+        write!(fmt, "  ; {} ", kind)?;
+        color!(fmt, Yellow, var.name)?;
+        if let Some(ref loc) = var.location {
+            write!(fmt, " @ ")?;
+            color!(fmt, Green, loc)?;
+        }
+        writeln!(fmt)?;
+    }
+    Ok(())
+}
+
 /// Prints the basic block into `fmt`, in disassembly order, in human readable form, and looks up any functions calls in `program`
 pub fn print_basic_block<W: Write + WriteColor>(fmt: &mut W, basic_block: &BasicBlock, program: &Program) -> Result<()> {
     for mnemonic in basic_block.mnemonics.iter() {
@@ -404,6 +466,9 @@ pub fn print_mnemonic<W: Write + WriteColor>(fmt: &mut W, mnemonic: &Mnemonic, p
                             write!(fmt, "{}", format!("{:#x}",val))?;
                         }
                     },
+                    Some(rv @ &Rvalue::Wide{ .. }) => {
+                        write!(fmt, "{}", rv.hex_digits().unwrap_or_default())?;
+                    },
                     Some(&Rvalue::Variable{ ref name, subscript: Some(_),.. }) => {
                         color!(fmt, Yellow, name.to_lowercase())?;
                     },
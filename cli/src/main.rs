@@ -6,6 +6,7 @@ extern crate error_chain;
 extern crate panopticon_core;
 extern crate panopticon_amd64;
 extern crate panopticon_avr;
+extern crate panopticon_mos6502;
 extern crate panopticon_analysis;
 extern crate panopticon_graph_algos;
 extern crate futures;
@@ -18,7 +19,8 @@ extern crate atty;
 use panopticon_amd64 as amd64;
 use panopticon_analysis::analyze;
 use panopticon_avr as avr;
-use panopticon_core::{Machine, Function, FunctionKind, Program, Result, loader};
+use panopticon_mos6502 as mos6502;
+use panopticon_core::{Architecture, Machine, Function, FunctionKind, Program, Project, Result, loader};
 use std::path::Path;
 use std::result;
 use structopt::StructOpt;
@@ -45,6 +47,9 @@ struct Args {
     /// Dumps the il of the matched function
     #[structopt(long = "il", help = "Print the rreil of this function")]
     dump_il: bool,
+    /// Dumps the il of the matched function as expression trees
+    #[structopt(long = "expressions", help = "Print the rreil of this function with single use definitions folded into expressions")]
+    dump_expressions: bool,
     #[structopt(long = "color", help = "Forces coloring, even when piping to a file, etc.")]
     color: bool,
     /// Print every function the function calls
@@ -56,6 +61,21 @@ struct Args {
     /// The specific function address to disassemble
     #[structopt(short = "a", long = "address", help = "Disassemble the function at the given address")]
     address_filter: Option<String>,
+    /// The architecture to load from a fat binary
+    #[structopt(long = "arch", help = "Load the slice for this architecture (amd64, ia32, avr or 6502) from a fat binary or disassemble a raw image as this architecture")]
+    arch: Option<String>,
+    /// Treat the binary as a flat image
+    #[structopt(long = "raw", help = "Load the binary as a raw image or Intel HEX/S-record file, requires --arch")]
+    raw: bool,
+    /// Load address of a raw image
+    #[structopt(long = "base", help = "Hexadecimal address the raw image is loaded at, defaults to 0")]
+    base: Option<String>,
+    /// Entry point of a raw image
+    #[structopt(long = "entry", help = "Hexadecimal address of the entry point of a raw image, defaults to --base")]
+    entry: Option<String>,
+    /// Allow functions outside of executable segments
+    #[structopt(long = "ignore-permissions", help = "Disassemble functions in segments that are not executable")]
+    ignore_permissions: bool,
     /// The binary to disassemble
     #[structopt(help = "The binary to disassemble")]
     binary: String,
@@ -162,20 +182,85 @@ fn print_reverse_deps<W: Write + WriteColor>(mut fmt: W, program: &Program, filt
     Ok(())
 }
 
-fn disassemble(binary: &str) -> Result<Program> {
-    let (mut proj, machine) = loader::load(Path::new(&binary))?;
-    let program = proj.code.pop().unwrap();
+fn parse_address(s: &str) -> Result<u64> {
+    match u64::from_str_radix(s.trim_left_matches("0x"), 16) {
+        Ok(addr) => Ok(addr),
+        Err(e) => Err(format!("'{}' is not a hexadecimal address: {}", s, e).into()),
+    }
+}
+
+fn load_raw(args: &Args) -> Result<(Project, Machine)> {
+    let machine = match args.arch {
+        Some(ref arch) => arch.parse::<Machine>()?,
+        None => return Err("--raw requires --arch".into()),
+    };
+    let base = match args.base {
+        Some(ref base) => parse_address(base)?,
+        None => 0,
+    };
+    let entry_points = match (&args.entry, machine) {
+        (&Some(ref entry), _) => vec![parse_address(entry)?],
+        // AVR and 6502 entry points are taken from the interrupt vectors
+        (&None, Machine::Avr) | (&None, Machine::Mos6502) => vec![],
+        (&None, _) => vec![base],
+    };
+    let path = Path::new(&args.binary);
+    let proj = match machine {
+        Machine::Avr => load_image::<avr::Avr>(path, machine, base, &entry_points, &avr::Mcu::atmega103())?,
+        Machine::Ia32 => load_image::<amd64::Amd64>(path, machine, base, &entry_points, &amd64::Mode::Protected)?,
+        Machine::Amd64 => load_image::<amd64::Amd64>(path, machine, base, &entry_points, &amd64::Mode::Long)?,
+        Machine::Mos6502 => load_image::<mos6502::Mos>(path, machine, base, &entry_points, &mos6502::Variant::mos6502())?,
+    };
+
+    Ok((proj, machine))
+}
+
+fn load_image<A: Architecture>(path: &Path, machine: Machine, base: u64, entry_points: &[u64], config: &A::Configuration) -> Result<Project> {
+    // firmware images carry their own load addresses
+    match path.extension().and_then(|x| x.to_str()) {
+        Some("hex") | Some("ihx") | Some("ihex") => loader::load_ihex::<A>(path, machine, config),
+        Some("srec") | Some("s19") | Some("s28") | Some("s37") | Some("mot") => loader::load_srec::<A>(path, machine, config),
+        _ => {
+            let mut proj = loader::load_raw(path, machine, base, entry_points)?;
+            loader::seed_entry_points::<A>(&mut proj, config)?;
+            Ok(proj)
+        }
+    }
+}
+
+fn disassemble(args: &Args) -> Result<Vec<Program>> {
+    let (mut proj, machine) = if args.raw {
+        load_raw(args)?
+    } else {
+        match args.arch {
+            Some(ref arch) => loader::load_arch(Path::new(&args.binary), arch.parse::<Machine>()?)?,
+            None => loader::load(Path::new(&args.binary))?,
+        }
+    };
+    if !args.raw && machine == Machine::Mos6502 {
+        // iNES images carry their entry points in the interrupt vectors
+        loader::seed_entry_points::<mos6502::Mos>(&mut proj, &mos6502::Variant::mos6502())?;
+    }
     let reg = proj.region().clone();
     info!("disassembly thread started");
-    Ok(match machine {
-        Machine::Avr => analyze::<avr::Avr>(program, reg.clone(), avr::Mcu::atmega103()),
-        Machine::Ia32 => analyze::<amd64::Amd64>(program, reg.clone(), amd64::Mode::Protected),
-        Machine::Amd64 => analyze::<amd64::Amd64>(program, reg.clone(), amd64::Mode::Long),
-    }?)
+    // static archives yield one program per object file
+    proj.code
+        .drain(..)
+        .map(
+            |program| {
+                Ok(match machine {
+                    Machine::Avr => analyze::<avr::Avr>(program, reg.clone(), avr::Mcu::atmega103(), args.ignore_permissions),
+                    Machine::Ia32 => analyze::<amd64::Amd64>(program, reg.clone(), amd64::Mode::Protected, args.ignore_permissions),
+                    Machine::Amd64 => analyze::<amd64::Amd64>(program, reg.clone(), amd64::Mode::Long, args.ignore_permissions),
+                    Machine::Mos6502 => analyze::<mos6502::Mos>(program, reg.clone(), mos6502::Variant::mos6502(), args.ignore_permissions),
+                }?)
+            }
+        )
+        .collect()
 }
 
-fn app_logic(fmt: &mut termcolor::Buffer, program: Program, args: Args) -> Result<()> {
-    let filter = Filter { name: args.function_filter, addr: args.address_filter.map(|addr| u64::from_str_radix(&addr, 16).unwrap()) };
+fn app_logic(fmt: &mut termcolor::Buffer, program: Program, args: &Args) -> Result<()> {
+    let filter = Filter { name: args.function_filter.clone(), addr: args.address_filter.as_ref().map(|addr| u64::from_str_radix(addr, 16).unwrap()) };
 
     debug!("Program.imports: {:#?}", program.imports);
     if args.reverse_deps && filter.filtering() {
@@ -209,6 +294,9 @@ fn app_logic(fmt: &mut termcolor::Buffer, program: Program, args: Args) -> Resul
         if args.dump_il {
             display::print_rreil(fmt, &bbs)?;
         }
+        if args.dump_expressions {
+            display::print_expressions(fmt, &function, &bbs)?;
+        }
         writeln!(fmt, "Aliases: {:?}", function.aliases())?;
     }
     Ok(())
@@ -216,11 +304,18 @@ fn app_logic(fmt: &mut termcolor::Buffer, program: Program, args: Args) -> Resul
 
 fn run(args: Args) -> Result<()> {
     exists_path_val(&args.binary)?;
-    let program = disassemble(&args.binary)?;
+    let programs = disassemble(&args)?;
     let cc = if args.color || atty::is(atty::Stream::Stdout) { ColorChoice::Auto } else { ColorChoice::Never };
     let writer = BufferWriter::stdout(cc);
     let mut fmt = writer.buffer();
-    app_logic(&mut fmt, program, args)?;
+    let multiple = programs.len() > 1;
+    for program in programs {
+        if multiple {
+            color_bold!(fmt, Blue, program.name.clone())?;
+            writeln!(fmt, ":")?;
+        }
+        app_logic(&mut fmt, program, &args)?;
+    }
     writer.print(&fmt)?;
     Ok(())
 }
@@ -0,0 +1,424 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Reader for process memory dumps.
+//!
+//! Two formats are supported: the notes of Linux ELF core files and Windows minidumps. For ELF
+//! cores only the notes are parsed here, the memory is mapped by the loader from the `PT_LOAD`
+//! segments. Minidumps are read with the `minidump` crate: memory ranges, loaded modules and
+//! threads.
+
+use crate::{Bound, Machine, Result};
+use byteorder::{ByteOrder, LittleEndian};
+use minidump::{Minidump as Dump, MinidumpException, MinidumpMemory64List, MinidumpMemoryList, MinidumpModuleList, MinidumpRawContext,
+               MinidumpSystemInfo, MinidumpThreadList, Module};
+use minidump::system_info::Cpu;
+
+const NT_PRSTATUS: u32 = 1;
+const NT_FILE: u32 = 0x4649_4c45;
+
+// Order of the registers in the `user_regs_struct` of NT_PRSTATUS
+const AMD64_PRSTATUS_REGS: [&'static str; 27] = [
+    "R15", "R14", "R13", "R12", "RBP", "RBX", "R11", "R10", "R9", "R8", "RAX", "RCX", "RDX", "RSI", "RDI", "ORIG_RAX", "RIP", "CS", "EFLAGS",
+    "RSP", "SS", "FS_BASE", "GS_BASE", "DS", "ES", "FS", "GS",
+];
+const IA32_PRSTATUS_REGS: [&'static str; 17] = ["EBX", "ECX", "EDX", "ESI", "EDI", "EBP", "EAX", "DS", "ES", "FS", "GS", "ORIG_EAX", "EIP", "CS", "EFLAGS", "ESP", "SS"];
+
+// Registers shown in thread comments, in this order
+const AMD64_REGS: [&'static str; 18] = [
+    "RAX", "RBX", "RCX", "RDX", "RSI", "RDI", "RBP", "RSP", "R8", "R9", "R10", "R11", "R12", "R13", "R14", "R15", "RIP", "EFLAGS",
+];
+const IA32_REGS: [&'static str; 10] = ["EAX", "EBX", "ECX", "EDX", "ESI", "EDI", "EBP", "ESP", "EIP", "EFLAGS"];
+
+const MINIDUMP_MAGIC: &'static [u8] = b"MDMP";
+
+/// A thread of the dumped process.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Thread {
+    /// Thread ID.
+    pub id: u64,
+    /// Signal or exception that stopped the thread. `None` for threads that did not crash.
+    pub reason: Option<String>,
+    /// Values of the general purpose registers, using RREIL names.
+    pub registers: Vec<(&'static str, u64)>,
+    /// Value of the program counter.
+    pub pc: u64,
+}
+
+impl Thread {
+    /// Human readable description of the thread state, suitable as a comment at `pc`.
+    pub fn describe(&self, machine: Machine) -> String {
+        let order: &[&str] = match machine {
+            Machine::Amd64 => &AMD64_REGS,
+            Machine::Ia32 => &IA32_REGS,
+            _ => &[],
+        };
+        let regs = order
+            .iter()
+            .filter_map(|name| self.registers.iter().find(|r| r.0 == *name))
+            .map(|&(name, value)| format!("{}={:#x}", name, value))
+            .collect::<Vec<_>>();
+        let title = match self.reason {
+            Some(ref reason) => format!("{} in thread {}", reason, self.id),
+            None => format!("Thread {}", self.id),
+        };
+
+        format!("{}\n{}", title, regs.join(" "))
+    }
+}
+
+/// A file mapped into the dumped process.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct MappedFile {
+    /// Path of the file.
+    pub name: String,
+    /// Address range the file is mapped to.
+    pub bound: Bound,
+}
+
+/// Contents of the `PT_NOTE` segments of an ELF core file.
+#[derive(Clone,Debug,Default)]
+pub struct CoreNotes {
+    /// Threads in the order of their `NT_PRSTATUS` notes. The first one is the thread that crashed.
+    pub threads: Vec<Thread>,
+    /// Mappings listed in the `NT_FILE` note.
+    pub files: Vec<MappedFile>,
+}
+
+/// Contents of a minidump.
+#[derive(Clone,Debug)]
+pub struct Minidump {
+    /// CPU of the dumped process.
+    pub machine: Machine,
+    /// Start address and contents of all saved memory ranges.
+    pub memory: Vec<(u64, Vec<u8>)>,
+    /// Loaded executables and libraries.
+    pub modules: Vec<MappedFile>,
+    /// Threads with their register state. The thread the exception happened in is first.
+    pub threads: Vec<Thread>,
+}
+
+fn read_u16(bytes: &[u8], off: usize) -> Result<u16> {
+    bytes.get(off..off.wrapping_add(2)).map(LittleEndian::read_u16).ok_or_else(|| "Dump truncated".into())
+}
+
+fn read_u32(bytes: &[u8], off: usize) -> Result<u32> {
+    bytes.get(off..off.wrapping_add(4)).map(LittleEndian::read_u32).ok_or_else(|| "Dump truncated".into())
+}
+
+fn read_u64(bytes: &[u8], off: usize) -> Result<u64> {
+    bytes.get(off..off.wrapping_add(8)).map(LittleEndian::read_u64).ok_or_else(|| "Dump truncated".into())
+}
+
+fn read_word(bytes: &[u8], off: usize, is_64: bool) -> Result<u64> {
+    if is_64 { read_u64(bytes, off) } else { read_u32(bytes, off).map(|x| x as u64) }
+}
+
+fn slice(bytes: &[u8], off: usize, len: usize) -> Result<&[u8]> {
+    match off.checked_add(len) {
+        Some(end) if end <= bytes.len() => Ok(&bytes[off..end]),
+        _ => Err(format!("Dump range {:#x}+{:#x} out of bounds", off, len).into()),
+    }
+}
+
+fn signal_name(signal: u16) -> String {
+    match signal {
+        4 => "SIGILL".to_string(),
+        5 => "SIGTRAP".to_string(),
+        6 => "SIGABRT".to_string(),
+        7 => "SIGBUS".to_string(),
+        8 => "SIGFPE".to_string(),
+        11 => "SIGSEGV".to_string(),
+        s => format!("Signal {}", s),
+    }
+}
+
+fn exception_name(code: u32) -> String {
+    let name = match code {
+        0x80000003 => "EXCEPTION_BREAKPOINT",
+        0x80000004 => "EXCEPTION_SINGLE_STEP",
+        0xc0000005 => "EXCEPTION_ACCESS_VIOLATION",
+        0xc000001d => "EXCEPTION_ILLEGAL_INSTRUCTION",
+        0xc0000094 => "EXCEPTION_INT_DIVIDE_BY_ZERO",
+        0xc00000fd => "EXCEPTION_STACK_OVERFLOW",
+        0xc0000409 => "STATUS_STACK_BUFFER_OVERRUN",
+        _ => return format!("Exception {:#x}", code),
+    };
+
+    format!("{} ({:#x})", name, code)
+}
+
+fn prstatus(desc: &[u8], machine: Machine) -> Result<Thread> {
+    let (regs, regs_off, pid_off, is_64, pc): (&[&'static str], usize, usize, bool, &str) = match machine {
+        Machine::Amd64 => (&AMD64_PRSTATUS_REGS, 112, 32, true, "RIP"),
+        Machine::Ia32 => (&IA32_PRSTATUS_REGS, 72, 24, false, "EIP"),
+        m => return Err(format!("Core dumps of {:?} processes are not supported", m).into()),
+    };
+    let word = if is_64 { 8 } else { 4 };
+    let registers = regs
+        .iter()
+        .enumerate()
+        .map(|(i, name)| read_word(desc, regs_off + i * word, is_64).map(|value| (*name, value)))
+        .collect::<Result<Vec<_>>>()?;
+    let pc = registers.iter().find(|r| r.0 == pc).map(|r| r.1).unwrap_or(0);
+    let signal = read_u16(desc, 12)?;
+
+    Ok(
+        Thread {
+            id: read_u32(desc, pid_off)? as u64,
+            reason: if signal != 0 { Some(signal_name(signal)) } else { None },
+            registers: registers,
+            pc: pc,
+        }
+    )
+}
+
+fn nt_file(desc: &[u8], is_64: bool) -> Result<Vec<MappedFile>> {
+    let word = if is_64 { 8 } else { 4 };
+    let count = read_word(desc, 0, is_64)? as usize;
+    let names_start = count.checked_mul(word * 3).and_then(|x| x.checked_add(word * 2)).ok_or_else(|| "NT_FILE note truncated")?;
+    let mut names = desc.get(names_start..).ok_or_else(|| "NT_FILE note truncated")?.split(|&b| b == 0);
+    let mut ret = Vec::with_capacity(count);
+
+    for i in 0..count {
+        let entry = word * 2 + i * word * 3;
+        let start = read_word(desc, entry, is_64)?;
+        let end = read_word(desc, entry + word, is_64)?;
+        let name = names.next().ok_or_else(|| "NT_FILE note truncated")?;
+
+        if start < end {
+            ret.push(MappedFile { name: String::from_utf8_lossy(name).into_owned(), bound: Bound::new(start, end) });
+        }
+    }
+
+    Ok(ret)
+}
+
+/// Parses the contents of a `PT_NOTE` segment of an ELF core file of a `machine` process.
+/// `is_64` is true for ELFCLASS64 files.
+pub fn parse_core_notes(bytes: &[u8], machine: Machine, is_64: bool) -> Result<CoreNotes> {
+    let mut ret = CoreNotes::default();
+    let mut pos = 0;
+
+    while pos + 12 <= bytes.len() {
+        let namesz = read_u32(bytes, pos)? as usize;
+        let descsz = read_u32(bytes, pos + 4)? as usize;
+        let kind = read_u32(bytes, pos + 8)?;
+        let name_start = pos + 12;
+        let desc_start = name_start + ((namesz + 3) & !3);
+        let name = slice(bytes, name_start, namesz)?;
+        let desc = slice(bytes, desc_start, descsz)?;
+
+        pos = desc_start + ((descsz + 3) & !3);
+
+        if name != b"CORE\0" {
+            continue;
+        }
+
+        match kind {
+            NT_PRSTATUS => ret.threads.push(prstatus(desc, machine)?),
+            NT_FILE => ret.files.extend(nt_file(desc, is_64)?),
+            _ => {}
+        }
+    }
+
+    Ok(ret)
+}
+
+/// Returns true if `bytes` starts with the minidump signature.
+pub fn is_minidump(bytes: &[u8]) -> bool {
+    bytes.starts_with(MINIDUMP_MAGIC)
+}
+
+/// Returns the general purpose registers and the program counter saved in `context`.
+fn minidump_registers(context: &MinidumpRawContext) -> Result<(Vec<(&'static str, u64)>, u64)> {
+    match context {
+        &MinidumpRawContext::Amd64(ref c) => {
+            let registers = vec![
+                ("EFLAGS", c.eflags as u64),
+                ("RAX", c.rax),
+                ("RCX", c.rcx),
+                ("RDX", c.rdx),
+                ("RBX", c.rbx),
+                ("RSP", c.rsp),
+                ("RBP", c.rbp),
+                ("RSI", c.rsi),
+                ("RDI", c.rdi),
+                ("R8", c.r8),
+                ("R9", c.r9),
+                ("R10", c.r10),
+                ("R11", c.r11),
+                ("R12", c.r12),
+                ("R13", c.r13),
+                ("R14", c.r14),
+                ("R15", c.r15),
+                ("RIP", c.rip),
+            ];
+
+            Ok((registers, c.rip))
+        }
+        &MinidumpRawContext::X86(ref c) => {
+            let registers = vec![
+                ("EDI", c.edi as u64),
+                ("ESI", c.esi as u64),
+                ("EBX", c.ebx as u64),
+                ("EDX", c.edx as u64),
+                ("ECX", c.ecx as u64),
+                ("EAX", c.eax as u64),
+                ("EBP", c.ebp as u64),
+                ("EIP", c.eip as u64),
+                ("EFLAGS", c.eflags as u64),
+                ("ESP", c.esp as u64),
+            ];
+
+            Ok((registers, c.eip as u64))
+        }
+        _ => Err("Unsupported minidump thread context".into()),
+    }
+}
+
+/// Parses the Windows minidump in `bytes`.
+pub fn parse_minidump(bytes: &[u8]) -> Result<Minidump> {
+    if !is_minidump(bytes) {
+        return Err("Not a minidump".into());
+    }
+
+    let dump = Dump::read(bytes)?;
+    let system_info = dump.get_stream::<MinidumpSystemInfo>()?;
+    let machine = match system_info.cpu {
+        Cpu::X86_64 => Machine::Amd64,
+        Cpu::X86 => Machine::Ia32,
+        cpu => return Err(format!("Unsupported minidump processor architecture {:?}", cpu).into()),
+    };
+    let mut ret = Minidump { machine: machine, memory: vec![], modules: vec![], threads: vec![] };
+
+    if let Ok(list) = dump.get_stream::<MinidumpMemoryList>() {
+        ret.memory.extend(list.iter().map(|m| (m.base_address, m.bytes.to_vec())));
+    }
+    if let Ok(list) = dump.get_stream::<MinidumpMemory64List>() {
+        ret.memory.extend(list.iter().map(|m| (m.base_address, m.bytes.to_vec())));
+    }
+
+    if let Ok(list) = dump.get_stream::<MinidumpModuleList>() {
+        for module in list.iter().filter(|m| m.size() > 0) {
+            let bound = Bound::new(module.base_address(), module.base_address() + module.size());
+
+            ret.modules.push(MappedFile { name: module.code_file().into_owned(), bound: bound });
+        }
+    }
+
+    let exception = dump.get_stream::<MinidumpException>().ok().map(|e| (e.raw.thread_id as u64, exception_name(e.raw.exception_record.exception_code)));
+
+    if let Ok(list) = dump.get_stream::<MinidumpThreadList>() {
+        for thread in list.threads.iter() {
+            let id = thread.raw.thread_id as u64;
+            // memory and modules are still useful without the registers of every thread
+            let (registers, pc) = match thread.context(&system_info, None) {
+                Some(context) => minidump_registers(&context.raw)?,
+                None => {
+                    warn!("Skipping minidump thread {} without context", id);
+                    continue;
+                }
+            };
+            let reason = exception.as_ref().and_then(|e| if e.0 == id { Some(e.1.clone()) } else { None });
+
+            ret.threads.push(Thread { id: id, reason: reason, registers: registers, pc: pc });
+        }
+    }
+
+    // crashing thread first
+    ret.threads.sort_by_key(|t| t.reason.is_none());
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(kind: u32, desc: &[u8]) -> Vec<u8> {
+        let mut ret = vec![];
+
+        ret.extend_from_slice(&[5, 0, 0, 0]);
+        ret.extend_from_slice(&[desc.len() as u8, (desc.len() >> 8) as u8, 0, 0]);
+        ret.extend_from_slice(&[kind as u8, (kind >> 8) as u8, (kind >> 16) as u8, (kind >> 24) as u8]);
+        ret.extend_from_slice(b"CORE\0\0\0\0");
+        ret.extend_from_slice(desc);
+        while ret.len() % 4 != 0 {
+            ret.push(0);
+        }
+        ret
+    }
+
+    #[test]
+    fn notes() {
+        let mut prstatus = vec![0u8; 144];
+        prstatus[12] = 11;
+        prstatus[24] = 42;
+        for (i, r) in [0x11u8, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff, 0x01, 0x02].iter().enumerate() {
+            prstatus[72 + i * 4] = *r;
+        }
+
+        let mut file = vec![2, 0, 0, 0, 0, 0x10, 0, 0];
+        file.extend_from_slice(&[0, 0, 0, 8, 0, 0x10, 0, 8, 0, 0, 0, 0]);
+        file.extend_from_slice(&[0, 0x10, 0, 8, 0, 0x10, 0, 8, 1, 0, 0, 0]);
+        file.extend_from_slice(b"/bin/a\0/bin/b\0");
+
+        let mut bytes = note(NT_PRSTATUS, &prstatus);
+        bytes.extend(note(0x200, &[1, 2, 3]));
+        bytes.extend(note(NT_FILE, &file));
+
+        // a second table adds to the first
+        let mut file = vec![1, 0, 0, 0, 0, 0x10, 0, 0];
+        file.extend_from_slice(&[0, 0, 0, 9, 0, 0x20, 0, 9, 0, 0, 0, 0]);
+        file.extend_from_slice(b"/bin/c\0");
+        bytes.extend(note(NT_FILE, &file));
+
+        let notes = parse_core_notes(&bytes, Machine::Ia32, false).unwrap();
+
+        assert_eq!(notes.threads.len(), 1);
+        assert_eq!(notes.threads[0].id, 42);
+        assert_eq!(notes.threads[0].pc, 0xdd);
+        assert_eq!(notes.threads[0].reason, Some("SIGSEGV".to_string()));
+        assert_eq!(
+            notes.threads[0].describe(Machine::Ia32),
+            "SIGSEGV in thread 42\nEAX=0x77 EBX=0x11 ECX=0x22 EDX=0x33 ESI=0x44 EDI=0x55 EBP=0x66 ESP=0x1 EIP=0xdd EFLAGS=0xff"
+        );
+        // empty mapping is skipped
+        assert_eq!(
+            notes.files,
+            vec![
+                MappedFile { name: "/bin/a".to_string(), bound: Bound::new(0x8000000, 0x8001000) },
+                MappedFile { name: "/bin/c".to_string(), bound: Bound::new(0x9000000, 0x9002000) },
+            ]
+        );
+    }
+
+    #[test]
+    fn truncated_note() {
+        let mut bytes = note(NT_PRSTATUS, &[0u8; 8]);
+        assert!(parse_core_notes(&bytes, Machine::Amd64, true).is_err());
+        bytes.truncate(16);
+        assert!(parse_core_notes(&bytes, Machine::Amd64, true).is_err());
+    }
+
+    #[test]
+    fn not_minidump() {
+        assert!(!is_minidump(b"\x7fELF"));
+        assert!(parse_minidump(b"MDMP").is_err());
+    }
+}
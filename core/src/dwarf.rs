@@ -0,0 +1,1150 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Reader for DWARF debug information.
+//!
+//! Only the parts of `.debug_info` needed to name functions are read: subprograms with their
+//! address range, the inlined subroutines inside them, formal parameters and local variables.
+//! Variable locations given as a single register or a register/frame base relative offset are
+//! translated into RREIL variable names like `RDI` or `RSP+8`. Everything else (location
+//! lists, composite expressions, types) is ignored. DWARF 5 address and string indices are
+//! resolved through `.debug_addr` and `.debug_str_offsets`, non-contiguous address ranges through
+//! `.debug_rnglists` or `.debug_ranges`.
+
+use crate::{Bound, Machine, Result};
+use byteorder::{ByteOrder, LittleEndian};
+use std::collections::HashMap;
+use std::u64;
+
+/// A function described by a `DW_TAG_subprogram` entry.
+#[derive(Clone,Debug,PartialEq,Eq,Serialize,Deserialize)]
+pub struct DebugFunction {
+    /// Source-level name.
+    pub name: Option<String>,
+    /// Address range covered by the function's code. For functions split into several ranges
+    /// this is the first one.
+    pub bound: Bound,
+    /// Formal parameters, in declaration order.
+    pub parameters: Vec<DebugVariable>,
+    /// Local variables, including those of nested lexical blocks.
+    pub locals: Vec<DebugVariable>,
+    /// Functions inlined into this one.
+    pub inlined: Vec<InlinedSubroutine>,
+}
+
+impl DebugFunction {
+    /// Moves all address ranges by `delta` bytes.
+    pub fn rebase(&mut self, delta: i64) {
+        self.bound = self.bound.shifted(delta);
+        for inlined in self.inlined.iter_mut() {
+            inlined.bound = inlined.bound.as_ref().map(|b| b.shifted(delta));
+        }
+    }
+}
+
+/// A named parameter or variable.
+#[derive(Clone,Debug,PartialEq,Eq,Serialize,Deserialize)]
+pub struct DebugVariable {
+    /// Source-level name.
+    pub name: String,
+    /// Location as RREIL variable name, optionally with an offset (`RDI`, `RSP+8`, `RBP-20`) or
+    /// an absolute address for statics. `None` if the location is not a simple expression.
+    pub location: Option<String>,
+}
+
+/// A `DW_TAG_inlined_subroutine` entry.
+#[derive(Clone,Debug,PartialEq,Eq,Serialize,Deserialize)]
+pub struct InlinedSubroutine {
+    /// Name of the inlined function.
+    pub name: Option<String>,
+    /// Address range of the inlined code, if it is contiguous.
+    pub bound: Option<Bound>,
+}
+
+/// Contents of the DWARF sections besides `.debug_info`.
+#[derive(Clone,Copy,Debug,Default)]
+pub struct Sections<'a> {
+    /// `.debug_abbrev`
+    pub debug_abbrev: &'a [u8],
+    /// `.debug_str`
+    pub debug_str: Option<&'a [u8]>,
+    /// `.debug_addr`, DWARF 5 only.
+    pub debug_addr: Option<&'a [u8]>,
+    /// `.debug_str_offsets`, DWARF 5 only.
+    pub debug_str_offsets: Option<&'a [u8]>,
+    /// `.debug_rnglists`, DWARF 5 only.
+    pub debug_rnglists: Option<&'a [u8]>,
+    /// `.debug_ranges`, DWARF 4 and earlier.
+    pub debug_ranges: Option<&'a [u8]>,
+}
+
+const DW_TAG_FORMAL_PARAMETER: u64 = 0x05;
+const DW_TAG_LEXICAL_BLOCK: u64 = 0x0b;
+const DW_TAG_INLINED_SUBROUTINE: u64 = 0x1d;
+const DW_TAG_SUBPROGRAM: u64 = 0x2e;
+const DW_TAG_VARIABLE: u64 = 0x34;
+
+const DW_AT_LOCATION: u64 = 0x02;
+const DW_AT_NAME: u64 = 0x03;
+const DW_AT_LOW_PC: u64 = 0x11;
+const DW_AT_HIGH_PC: u64 = 0x12;
+const DW_AT_ABSTRACT_ORIGIN: u64 = 0x31;
+const DW_AT_FRAME_BASE: u64 = 0x40;
+const DW_AT_SPECIFICATION: u64 = 0x47;
+const DW_AT_RANGES: u64 = 0x55;
+const DW_AT_STR_OFFSETS_BASE: u64 = 0x72;
+const DW_AT_ADDR_BASE: u64 = 0x73;
+const DW_AT_RNGLISTS_BASE: u64 = 0x74;
+
+const DW_OP_ADDR: u8 = 0x03;
+const DW_OP_REG0: u8 = 0x50;
+const DW_OP_REG31: u8 = 0x6f;
+const DW_OP_BREG0: u8 = 0x70;
+const DW_OP_BREG31: u8 = 0x8f;
+const DW_OP_REGX: u8 = 0x90;
+const DW_OP_FBREG: u8 = 0x91;
+const DW_OP_BREGX: u8 = 0x92;
+const DW_OP_CALL_FRAME_CFA: u8 = 0x9c;
+
+#[derive(Clone,Debug)]
+enum Value<'a> {
+    Address(u64),
+    Constant(u64),
+    Block(&'a [u8]),
+    String(String),
+    Reference(u64),
+    Ranges(Vec<Bound>),
+    // DWARF 5 indices, resolved once the bases in the unit DIE are known
+    AddressIndex(u64),
+    StringIndex(u64),
+    RangeListIndex(u64),
+    SectionOffset(u64),
+    Other,
+}
+
+#[derive(Clone,Debug)]
+struct Abbrev {
+    tag: u64,
+    children: bool,
+    attributes: Vec<(u64, u64, i64)>,
+}
+
+#[derive(Clone,Debug)]
+struct Die<'a> {
+    offset: u64,
+    depth: usize,
+    tag: u64,
+    /// Size of a target address in the unit of this entry.
+    address_size: usize,
+    attributes: Vec<(u64, Value<'a>)>,
+}
+
+impl<'a> Die<'a> {
+    fn get(&self, at: u64) -> Option<&Value<'a>> {
+        self.attributes.iter().find(|&&(a, _)| a == at).map(|&(_, ref v)| v)
+    }
+
+    fn low_pc(&self) -> Option<u64> {
+        match self.get(DW_AT_LOW_PC) {
+            Some(&Value::Address(a)) => Some(a),
+            _ => None,
+        }
+    }
+
+    fn bound(&self) -> Option<Bound> {
+        match (self.low_pc(), self.get(DW_AT_HIGH_PC), self.get(DW_AT_RANGES)) {
+            (Some(low), Some(&Value::Address(high)), _) => Some(Bound::new(low, high)),
+            (Some(low), Some(&Value::Constant(len)), _) => Some(Bound::new(low, low + len)),
+            (_, _, Some(&Value::Ranges(ref ranges))) => ranges.first().cloned(),
+            _ => None,
+        }
+    }
+
+    /// Like `bound` but `None` if the DIE covers more than one range.
+    fn contiguous_bound(&self) -> Option<Bound> {
+        match self.get(DW_AT_RANGES) {
+            Some(&Value::Ranges(ref ranges)) if ranges.len() != 1 => None,
+            _ => self.bound(),
+        }
+    }
+}
+
+/// Header fields and base attributes of the unit currently read.
+struct Unit {
+    offset: u64,
+    version: u16,
+    address_size: usize,
+    offset_size: usize,
+    addr_base: u64,
+    str_offsets_base: u64,
+    rnglists_base: u64,
+    base_address: u64,
+}
+
+impl Unit {
+    fn address(&self, sections: &Sections, index: u64) -> Result<u64> {
+        let addr = sections.debug_addr.ok_or("Missing .debug_addr section")?;
+        let pos = index.checked_mul(self.address_size as u64).and_then(|x| x.checked_add(self.addr_base)).ok_or("DWARF address index overflow")?;
+
+        Reader::new(addr, pos as usize).uint(self.address_size)
+    }
+
+    fn string(&self, sections: &Sections, index: u64) -> Result<Value<'static>> {
+        let offsets = sections.debug_str_offsets.ok_or("Missing .debug_str_offsets section")?;
+        let pos = index.checked_mul(self.offset_size as u64).and_then(|x| x.checked_add(self.str_offsets_base)).ok_or("DWARF string index overflow")?;
+        let offset = Reader::new(offsets, pos as usize).uint(self.offset_size)?;
+
+        Ok(string_at(sections.debug_str, offset))
+    }
+
+    /// Reads the DWARF 5 range list at `offset` into `.debug_rnglists`.
+    fn rnglist(&self, sections: &Sections, offset: u64) -> Result<Vec<Bound>> {
+        let mut rd = Reader::new(sections.debug_rnglists.ok_or("Missing .debug_rnglists section")?, offset as usize);
+        let mut base = self.base_address;
+        let mut ret = vec![];
+
+        loop {
+            let range = match rd.u8()? {
+                // DW_RLE_end_of_list
+                0x00 => return Ok(ret),
+                // DW_RLE_base_addressx
+                0x01 => {
+                    base = self.address(sections, rd.uleb()?)?;
+                    None
+                }
+                // DW_RLE_startx_endx
+                0x02 => {
+                    let start = self.address(sections, rd.uleb()?)?;
+                    Some((start, self.address(sections, rd.uleb()?)?))
+                }
+                // DW_RLE_startx_length
+                0x03 => {
+                    let start = self.address(sections, rd.uleb()?)?;
+                    Some((start, start.wrapping_add(rd.uleb()?)))
+                }
+                // DW_RLE_offset_pair
+                0x04 => {
+                    let start = base.wrapping_add(rd.uleb()?);
+                    Some((start, base.wrapping_add(rd.uleb()?)))
+                }
+                // DW_RLE_base_address
+                0x05 => {
+                    base = rd.uint(self.address_size)?;
+                    None
+                }
+                // DW_RLE_start_end
+                0x06 => {
+                    let start = rd.uint(self.address_size)?;
+                    Some((start, rd.uint(self.address_size)?))
+                }
+                // DW_RLE_start_length
+                0x07 => {
+                    let start = rd.uint(self.address_size)?;
+                    Some((start, start.wrapping_add(rd.uleb()?)))
+                }
+                kind => return Err(format!("Unknown DWARF range list entry {:#x}", kind).into()),
+            };
+
+            if let Some((start, end)) = range {
+                if start < end {
+                    ret.push(Bound::new(start, end));
+                }
+            }
+        }
+    }
+
+    /// Reads the pre DWARF 5 range list at `offset` into `.debug_ranges`.
+    fn ranges(&self, sections: &Sections, offset: u64) -> Result<Vec<Bound>> {
+        let mut rd = Reader::new(sections.debug_ranges.ok_or("Missing .debug_ranges section")?, offset as usize);
+        let max = if self.address_size >= 8 { u64::MAX } else { (1u64 << (self.address_size * 8)) - 1 };
+        let mut base = self.base_address;
+        let mut ret = vec![];
+
+        loop {
+            let start = rd.uint(self.address_size)?;
+            let end = rd.uint(self.address_size)?;
+
+            if start == 0 && end == 0 {
+                return Ok(ret);
+            } else if start == max {
+                base = end;
+            } else if start < end {
+                ret.push(Bound::new(base.wrapping_add(start), base.wrapping_add(end)));
+            }
+        }
+    }
+
+    /// Replaces indices and range list offsets in the value of attribute `at` with what they
+    /// point to.
+    fn resolve<'a>(&self, sections: &Sections, at: u64, value: Value<'a>) -> Value<'a> {
+        let ret = match value {
+            Value::AddressIndex(index) => self.address(sections, index).map(Value::Address),
+            Value::StringIndex(index) => self.string(sections, index),
+            Value::RangeListIndex(index) if at == DW_AT_RANGES => {
+                let pos = index.checked_mul(self.offset_size as u64).and_then(|x| x.checked_add(self.rnglists_base));
+                let rnglists = sections.debug_rnglists.unwrap_or(&[]);
+
+                match pos.map(|pos| Reader::new(rnglists, pos as usize).uint(self.offset_size)) {
+                    Some(Ok(offset)) => self.rnglist(sections, self.rnglists_base + offset).map(Value::Ranges),
+                    Some(Err(e)) => Err(e),
+                    None => Err("DWARF range list index overflow".into()),
+                }
+            }
+            Value::SectionOffset(offset) if at == DW_AT_RANGES && self.version >= 5 => self.rnglist(sections, offset).map(Value::Ranges),
+            Value::SectionOffset(offset) | Value::Constant(offset) if at == DW_AT_RANGES => self.ranges(sections, offset).map(Value::Ranges),
+            value => Ok(value),
+        };
+
+        ret.unwrap_or_else(
+            |e| {
+                debug!("Failed to resolve DWARF attribute {:#x} in unit at {:#x}: {}", at, self.offset, e);
+                Value::Other
+            }
+        )
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8], pos: usize) -> Reader<'a> {
+        Reader { bytes: bytes, pos: pos }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.pos.checked_add(len).map(|e| e <= self.bytes.len()).unwrap_or(false) {
+            let ret = &self.bytes[self.pos..self.pos + len];
+            self.pos += len;
+            Ok(ret)
+        } else {
+            Err(format!("DWARF data truncated at {:#x}", self.pos).into())
+        }
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(LittleEndian::read_u16(self.take(2)?))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(LittleEndian::read_u32(self.take(4)?))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(LittleEndian::read_u64(self.take(8)?))
+    }
+
+    fn uint(&mut self, size: usize) -> Result<u64> {
+        match size {
+            1 => Ok(self.u8()? as u64),
+            2 => Ok(self.u16()? as u64),
+            3 => Ok(LittleEndian::read_uint(self.take(3)?, 3)),
+            4 => Ok(self.u32()? as u64),
+            8 => self.u64(),
+            _ => Err(format!("Unsupported DWARF integer size {}", size).into()),
+        }
+    }
+
+    fn uleb(&mut self) -> Result<u64> {
+        let mut ret = 0u64;
+        let mut shift = 0;
+
+        loop {
+            let b = self.u8()?;
+            if shift < 64 {
+                ret |= ((b & 0x7f) as u64) << shift;
+            }
+            shift += 7;
+            if b & 0x80 == 0 {
+                return Ok(ret);
+            }
+        }
+    }
+
+    fn sleb(&mut self) -> Result<i64> {
+        let mut ret = 0i64;
+        let mut shift = 0;
+
+        loop {
+            let b = self.u8()?;
+            if shift < 64 {
+                ret |= ((b & 0x7f) as i64) << shift;
+            }
+            shift += 7;
+            if b & 0x80 == 0 {
+                if shift < 64 && b & 0x40 != 0 {
+                    ret |= -1i64 << shift;
+                }
+                return Ok(ret);
+            }
+        }
+    }
+
+    fn cstr(&mut self) -> Result<String> {
+        let rest = &self.bytes[self.pos..];
+        match rest.iter().position(|&b| b == 0) {
+            Some(len) => {
+                self.pos += len + 1;
+                Ok(String::from_utf8_lossy(&rest[..len]).into_owned())
+            }
+            None => Err(format!("Unterminated DWARF string at {:#x}", self.pos).into()),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos >= self.bytes.len()
+    }
+}
+
+fn string_at(strtab: Option<&[u8]>, offset: u64) -> Value<'static> {
+    match strtab {
+        Some(strtab) if (offset as usize) < strtab.len() => {
+            Reader::new(strtab, offset as usize).cstr().map(Value::String).unwrap_or(Value::Other)
+        }
+        _ => Value::Other,
+    }
+}
+
+fn parse_abbrevs(bytes: &[u8], offset: u64) -> Result<HashMap<u64, Abbrev>> {
+    let mut rd = Reader::new(bytes, offset as usize);
+    let mut ret = HashMap::new();
+
+    loop {
+        let code = rd.uleb()?;
+        if code == 0 {
+            return Ok(ret);
+        }
+
+        let tag = rd.uleb()?;
+        let children = rd.u8()? != 0;
+        let mut attributes = vec![];
+
+        loop {
+            let name = rd.uleb()?;
+            let form = rd.uleb()?;
+            if name == 0 && form == 0 {
+                break;
+            }
+            // DW_FORM_implicit_const stores its value in the abbreviation
+            let implicit = if form == 0x21 { rd.sleb()? } else { 0 };
+            attributes.push((name, form, implicit));
+        }
+
+        ret.insert(code, Abbrev { tag: tag, children: children, attributes: attributes });
+    }
+}
+
+fn read_value<'a>(rd: &mut Reader<'a>, form: u64, implicit: i64, unit: &Unit, strtab: Option<&[u8]>) -> Result<Value<'a>> {
+    Ok(
+        match form {
+            // DW_FORM_addr
+            0x01 => Value::Address(rd.uint(unit.address_size)?),
+            // DW_FORM_block2, block4, block, block1, exprloc
+            0x03 => {
+                let len = rd.u16()? as usize;
+                Value::Block(rd.take(len)?)
+            }
+            0x04 => {
+                let len = rd.u32()? as usize;
+                Value::Block(rd.take(len)?)
+            }
+            0x09 | 0x18 => {
+                let len = rd.uleb()? as usize;
+                Value::Block(rd.take(len)?)
+            }
+            0x0a => {
+                let len = rd.u8()? as usize;
+                Value::Block(rd.take(len)?)
+            }
+            // DW_FORM_data1, data2, data4, data8
+            0x0b => Value::Constant(rd.u8()? as u64),
+            0x05 => Value::Constant(rd.u16()? as u64),
+            0x06 => Value::Constant(rd.u32()? as u64),
+            0x07 => Value::Constant(rd.u64()?),
+            // DW_FORM_data16
+            0x1e => {
+                rd.take(16)?;
+                Value::Other
+            }
+            // DW_FORM_string
+            0x08 => Value::String(rd.cstr()?),
+            // DW_FORM_flag
+            0x0c => Value::Constant(rd.u8()? as u64),
+            // DW_FORM_flag_present
+            0x19 => Value::Constant(1),
+            // DW_FORM_sdata
+            0x0d => Value::Constant(rd.sleb()? as u64),
+            // DW_FORM_udata
+            0x0f => Value::Constant(rd.uleb()?),
+            // DW_FORM_implicit_const
+            0x21 => Value::Constant(implicit as u64),
+            // DW_FORM_strp
+            0x0e => {
+                let off = rd.uint(unit.offset_size)?;
+                string_at(strtab, off)
+            }
+            // DW_FORM_sec_offset
+            0x17 => Value::SectionOffset(rd.uint(unit.offset_size)?),
+            // DW_FORM_line_strp, strp_sup, ref_sup4
+            0x1f | 0x1d => {
+                rd.uint(unit.offset_size)?;
+                Value::Other
+            }
+            0x1c => {
+                rd.u32()?;
+                Value::Other
+            }
+            // DW_FORM_ref_addr
+            0x10 => {
+                let size = if unit.version <= 2 { unit.address_size } else { unit.offset_size };
+                Value::Reference(rd.uint(size)?)
+            }
+            // DW_FORM_ref1, ref2, ref4, ref8, ref_udata
+            0x11 => Value::Reference(unit.offset + rd.u8()? as u64),
+            0x12 => Value::Reference(unit.offset + rd.u16()? as u64),
+            0x13 => Value::Reference(unit.offset + rd.u32()? as u64),
+            0x14 => Value::Reference(unit.offset + rd.u64()?),
+            0x15 => Value::Reference(unit.offset + rd.uleb()?),
+            // DW_FORM_ref_sig8
+            0x20 => {
+                rd.u64()?;
+                Value::Other
+            }
+            // DW_FORM_indirect
+            0x16 => {
+                let form = rd.uleb()?;
+                read_value(rd, form, 0, unit, strtab)?
+            }
+            // DW_FORM_strx, strx1-4
+            0x1a => Value::StringIndex(rd.uleb()?),
+            0x25 => Value::StringIndex(rd.uint(1)?),
+            0x26 => Value::StringIndex(rd.uint(2)?),
+            0x27 => Value::StringIndex(rd.uint(3)?),
+            0x28 => Value::StringIndex(rd.uint(4)?),
+            // DW_FORM_addrx, addrx1-4
+            0x1b => Value::AddressIndex(rd.uleb()?),
+            0x29 => Value::AddressIndex(rd.uint(1)?),
+            0x2a => Value::AddressIndex(rd.uint(2)?),
+            0x2b => Value::AddressIndex(rd.uint(3)?),
+            0x2c => Value::AddressIndex(rd.uint(4)?),
+            // DW_FORM_rnglistx
+            0x23 => Value::RangeListIndex(rd.uleb()?),
+            // DW_FORM_loclistx
+            0x22 => {
+                rd.uleb()?;
+                Value::Other
+            }
+            _ => return Err(format!("Unknown DWARF form {:#x}", form).into()),
+        }
+    )
+}
+
+fn parse_unit<'a>(bytes: &'a [u8], unit_offset: usize, sections: &Sections, dies: &mut Vec<Die<'a>>) -> Result<usize> {
+    let mut rd = Reader::new(bytes, unit_offset);
+    let (length, offset_size) = match rd.u32()? {
+        0xffffffff => (rd.u64()? as usize, 8),
+        l => (l as usize, 4),
+    };
+    let end = rd.pos.checked_add(length).ok_or("DWARF unit length overflow")?;
+
+    if end > bytes.len() {
+        return Err(format!("DWARF unit at {:#x} runs past the end of .debug_info", unit_offset).into());
+    }
+
+    let version = rd.u16()?;
+    let (abbrev_offset, address_size) = match version {
+        2 | 3 | 4 => {
+            let abbrev_offset = rd.uint(offset_size)?;
+            (abbrev_offset, rd.u8()? as usize)
+        }
+        5 => {
+            let unit_type = rd.u8()?;
+            let address_size = rd.u8()? as usize;
+            let abbrev_offset = rd.uint(offset_size)?;
+
+            // only full and partial compilation units describe code
+            if unit_type != 0x01 && unit_type != 0x03 {
+                return Ok(end);
+            }
+            (abbrev_offset, address_size)
+        }
+        _ => {
+            debug!("Skipping DWARF unit at {:#x} with version {}", unit_offset, version);
+            return Ok(end);
+        }
+    };
+    let abbrevs = parse_abbrevs(sections.debug_abbrev, abbrev_offset)?;
+    let mut rd = Reader::new(&bytes[..end], rd.pos);
+    let mut depth = 0usize;
+    let first = dies.len();
+    // DWARF 5 bases default to just past the section headers
+    let header = if offset_size == 8 { 16 } else { 8 };
+    let mut unit = Unit {
+        offset: unit_offset as u64,
+        version: version,
+        address_size: address_size,
+        offset_size: offset_size,
+        addr_base: header,
+        str_offsets_base: header,
+        rnglists_base: header + 4,
+        base_address: 0,
+    };
+
+    while !rd.is_empty() {
+        let offset = rd.pos as u64;
+        let code = rd.uleb()?;
+
+        if code == 0 {
+            depth = depth.saturating_sub(1);
+            continue;
+        }
+
+        let abbrev = abbrevs.get(&code).ok_or_else(|| format!("Unknown DWARF abbreviation {} at {:#x}", code, offset))?;
+        let mut attributes = Vec::with_capacity(abbrev.attributes.len());
+
+        for &(name, form, implicit) in abbrev.attributes.iter() {
+            match read_value(&mut rd, form, implicit, &unit, sections.debug_str) {
+                Ok(value) => attributes.push((name, value)),
+                // the rest of the unit can't be decoded without knowing the size of the attribute
+                Err(e) => {
+                    debug!("Skipping DWARF unit at {:#x}: {}", unit_offset, e);
+                    dies.truncate(first);
+                    return Ok(end);
+                }
+            }
+        }
+
+        if dies.len() == first {
+            for &(name, ref value) in attributes.iter() {
+                match (name, value) {
+                    (DW_AT_ADDR_BASE, &Value::SectionOffset(base)) => unit.addr_base = base,
+                    (DW_AT_STR_OFFSETS_BASE, &Value::SectionOffset(base)) => unit.str_offsets_base = base,
+                    (DW_AT_RNGLISTS_BASE, &Value::SectionOffset(base)) => unit.rnglists_base = base,
+                    _ => {}
+                }
+            }
+        }
+
+        let attributes = attributes.into_iter().map(|(name, value)| (name, unit.resolve(sections, name, value))).collect::<Vec<_>>();
+
+        if dies.len() == first {
+            // base address of the unit's range lists
+            if let Some(&(_, Value::Address(base))) = attributes.iter().find(|&&(name, _)| name == DW_AT_LOW_PC) {
+                unit.base_address = base;
+            }
+        }
+
+        dies.push(Die { offset: offset, depth: depth, tag: abbrev.tag, address_size: address_size, attributes: attributes });
+        if abbrev.children {
+            depth += 1;
+        }
+    }
+
+    Ok(end)
+}
+
+/// Returns the RREIL name of DWARF register `reg`.
+fn register_name(machine: Machine, reg: u64) -> Option<String> {
+    const AMD64: [&'static str; 17] = [
+        "RAX", "RDX", "RCX", "RBX", "RSI", "RDI", "RBP", "RSP",
+        "R8", "R9", "R10", "R11", "R12", "R13", "R14", "R15", "RIP",
+    ];
+    const IA32: [&'static str; 9] = ["EAX", "ECX", "EDX", "EBX", "ESP", "EBP", "ESI", "EDI", "EIP"];
+
+    match machine {
+        Machine::Amd64 => AMD64.get(reg as usize).map(|x| x.to_string()),
+        Machine::Ia32 => IA32.get(reg as usize).map(|x| x.to_string()),
+        Machine::Avr if reg < 32 => Some(format!("R{}", reg)),
+        Machine::Avr if reg == 32 => Some("SP".to_string()),
+        _ => None,
+    }
+}
+
+fn with_offset(base: String, offset: i64) -> String {
+    if offset > 0 {
+        format!("{}+{}", base, offset)
+    } else if offset < 0 {
+        format!("{}-{}", base, (offset as u64).wrapping_neg())
+    } else {
+        base
+    }
+}
+
+/// Where a `DW_AT_frame_base` expression points, as register and offset.
+fn frame_base(machine: Machine, expr: &[u8]) -> Option<(String, i64)> {
+    let mut rd = Reader::new(expr, 0);
+    let op = rd.u8().ok()?;
+    let ret = match op {
+        // the CFA is the stack pointer before the call pushed the return address
+        DW_OP_CALL_FRAME_CFA => {
+            match machine {
+                Machine::Amd64 => ("RSP".to_string(), 8),
+                Machine::Ia32 => ("ESP".to_string(), 4),
+                _ => return None,
+            }
+        }
+        DW_OP_REG0..=DW_OP_REG31 => (register_name(machine, (op - DW_OP_REG0) as u64)?, 0),
+        DW_OP_BREG0..=DW_OP_BREG31 => (register_name(machine, (op - DW_OP_BREG0) as u64)?, rd.sleb().ok()?),
+        DW_OP_REGX => (register_name(machine, rd.uleb().ok()?)?, 0),
+        DW_OP_BREGX => {
+            let reg = register_name(machine, rd.uleb().ok()?)?;
+            (reg, rd.sleb().ok()?)
+        }
+        _ => return None,
+    };
+
+    if rd.is_empty() { Some(ret) } else { None }
+}
+
+/// Translates a `DW_AT_location` expression into a RREIL variable name.
+fn location(machine: Machine, expr: &[u8], address_size: usize, fb: Option<&(String, i64)>) -> Option<String> {
+    let mut rd = Reader::new(expr, 0);
+    let op = rd.u8().ok()?;
+    let ret = match op {
+        DW_OP_ADDR => format!("{:#x}", rd.uint(address_size).ok()?),
+        DW_OP_REG0..=DW_OP_REG31 => register_name(machine, (op - DW_OP_REG0) as u64)?,
+        DW_OP_REGX => register_name(machine, rd.uleb().ok()?)?,
+        DW_OP_BREG0..=DW_OP_BREG31 => {
+            let reg = register_name(machine, (op - DW_OP_BREG0) as u64)?;
+            with_offset(reg, rd.sleb().ok()?)
+        }
+        DW_OP_BREGX => {
+            let reg = register_name(machine, rd.uleb().ok()?)?;
+            with_offset(reg, rd.sleb().ok()?)
+        }
+        DW_OP_FBREG => {
+            let &(ref reg, off) = fb?;
+            with_offset(reg.clone(), off + rd.sleb().ok()?)
+        }
+        _ => return None,
+    };
+
+    if rd.is_empty() { Some(ret) } else { None }
+}
+
+enum Scope {
+    Function(usize),
+    Block(Option<usize>),
+    Other,
+}
+
+/// Parses all functions described in `debug_info`. `sections` has the contents of the other DWARF
+/// sections of the file. Registers are named after the conventions of `machine`.
+pub fn parse(machine: Machine, debug_info: &[u8], sections: &Sections) -> Result<Vec<DebugFunction>> {
+    let mut dies = vec![];
+    let mut offset = 0;
+
+    while offset < debug_info.len() {
+        offset = parse_unit(debug_info, offset, sections, &mut dies)?;
+    }
+
+    // names of abstract instances and declarations referenced by concrete ones
+    let index = dies.iter().enumerate().map(|(i, d)| (d.offset, i)).collect::<HashMap<u64, usize>>();
+    let name_of = |die: &Die| -> Option<String> {
+        let mut die = die;
+
+        for _ in 0..8 {
+            if let Some(&Value::String(ref s)) = die.get(DW_AT_NAME) {
+                return Some(s.clone());
+            }
+            match die.get(DW_AT_ABSTRACT_ORIGIN).or(die.get(DW_AT_SPECIFICATION)) {
+                Some(&Value::Reference(r)) if index.contains_key(&r) => die = &dies[index[&r]],
+                _ => return None,
+            }
+        }
+        None
+    };
+
+    let mut functions = Vec::<DebugFunction>::new();
+    let mut frame_bases = Vec::<Option<(String, i64)>>::new();
+    let mut scopes = Vec::<(usize, Scope)>::new();
+
+    for die in dies.iter() {
+        while scopes.last().map(|&(d, _)| d >= die.depth).unwrap_or(false) {
+            scopes.pop();
+        }
+
+        let current = match scopes.last() {
+            Some(&(_, Scope::Function(i))) => Some((i, true)),
+            Some(&(_, Scope::Block(Some(i)))) => Some((i, false)),
+            _ => None,
+        };
+
+        let scope = match die.tag {
+            DW_TAG_SUBPROGRAM => {
+                match die.bound() {
+                    Some(bound) => {
+                        let fb = match die.get(DW_AT_FRAME_BASE) {
+                            Some(&Value::Block(expr)) => frame_base(machine, expr),
+                            _ => None,
+                        };
+
+                        functions.push(
+                            DebugFunction {
+                                name: name_of(die),
+                                bound: bound,
+                                parameters: vec![],
+                                locals: vec![],
+                                inlined: vec![],
+                            }
+                        );
+                        frame_bases.push(fb);
+                        Scope::Function(functions.len() - 1)
+                    }
+                    None => Scope::Other,
+                }
+            }
+            DW_TAG_INLINED_SUBROUTINE => {
+                if let Some((i, _)) = current {
+                    functions[i].inlined.push(InlinedSubroutine { name: name_of(die), bound: die.contiguous_bound() });
+                }
+                Scope::Other
+            }
+            DW_TAG_LEXICAL_BLOCK => Scope::Block(current.map(|(i, _)| i)),
+            DW_TAG_FORMAL_PARAMETER | DW_TAG_VARIABLE => {
+                if let (Some((i, direct)), Some(name)) = (current, name_of(die)) {
+                    let loc = match die.get(DW_AT_LOCATION) {
+                        Some(&Value::Block(expr)) => location(machine, expr, die.address_size, frame_bases[i].as_ref()),
+                        _ => None,
+                    };
+                    let var = DebugVariable { name: name, location: loc };
+
+                    if die.tag == DW_TAG_FORMAL_PARAMETER {
+                        if direct {
+                            functions[i].parameters.push(var);
+                        }
+                    } else {
+                        functions[i].locals.push(var);
+                    }
+                }
+                Scope::Other
+            }
+            _ => Scope::Other,
+        };
+
+        scopes.push((die.depth, scope));
+    }
+
+    Ok(functions)
+}
+
+// DWARF pointer encodings used by .eh_frame and .eh_frame_hdr
+const DW_EH_PE_OMIT: u8 = 0xff;
+const DW_EH_PE_PCREL: u8 = 0x10;
+const DW_EH_PE_DATAREL: u8 = 0x30;
+
+/// Reads a pointer encoded as `enc`. `address` is the address of the start of `rd`'s data, `datarel`
+/// the base of data relative pointers. Returns `None` for omitted pointers and pointers relative
+/// to bases we don't know.
+fn read_eh_pointer(rd: &mut Reader, enc: u8, address: u64, datarel: u64, address_size: usize) -> Result<Option<u64>> {
+    if enc == DW_EH_PE_OMIT {
+        return Ok(None);
+    }
+
+    let field = address.wrapping_add(rd.pos as u64);
+    let value = match enc & 0x0f {
+        0x00 => rd.uint(address_size)?,
+        0x01 => rd.uleb()?,
+        0x02 => rd.u16()? as u64,
+        0x03 => rd.u32()? as u64,
+        0x04 => rd.u64()?,
+        0x09 => rd.sleb()? as u64,
+        0x0a => rd.u16()? as i16 as i64 as u64,
+        0x0b => rd.u32()? as i32 as i64 as u64,
+        0x0c => rd.u64()?,
+        f => return Err(format!("Unknown pointer encoding {:#x}", f).into()),
+    };
+    let base = match enc & 0x70 {
+        0 => 0,
+        DW_EH_PE_PCREL => field,
+        DW_EH_PE_DATAREL => datarel,
+        _ => return Ok(None),
+    };
+    // indirect pointers point to the address we want
+    if enc & 0x80 != 0 {
+        return Ok(None);
+    }
+
+    let ret = base.wrapping_add(value);
+    Ok(Some(if address_size < 8 { ret & ((1 << (address_size * 8)) - 1) } else { ret }))
+}
+
+/// Parses a CIE and returns the encoding of the FDE addresses.
+fn parse_cie(rd: &mut Reader, address: u64, address_size: usize) -> Result<u8> {
+    let version = rd.u8()?;
+    let augmentation = rd.cstr()?;
+    let mut enc = 0;
+
+    if augmentation.contains("eh") {
+        rd.take(address_size)?;
+    }
+    // code and data alignment, return address register
+    rd.uleb()?;
+    rd.sleb()?;
+    if version == 1 {
+        rd.u8()?;
+    } else {
+        rd.uleb()?;
+    }
+
+    if augmentation.starts_with('z') {
+        rd.uleb()?;
+        for c in augmentation.chars().skip(1) {
+            match c {
+                'R' => enc = rd.u8()?,
+                'L' => {
+                    rd.u8()?;
+                }
+                'P' => {
+                    let penc = rd.u8()?;
+                    read_eh_pointer(rd, penc & 0x7f, address, 0, address_size)?;
+                }
+                'S' | 'B' => {}
+                _ => break,
+            }
+        }
+    }
+
+    Ok(enc)
+}
+
+/// Parses the contents of an `.eh_frame` section mapped at `address` and returns the address
+/// range of each FDE.
+pub fn parse_eh_frame(bytes: &[u8], address: u64, address_size: usize) -> Result<Vec<Bound>> {
+    let mut cies = HashMap::<usize, u8>::new();
+    let mut ret = vec![];
+    let mut pos = 0;
+
+    while pos + 4 <= bytes.len() {
+        let mut rd = Reader::new(bytes, pos);
+        let (length, is_64) = match rd.u32()? {
+            // terminator
+            0 => {
+                pos += 4;
+                continue;
+            }
+            0xffffffff => (rd.u64()? as usize, true),
+            l => (l as usize, false),
+        };
+        let id_pos = rd.pos;
+        let end = id_pos.checked_add(length).ok_or("CFI entry length overflow")?;
+
+        if end > bytes.len() {
+            return Err(format!("CFI entry at {:#x} runs past the end of .eh_frame", pos).into());
+        }
+
+        let id = if is_64 { rd.u64()? } else { rd.u32()? as u64 };
+        let mut rd = Reader::new(&bytes[..end], rd.pos);
+
+        if id == 0 {
+            cies.insert(pos, parse_cie(&mut rd, address, address_size)?);
+        } else {
+            // FDEs point back to their CIE
+            let enc = id_pos.checked_sub(id as usize).and_then(|cie| cies.get(&cie).cloned());
+
+            match enc {
+                Some(enc) => {
+                    let begin = read_eh_pointer(&mut rd, enc, address, 0, address_size)?;
+                    let len = read_eh_pointer(&mut rd, enc & 0x0f, address, 0, address_size)?;
+
+                    if let (Some(begin), Some(len)) = (begin, len) {
+                        ret.push(Bound::new(begin, begin.wrapping_add(len)));
+                    }
+                }
+                None => debug!("FDE at {:#x} without CIE", pos),
+            }
+        }
+
+        pos = end;
+    }
+
+    Ok(ret)
+}
+
+/// Parses the contents of an `.eh_frame_hdr` section mapped at `address` and returns the start
+/// addresses listed in its binary search table.
+pub fn parse_eh_frame_hdr(bytes: &[u8], address: u64, address_size: usize) -> Result<Vec<u64>> {
+    let mut rd = Reader::new(bytes, 0);
+    let version = rd.u8()?;
+
+    if version != 1 {
+        return Err(format!("Unknown .eh_frame_hdr version {}", version).into());
+    }
+
+    let eh_frame_ptr_enc = rd.u8()?;
+    let fde_count_enc = rd.u8()?;
+    let table_enc = rd.u8()?;
+
+    read_eh_pointer(&mut rd, eh_frame_ptr_enc, address, address, address_size)?;
+
+    let count = match read_eh_pointer(&mut rd, fde_count_enc, address, address, address_size)? {
+        Some(count) if table_enc != DW_EH_PE_OMIT => count,
+        _ => return Ok(vec![]),
+    };
+    let mut ret = vec![];
+
+    for _ in 0..count {
+        let start = read_eh_pointer(&mut rd, table_enc, address, address, address_size)?;
+        read_eh_pointer(&mut rd, table_enc, address, address, address_size)?;
+
+        if let Some(start) = start {
+            ret.push(start);
+        }
+    }
+
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leb128() {
+        let buf = [0xe5, 0x8e, 0x26, 0x7f, 0x80, 0x7f];
+        let mut rd = Reader::new(&buf, 0);
+
+        assert_eq!(rd.uleb().unwrap(), 624485);
+        assert_eq!(rd.sleb().unwrap(), -1);
+        assert_eq!(rd.sleb().unwrap(), -128);
+        assert!(rd.is_empty());
+    }
+
+    #[test]
+    fn eh_frame() {
+        let eh_frame = [
+            // CIE "zR", pcrel|sdata4 FDE addresses
+            0x14, 0, 0, 0, 0, 0, 0, 0, 1, b'z', b'R', 0, 1, 0x78, 0x10, 1, 0x1b, 0x0c, 0x07, 0x08, 0x90, 0x01, 0, 0,
+            // FDE for 0x1000..0x1020
+            0x10, 0, 0, 0, 0x1c, 0, 0, 0, 0xe0, 0x0f, 0, 0, 0x20, 0, 0, 0, 0, 0, 0, 0,
+            // terminator
+            0, 0, 0, 0,
+        ];
+
+        assert_eq!(parse_eh_frame(&eh_frame, 0x0, 8).unwrap(), vec![Bound::new(0x1000, 0x1020)]);
+
+        let hdr = [
+            1, 0x1b, 0x03, 0x3b, 0x10, 0, 0, 0, 1, 0, 0, 0,
+            // datarel|sdata4 table entry for 0x1000
+            0x00, 0x10, 0, 0, 0x30, 0, 0, 0,
+        ];
+
+        assert_eq!(parse_eh_frame_hdr(&hdr, 0x0, 8).unwrap(), vec![0x1000]);
+    }
+
+    #[test]
+    fn dwarf5_indices() {
+        let abbrev = [
+            // compile unit with str_offsets_base, addr_base, rnglists_base and low_pc
+            1, 0x11, 1, 0x72, 0x17, 0x73, 0x17, 0x74, 0x17, 0x11, 0x01, 0, 0,
+            // subprogram with strx1 name, addrx1 low_pc and data4 high_pc
+            2, 0x2e, 0, 0x03, 0x25, 0x11, 0x29, 0x12, 0x06, 0, 0,
+            // subprogram with strx1 name and rnglistx ranges
+            3, 0x2e, 0, 0x03, 0x25, 0x55, 0x23, 0, 0,
+            0,
+        ];
+        let info = [
+            40, 0, 0, 0, 5, 0, 1, 8, 0, 0, 0, 0,
+            1, 8, 0, 0, 0, 8, 0, 0, 0, 12, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+            2, 0, 0, 0x1a, 0, 0, 0,
+            3, 1, 0,
+            0,
+        ];
+        let str_offsets = [12, 0, 0, 0, 5, 0, 0, 0, 0, 0, 0, 0, 4, 0, 0, 0];
+        let addr = [20, 0, 0, 0, 5, 0, 8, 0, 0x00, 0x10, 0, 0, 0, 0, 0, 0, 0x00, 0x20, 0, 0, 0, 0, 0, 0];
+        let rnglists = [
+            33, 0, 0, 0, 5, 0, 8, 0, 1, 0, 0, 0, 4, 0, 0, 0,
+            // DW_RLE_startx_length, DW_RLE_start_end, DW_RLE_end_of_list
+            0x03, 1, 0x10,
+            0x06, 0x00, 0x30, 0, 0, 0, 0, 0, 0, 0x08, 0x30, 0, 0, 0, 0, 0, 0,
+            0x00,
+        ];
+        let sections = Sections {
+            debug_abbrev: &abbrev,
+            debug_str: Some(b"add\0split\0"),
+            debug_addr: Some(&addr),
+            debug_str_offsets: Some(&str_offsets),
+            debug_rnglists: Some(&rnglists),
+            debug_ranges: None,
+        };
+        let funcs = parse(Machine::Amd64, &info, &sections).unwrap();
+
+        assert_eq!(funcs.len(), 2);
+        assert_eq!(funcs[0].name, Some("add".to_string()));
+        assert_eq!(funcs[0].bound, Bound::new(0x1000, 0x101a));
+        assert_eq!(funcs[1].name, Some("split".to_string()));
+        assert_eq!(funcs[1].bound, Bound::new(0x2000, 0x2010));
+    }
+
+    #[test]
+    fn unknown_form() {
+        let abbrev = [
+            // subprogram with string name, addr low_pc and data4 high_pc
+            1, 0x2e, 1, 0x03, 0x08, 0x11, 0x01, 0x12, 0x06, 0, 0,
+            // subprogram with a name of unknown form
+            2, 0x2e, 0, 0x03, 0x7f, 0, 0,
+            // variable with string name and exprloc location
+            3, 0x34, 0, 0x03, 0x08, 0x02, 0x18, 0, 0,
+            0,
+        ];
+        let info = [
+            // DWARF 4 unit the parser can't decode
+            9, 0, 0, 0, 4, 0, 0, 0, 0, 0, 8,
+            2, 0xaa,
+            // DWARF 4 unit with 32 bit addresses
+            28, 0, 0, 0, 4, 0, 0, 0, 0, 0, 4,
+            1, b'f', 0, 0x00, 0x10, 0, 0, 0x10, 0, 0, 0,
+            3, b'v', 0, 5, DW_OP_ADDR, 0x00, 0x20, 0, 0,
+            0,
+        ];
+        let sections = Sections {
+            debug_abbrev: &abbrev,
+            debug_str: None,
+            debug_addr: None,
+            debug_str_offsets: None,
+            debug_rnglists: None,
+            debug_ranges: None,
+        };
+        let funcs = parse(Machine::Amd64, &info, &sections).unwrap();
+
+        assert_eq!(funcs.len(), 1);
+        assert_eq!(funcs[0].name, Some("f".to_string()));
+        assert_eq!(funcs[0].bound, Bound::new(0x1000, 0x1010));
+        assert_eq!(funcs[0].locals, vec![DebugVariable { name: "v".to_string(), location: Some("0x2000".to_string()) }]);
+    }
+
+    #[test]
+    fn locations() {
+        let cfa = frame_base(Machine::Amd64, &[DW_OP_CALL_FRAME_CFA]);
+
+        assert_eq!(cfa, Some(("RSP".to_string(), 8)));
+        assert_eq!(location(Machine::Amd64, &[DW_OP_REG0 + 5], 8, None), Some("RDI".to_string()));
+        assert_eq!(location(Machine::Amd64, &[DW_OP_FBREG, 0x00], 8, cfa.as_ref()), Some("RSP+8".to_string()));
+        assert_eq!(location(Machine::Amd64, &[DW_OP_FBREG, 0x6c], 8, cfa.as_ref()), Some("RSP-12".to_string()));
+        assert_eq!(location(Machine::Ia32, &[DW_OP_BREG0 + 5, 0x08], 4, None), Some("EBP+8".to_string()));
+        assert_eq!(location(Machine::Avr, &[DW_OP_REG0 + 24], 4, None), Some("R24".to_string()));
+        assert_eq!(location(Machine::Amd64, &[DW_OP_REG0, DW_OP_REG0], 8, None), None);
+        assert_eq!(location(Machine::Amd64, &[DW_OP_FBREG, 0x08], 8, None), None);
+    }
+}
@@ -28,13 +28,14 @@
 //! on the front-end.
 
 
-use crate::{Architecture, BasicBlock, Guard, Mnemonic, Operation, Region, Result, Rvalue, Statement};
+use crate::{Architecture, BasicBlock, Bound, DebugFunction, Guard, Mnemonic, Operation, Region, Result, Rvalue, Statement};
 
 use panopticon_graph_algos::{AdjacencyList, EdgeListGraphTrait, GraphTrait, MutableGraphTrait, VertexListGraphTrait};
 use panopticon_graph_algos::adjacency_list::{AdjacencyListEdgeDescriptor, AdjacencyListVertexDescriptor, VertexLabelIterator};
 use panopticon_graph_algos::search::{TraversalOrder, TreeIterator};
 use std::borrow::Cow;
 use std::collections::{BTreeMap, HashMap, HashSet};
+use std::u64;
 use uuid::Uuid;
 
 /// An iterator over every BasicBlock in a Function
@@ -96,6 +97,21 @@ pub enum FunctionKind {
     }
 }
 
+/// Settings of a disassembler run.
+#[derive(Clone,Debug,Default,PartialEq,Eq,Serialize,Deserialize)]
+pub struct DisassemblyOptions {
+    /// Address range the function is declared to span, e.g. by its symbol.
+    #[serde(default)]
+    pub extent: Option<Bound>,
+    /// Treat jumps leaving `extent` as tail calls into other functions and do not follow them.
+    #[serde(default)]
+    pub stop_at_extent: bool,
+    /// Disassemble code outside of executable segments, e.g. code that is copied to the heap
+    /// before it runs.
+    #[serde(default)]
+    pub ignore_permissions: bool,
+}
+
 /// A set of basic blocks connected by conditional jumps
 #[derive(Serialize,Deserialize,Debug,Clone)]
 pub struct Function {
@@ -114,6 +130,12 @@ pub struct Function {
     size: usize,
     /// What kind of function is this
     kind: FunctionKind,
+    /// Source-level description from the binary's debug information
+    #[serde(default)]
+    debug_info: Option<DebugFunction>,
+    /// Options the function was disassembled with, reused by `cont` and `reload`
+    #[serde(default)]
+    options: DisassemblyOptions,
 }
 
 #[derive(Clone,PartialEq,Eq,Debug)]
@@ -122,6 +144,16 @@ enum MnemonicOrError {
     Error(u64, Cow<'static, str>),
 }
 
+/// Adds `delta` to `rv` if it's a constant `is_address` accepts, keeping its size.
+fn rebase_constant(rv: &mut Rvalue, delta: i64, is_address: &Fn(u64) -> bool) {
+    if let &mut Rvalue::Constant { ref mut value, size } = rv {
+        if is_address(*value) {
+            let mask = if size >= 64 { u64::MAX } else { (1 << size) - 1 };
+            *value = value.wrapping_add(delta as u64) & mask;
+        }
+    }
+}
+
 impl Function {
     /// Create an undefined Function. This function has undefined behavior. Creating an undefined Function always succeeds, and is usually a bad idea. Don't do it unless you know what you're doing.
     pub fn undefined(start: u64, uuid: Option<Uuid>, region: &Region, name: Option<String>) -> Function {
@@ -137,11 +169,20 @@ impl Function {
             region: region.name().clone(),
             size: 0,
             kind: FunctionKind::Regular,
+            debug_info: None,
+            options: DisassemblyOptions::default(),
         }
     }
     // this private method is where the meat of making a function is;
     // almost all perf gains for function disassembly will be in here, and related functions like, assemble_cflow_graph, etc.
-    fn disassemble<A: Architecture>(start: u64, cflow_graph: &mut ControlFlowGraph, size: &mut usize, name: &str, uuid: &Uuid, region: &Region, init: A::Configuration) -> Result<ControlFlowRef> {
+    // returns the new entry point and the number of bytes decoded
+    fn disassemble<A: Architecture>(start: u64, cflow_graph: &mut ControlFlowGraph, name: &str, uuid: &Uuid, region: &Region, init: A::Configuration, options: &DisassemblyOptions) -> Result<(ControlFlowRef, usize)> {
+        let mut size = 0;
+
+        if !options.ignore_permissions && !region.is_executable(start) {
+            return Err(format!("function ({}) starts at {:#x}, outside of executable memory", name, start).into());
+        }
+
         let (mut mnemonics, mut by_source, mut by_destination) = Self::index_cflow_graph(cflow_graph, start);
 
         let mut todo = cflow_graph.vertex_labels().filter_map(|lb| {
@@ -159,6 +200,12 @@ impl Function {
 
             assert!(todo.remove(&addr));
 
+            if let (true, Some(extent)) = (options.stop_at_extent, options.extent.as_ref()) {
+                if addr != start && (addr < extent.start || addr >= extent.end) {
+                    continue;
+                }
+            }
+
             if let Some(mnes) = maybe_mnes {
                 if !mnes.is_empty() {
                     match mnes.first() {
@@ -167,7 +214,7 @@ impl Function {
                                 mnemonics.entry(addr).or_insert(Vec::new()).push(MnemonicOrError::Error(addr, "Jump inside instruction".into()));
                                 continue;
                             } else if mne.area.start == addr {
-                                *size += mne.size();
+                                size += mne.size();
                                 continue;
                             }
                         }
@@ -181,6 +228,12 @@ impl Function {
                 }
             }
 
+            // e.g. a computed jump into a data segment
+            if !options.ignore_permissions && !region.is_executable(addr) {
+                mnemonics.entry(addr).or_insert(Vec::new()).push(MnemonicOrError::Error(addr, "Not in executable memory".into()));
+                continue;
+            }
+
             let maybe_match = A::decode(region, addr, &init);
 
             match maybe_match {
@@ -195,7 +248,7 @@ impl Function {
                                 mne.opcode,
                                 match_st.tokens
                             );
-                            *size += mne.size();
+                            size += mne.size();
                             mnemonics.entry(mne.area.start).or_insert(Vec::new()).push(MnemonicOrError::Mnemonic(mne));
                         }
                     }
@@ -234,7 +287,7 @@ impl Function {
         match ep {
             Some(entry_point) => {
                 *cflow_graph = cfg;
-                Ok(entry_point)
+                Ok((entry_point, size))
             },
             None => {
                 Err(format!("function ({}) {} has no entry point", name, uuid).into())
@@ -243,19 +296,45 @@ impl Function {
     }
     /// Continue disassembling from `start`, at `region`, with CPU `configuration`, using the functions current, internal control flow graph.
     pub fn cont<A: Architecture>(&mut self, start: u64, region: &Region, configuration: A::Configuration) -> Result<()> {
-        self.entry_point = Self::disassemble::<A>(start, &mut self.cflow_graph, &mut self.size, &self.name, &self.uuid, region, configuration)?;
+        let (entry_point, size) = Self::disassemble::<A>(start, &mut self.cflow_graph, &self.name, &self.uuid, region, configuration, &self.options)?;
+
+        self.entry_point = entry_point;
+        self.size += size;
+        Ok(())
+    }
+
+    /// Disassembles the function again starting at its entry point, e.g. after the bytes in `region`
+    /// changed. The old control flow graph is discarded. Name, UUID, aliases, kind, debug
+    /// information and disassembly options are kept.
+    pub fn reload<A: Architecture>(&mut self, region: &Region, configuration: A::Configuration) -> Result<()> {
+        let start = self.start();
+        let mut cflow_graph = AdjacencyList::new();
+
+        cflow_graph.add_vertex(ControlFlowTarget::Unresolved(Rvalue::new_u64(start)));
+        let (entry_point, size) = Self::disassemble::<A>(start, &mut cflow_graph, &self.name, &self.uuid, region, configuration, &self.options)?;
+
+        self.entry_point = entry_point;
+        self.cflow_graph = cflow_graph;
+        self.size = size;
         Ok(())
     }
 
     /// Create and start disassembling a new function with `name`, inside memory `region`, starting at entry point `start`, with a random UUID.
+    /// Fails if `start` is not executable, see `Region::is_executable` and
+    /// `DisassemblyOptions::ignore_permissions`.
     pub fn new<A: Architecture>(start: u64, region: &Region, name: Option<String>, init: A::Configuration) -> Result<Function> {
+        Self::with_options::<A>(start, &Uuid::new_v4(), region, name, init, &DisassemblyOptions::default())
+    }
+
+    /// New function starting at `start`, with name `name`, inside memory region `region` and UUID
+    /// `uuid`, disassembled using `options`. The function keeps `options` for `cont` and `reload`.
+    pub fn with_options<A: Architecture>(start: u64, uuid: &Uuid, region: &Region, name: Option<String>, init: A::Configuration, options: &DisassemblyOptions) -> Result<Function> {
         let mut cflow_graph = AdjacencyList::new();
         let entry_point = ControlFlowTarget::Unresolved(Rvalue::new_u64(start));
         cflow_graph.add_vertex(entry_point);
-        let mut size = 0;
         let name = name.unwrap_or(format!("func_{:#x}", start));
-        let uuid = Uuid::new_v4();
-        let entry_point = Self::disassemble::<A>(start, &mut cflow_graph, &mut size, &name, &uuid, region, init)?;
+        let uuid = uuid.clone();
+        let (entry_point, size) = Self::disassemble::<A>(start, &mut cflow_graph, &name, &uuid, region, init, options)?;
         Ok(Function {
             name,
             aliases: Vec::new(),
@@ -265,6 +344,8 @@ impl Function {
             region: region.name().clone(),
             size,
             kind: FunctionKind::Regular,
+            debug_info: None,
+            options: options.clone(),
         })
     }
 
@@ -294,9 +375,7 @@ impl Function {
 
     /// New function starting at `start`, with name `name`, inside memory region `region` and UUID `uuid`.
     pub fn with_uuid<A: Architecture>(start: u64, uuid: &Uuid, region: &Region, name: Option<String>, init: A::Configuration) -> Result<Function> {
-        let mut f = Function::new::<A>(start, region, name, init)?;
-        f.uuid = uuid.clone();
-        Ok(f)
+        Self::with_options::<A>(start, uuid, region, name, init, &DisassemblyOptions::default())
     }
 
     /// Returns the UUID of this function
@@ -327,6 +406,72 @@ impl Function {
         self.kind = FunctionKind::Stub { name: name.to_string(), plt_address };
     }
 
+    /// Moves the function by `delta` bytes without disassembling it again. Constants in operands
+    /// and RREIL code of a mnemonic are adjusted if `is_relocated` returns true for the
+    /// mnemonic's area before the move and the constant. Call and jump targets are code addresses
+    /// and always moved.
+    pub fn rebase(&mut self, delta: i64, is_relocated: &Fn(&Bound, u64) -> bool) {
+        for ct in self.cflow_graph.vertex_labels_mut() {
+            match ct {
+                &mut ControlFlowTarget::Resolved(ref mut bb) => {
+                    bb.area = bb.area.shifted(delta);
+
+                    for mne in bb.mnemonics.iter_mut() {
+                        let targets = mne.instructions
+                            .iter()
+                            .filter_map(
+                                |stmt| match &stmt.op {
+                                    &Operation::Call(Rvalue::Constant { value, .. }) => Some(value),
+                                    _ => None,
+                                }
+                            )
+                            .collect::<Vec<u64>>();
+                        let area = mne.area.clone();
+                        let is_address = |value: u64| targets.contains(&value) || is_relocated(&area, value);
+
+                        mne.area = mne.area.shifted(delta);
+
+                        for op in mne.operands.iter_mut() {
+                            rebase_constant(op, delta, &is_address);
+                        }
+                        for stmt in mne.instructions.iter_mut() {
+                            for op in stmt.op.operands_mut() {
+                                rebase_constant(op, delta, &is_address);
+                            }
+                        }
+                    }
+                }
+                &mut ControlFlowTarget::Unresolved(ref mut rv) => rebase_constant(rv, delta, &|_| true),
+                &mut ControlFlowTarget::Failed(ref mut addr, _) => *addr = addr.wrapping_add(delta as u64),
+            }
+        }
+
+        if let FunctionKind::Stub { ref mut plt_address, .. } = self.kind {
+            *plt_address = plt_address.wrapping_add(delta as u64);
+        }
+        if let Some(ref mut info) = self.debug_info {
+            info.rebase(delta);
+        }
+        if let Some(ref mut extent) = self.options.extent {
+            *extent = extent.shifted(delta);
+        }
+    }
+
+    /// Returns the address range declared by the function's symbol, if any.
+    pub fn extent(&self) -> Option<&Bound> {
+        self.options.extent.as_ref()
+    }
+
+    /// Returns the debug information attached to this function, if any
+    pub fn debug_info(&self) -> Option<&DebugFunction> {
+        self.debug_info.as_ref()
+    }
+
+    /// Attaches the source-level description `info` to this function
+    pub fn set_debug_info(&mut self, info: DebugFunction) {
+        self.debug_info = Some(info);
+    }
+
     /// Returns this functions FunctionKind
     pub fn kind(&self) -> &FunctionKind {
         &self.kind
@@ -747,7 +892,8 @@ impl Function {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{Architecture, BasicBlock, Bound, Disassembler, Guard, Match, Mnemonic, OpaqueLayer, Region, Result, Rvalue, State};
+    use crate::{Architecture, BasicBlock, Bound, Disassembler, Guard, Match, Mnemonic, OpaqueLayer, Region, Result, Rvalue, Segment, State};
+    use crate::region::{SEGMENT_EXECUTE, SEGMENT_READ, SEGMENT_WRITE};
     use panopticon_graph_algos::{AdjacencyMatrixGraphTrait, EdgeListGraphTrait, VertexListGraphTrait};
     use panopticon_graph_algos::{GraphTrait, MutableGraphTrait};
     use std::borrow::Cow;
@@ -958,6 +1104,69 @@ mod tests {
         assert_eq!(func.name, "func_0x0".to_string());
     }
 
+    #[test]
+    fn non_executable() {
+        let main = new_disassembler!(TestArchShort =>
+            [ "........" ] = |st: &mut State<TestArchShort>| {
+                let next = st.address;
+                st.mnemonic(1,"A","",vec!(),&|_| { Ok(vec![]) }).unwrap();
+                st.jump(Rvalue::new_u64(next + 1),Guard::always()).unwrap();
+                true
+            }
+        );
+        let data = OpaqueLayer::wrap(vec![0, 1, 2, 3, 4, 5]);
+        let mut reg = Region::new("".to_string(), data);
+
+        assert!(reg.add_segment(Segment { name: Some(".text".to_string()), bound: Bound::new(0, 3), flags: SEGMENT_READ | SEGMENT_EXECUTE }));
+        assert!(reg.add_segment(Segment { name: Some(".data".to_string()), bound: Bound::new(3, 6), flags: SEGMENT_READ | SEGMENT_WRITE }));
+
+        let func = Function::new::<TestArchShort>(0, &reg, None, main.clone()).unwrap();
+
+        assert_eq!(func.basic_blocks().map(|bb| bb.area.end).max(), Some(3));
+        assert!(Function::new::<TestArchShort>(4, &reg, None, main.clone()).is_err());
+
+        let options = DisassemblyOptions { ignore_permissions: true, ..DisassemblyOptions::default() };
+        let mut func = Function::with_options::<TestArchShort>(0, &Uuid::new_v4(), &reg, None, main.clone(), &options).unwrap();
+
+        assert_eq!(func.basic_blocks().map(|bb| bb.area.end).max(), Some(6));
+        assert!(Function::with_options::<TestArchShort>(4, &Uuid::new_v4(), &reg, None, main.clone(), &options).is_ok());
+
+        // reloading keeps the options
+        func.reload::<TestArchShort>(&reg, main).unwrap();
+        assert_eq!(func.basic_blocks().map(|bb| bb.area.end).max(), Some(6));
+    }
+
+    #[test]
+    fn symbol_extent() {
+        let main = new_disassembler!(TestArchShort =>
+            [ "........" ] = |st: &mut State<TestArchShort>| {
+                let next = st.address;
+                st.mnemonic(1,"A","",vec!(),&|_| { Ok(vec![]) }).unwrap();
+                st.jump(Rvalue::new_u64(next + 1),Guard::always()).unwrap();
+                true
+            }
+        );
+        let data = OpaqueLayer::wrap(vec![0, 1, 2, 3, 4, 5]);
+        let reg = Region::new("".to_string(), data);
+        let options = DisassemblyOptions { extent: Some(Bound::new(0, 3)), ..DisassemblyOptions::default() };
+        let func = Function::with_options::<TestArchShort>(0, &Uuid::new_v4(), &reg, None, main.clone(), &options).unwrap();
+
+        assert_eq!(func.end(), 6);
+        assert_eq!(func.extent(), Some(&Bound::new(0, 3)));
+
+        let options = DisassemblyOptions { extent: Some(Bound::new(0, 3)), stop_at_extent: true, ..DisassemblyOptions::default() };
+        let func = Function::with_options::<TestArchShort>(0, &Uuid::new_v4(), &reg, None, main, &options).unwrap();
+
+        assert_eq!(func.end(), 3);
+        assert_eq!(func.len(), 3);
+        assert_eq!(func.extent(), Some(&Bound::new(0, 3)));
+        assert!(
+            func.cflow_graph
+                .vertex_labels()
+                .any(|ct| if let &ControlFlowTarget::Unresolved(Rvalue::Constant { value: 3, .. }) = ct { true } else { false })
+        );
+    }
+
     #[test]
     fn continuous() {
         let main = new_disassembler!(TestArchShort =>
@@ -42,7 +42,7 @@
 //! A RREIL program modeling the AVR "adc rd, rr" instruction looks as this:
 //!
 //! ```rreil
-//! zext carry:8, C:1
+//! convert_8 carry:8, C:1
 //! add res:8, rd:8, rr:8
 //! add res:8, res:8, carry:8
 //!
@@ -105,13 +105,34 @@
 //! # inner();
 //! # }
 //! ```
+//!
+//! RREIL code in textual form, for example hand-written stubs or the output of `Display`, is
+//! read with `parse_statements`.
+//!
+//! ```
+//! # use panopticon_core::il::parse_statements;
+//! let stmts = parse_statements("
+//!     convert_8 carry:8, C:1
+//!     add res:8, rd:8, rr:8
+//!     add res:8, res:8, carry:8
+//!
+//!     // zero flag
+//!     cmpeq Z:1, res:8, 0:8
+//! ").unwrap();
+//!
+//! assert_eq!(stmts.len(), 4);
+//! assert_eq!(format!("{}", stmts[3]), "cmpeq Z:1, res:8, 0x0:8");
+//! ```
 
-use crate::Result;
+use crate::{BasicBlock, ControlFlowTarget, Function, Result};
+use crate::wide;
+use panopticon_graph_algos::{EdgeListGraphTrait, GraphTrait, VertexListGraphTrait};
 use quickcheck::{Arbitrary, Gen};
 use serde::{Serialize,Deserialize};
 
 use std::borrow::Cow;
 use std::cmp;
+use std::collections::HashMap;
 use std::convert::From;
 use std::fmt::{Display, Error, Formatter, Debug};
 use std::num::Wrapping;
@@ -142,6 +163,13 @@ pub enum Rvalue {
         /// Size in bits
         size: usize,
     },
+    /// Constant whose value does not fit into 64 bits. Use `Rvalue::new_wide` to create one.
+    Wide {
+        /// Value as 64 bit words, least significant word first.
+        words: Vec<u64>,
+        /// Size in bits
+        size: usize,
+    },
 }
 
 impl Rvalue {
@@ -170,15 +198,52 @@ impl Rvalue {
         Rvalue::Constant { value: v, size: 64 }
     }
 
+    /// Returns a new constant of size `size`. The value is given as 64 bit words, least significant
+    /// word first, and is truncated to `size` bits. Values fitting into 64 bits are returned as
+    /// `Rvalue::Constant`, all others as `Rvalue::Wide`.
+    pub fn new_wide(mut words: Vec<u64>, size: usize) -> Rvalue {
+        words.resize((size + 63) / 64, 0);
+        if size % 64 != 0 {
+            if let Some(w) = words.last_mut() {
+                *w &= (1u64 << (size % 64)) - 1;
+            }
+        }
+
+        if words.iter().skip(1).all(|&w| w == 0) {
+            Rvalue::Constant { value: words.first().cloned().unwrap_or(0), size: size }
+        } else {
+            Rvalue::Wide { words: words, size: size }
+        }
+    }
+
     /// Returns the size of the value in bits or None if its undefined.
     pub fn size(&self) -> Option<usize> {
         match self {
             &Rvalue::Constant { ref size, .. } => Some(*size),
+            &Rvalue::Wide { ref size, .. } => Some(*size),
             &Rvalue::Variable { ref size, .. } => Some(*size),
             &Rvalue::Undefined => None,
         }
     }
 
+    /// Returns the value of a constant as hexadecimal number with `0x` prefix and without leading
+    /// zeros, or None if this is a variable or undefined.
+    pub fn hex_digits(&self) -> Option<String> {
+        match self {
+            &Rvalue::Constant { value, .. } => Some(format!("0x{:x}", value)),
+            &Rvalue::Wide { ref words, .. } => {
+                let mut iter = words.iter().rev().skip_while(|&&w| w == 0);
+                let mut ret = format!("0x{:x}", iter.next().cloned().unwrap_or(0));
+
+                for w in iter {
+                    ret += &format!("{:016x}", w);
+                }
+                Some(ret)
+            }
+            &Rvalue::Variable { .. } | &Rvalue::Undefined => None,
+        }
+    }
+
     /// Returns a new Rvalue with the first `s` starting at `o`.
     pub fn extract(&self, s: usize, o: usize) -> Result<Rvalue> {
         if s <= 0 {
@@ -186,9 +251,9 @@ impl Rvalue {
         }
 
         match self {
-            &Rvalue::Constant { ref size, ref value } => {
+            &Rvalue::Constant { ref size, .. } | &Rvalue::Wide { ref size, .. } => {
                 if *size >= s + o {
-                    Ok(Rvalue::Constant { size: s, value: (*value >> o) % (1 << s) })
+                    Ok(wide::extract(self, s, o).unwrap())
                 } else {
                     Err("Rvalue::extract: invalid argument".into())
                 }
@@ -254,6 +319,7 @@ impl Display for Rvalue {
         match self {
             &Rvalue::Undefined => f.write_str("?"),
             &Rvalue::Constant { value: v, size: s } => f.write_fmt(format_args!("0x{:x}:{}", v, s)),
+            &Rvalue::Wide { size: s, .. } => f.write_fmt(format_args!("{}:{}", self.hex_digits().unwrap_or_default(), s)),
             &Rvalue::Variable { ref name, ref subscript, ref offset, ref size } => {
                 f.write_str(name)?;
                 if let &Some(ss) = subscript {
@@ -634,6 +700,10 @@ impl Statement {
 
 /// Executes a RREIL operation returning the result.
 pub fn execute(op: Operation<Rvalue>) -> Rvalue {
+    if let Some(rv) = wide::execute(&op) {
+        return rv;
+    }
+
     match op {
         Operation::Add(Rvalue::Constant { value: _a, size: s }, Rvalue::Constant { value: _b, size: _s }) => {
             debug_assert!(s == _s);
@@ -988,7 +1058,7 @@ pub fn execute(op: Operation<Rvalue>) -> Rvalue {
                 size: s,
             }
         }
-        Operation::ZeroExtend(_, Rvalue::Undefined) => Rvalue::Undefined,
+        Operation::ZeroExtend(_, _) => Rvalue::Undefined,
 
         Operation::SignExtend(t, Rvalue::Constant { value: v, size: s, .. }) => {
             let mask0 = if s < 64 { (1u64 << s) - 1 } else { u64::MAX };
@@ -1010,7 +1080,7 @@ pub fn execute(op: Operation<Rvalue>) -> Rvalue {
                 size: s,
             }
         }
-        Operation::SignExtend(_, Rvalue::Undefined) => Rvalue::Undefined,
+        Operation::SignExtend(_, _) => Rvalue::Undefined,
 
         Operation::Move(Rvalue::Constant { ref value, ref size }) => {
             if *size < 64 {
@@ -1210,13 +1280,611 @@ impl Display for Statement {
 
             Operation::Phi(ref vec) => {
                 f.write_fmt(format_args!("phi {}", self.assignee))?;
-                for (i, x) in vec.iter().enumerate() {
-                    f.write_fmt(format_args!("{}", x))?;
-                    if i < vec.len() - 1 {
+                for x in vec.iter() {
+                    f.write_fmt(format_args!(", {}", x))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Parses a RREIL listing into a sequence of statements.
+///
+/// The listing has one statement per line in the syntax `Display` emits for `Statement`. Empty
+/// lines and everything after `//` are ignored. Constants may be decimal or hexadecimal with a `0x`
+/// prefix. A trailing `_<n>` of a variable name is read as SSA subscript `n`. Errors are prefixed
+/// with the line and column they occurred at.
+pub fn parse_statements(text: &str) -> Result<Vec<Statement>> {
+    let mut ret = vec![];
+
+    for (idx, line) in text.lines().enumerate() {
+        let line = match line.find("//") {
+            Some(p) => &line[..p],
+            None => line,
+        };
+        let mut parser = StatementParser { line: idx + 1, text: line, pos: 0 };
+
+        parser.skip_whitespace();
+        if !parser.at_end() {
+            ret.push(parser.statement()?);
+        }
+    }
+
+    Ok(ret)
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '.'
+}
+
+fn parse_number(s: &str) -> Option<u64> {
+    if s.starts_with("0x") {
+        u64::from_str_radix(&s[2..], 16).ok()
+    } else {
+        u64::from_str(s).ok()
+    }
+}
+
+/// Parses hexadecimal numbers of arbitrary length into 64 bit words, least significant first.
+fn parse_wide_number(s: &str) -> Option<Vec<u64>> {
+    if !s.starts_with("0x") || s.len() == 2 || !s[2..].chars().all(|c| c.is_digit(16)) {
+        return None;
+    }
+
+    let digits = s[2..].as_bytes();
+    let words = digits
+        .rchunks(16)
+        .map(|c| u64::from_str_radix(::std::str::from_utf8(c).unwrap(), 16).unwrap())
+        .collect();
+
+    Some(words)
+}
+
+/// Splits `foo_12` into `foo` and SSA subscript 12.
+fn split_subscript(s: &str) -> (&str, Option<usize>) {
+    if let Some(p) = s.rfind('_') {
+        if p > 0 && p + 1 < s.len() && s[p + 1..].chars().all(|c| c.is_ascii_digit()) {
+            if let Ok(ss) = usize::from_str(&s[p + 1..]) {
+                return (&s[..p], Some(ss));
+            }
+        }
+    }
+
+    (s, None)
+}
+
+/// Recursive descent parser for a single line of RREIL.
+struct StatementParser<'a> {
+    line: usize,
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> StatementParser<'a> {
+    fn error<T>(&self, pos: usize, msg: String) -> Result<T> {
+        let col = self.text[..pos].chars().count() + 1;
+        Err(format!("{}:{}: {}", self.line, col, msg).into())
+    }
+
+    fn unexpected<T>(&self, what: &str) -> Result<T> {
+        match self.rest().chars().next() {
+            Some(c) => self.error(self.pos, format!("Expected {}, found '{}'", what, c)),
+            None => self.error(self.pos, format!("Expected {}, found end of line", what)),
+        }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+
+    fn at_end(&self) -> bool {
+        self.rest().is_empty()
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    /// Consumes the longest prefix made of characters `f` accepts. Returns its start and the prefix.
+    fn token<F: Fn(char) -> bool>(&mut self, f: F) -> (usize, &'a str) {
+        let start = self.pos;
+        let rest = self.rest();
+        let len = rest.find(|c: char| !f(c)).unwrap_or(rest.len());
+
+        self.pos += len;
+        (start, &rest[..len])
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        if self.rest().starts_with(c) {
+            self.pos += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<()> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            self.unexpected(&format!("'{}'", c))
+        }
+    }
+
+    fn size(&mut self) -> Result<usize> {
+        self.skip_whitespace();
+        let (pos, tok) = self.token(is_name_char);
+
+        if tok.is_empty() {
+            return self.unexpected("a number");
+        }
+        match parse_number(tok) {
+            Some(n) if n <= usize::max_value() as u64 => Ok(n as usize),
+            _ => self.error(pos, format!("Invalid number '{}'", tok)),
+        }
+    }
+
+    fn rvalue(&mut self) -> Result<Rvalue> {
+        if self.eat('?') {
+            return Ok(Rvalue::Undefined);
+        }
+
+        let (pos, tok) = self.token(is_name_char);
+
+        if tok.is_empty() {
+            return self.unexpected("a value");
+        }
+
+        self.expect(':')?;
+        let size = self.size()?;
+
+        if let Some(value) = parse_number(tok) {
+            if size < 64 && value >> size != 0 {
+                self.error(pos, format!("Constant {} does not fit into {} bits", tok, size))
+            } else {
+                Ok(Rvalue::Constant { value: value, size: size })
+            }
+        } else if let Some(words) = parse_wide_number(tok) {
+            let rv = Rvalue::new_wide(words.clone(), size);
+            let fits = match rv {
+                Rvalue::Wide { words: ref w, .. } => words.iter().skip(w.len()).all(|&x| x == 0) && words.iter().zip(w.iter()).all(|(a, b)| a == b),
+                _ => false,
+            };
+
+            if fits {
+                Ok(rv)
+            } else {
+                self.error(pos, format!("Constant {} does not fit into {} bits", tok, size))
+            }
+        } else {
+            let (name, subscript) = split_subscript(tok);
+            let offset = if self.eat('/') { self.size()? } else { 0 };
+
+            Ok(
+                Rvalue::Variable {
+                    name: Cow::Owned(name.to_string()),
+                    subscript: subscript,
+                    offset: offset,
+                    size: size,
+                }
+            )
+        }
+    }
+
+    fn lvalue(&mut self) -> Result<Lvalue> {
+        self.skip_whitespace();
+        let pos = self.pos;
+        let rv = self.rvalue()?;
+
+        match Lvalue::from_rvalue(rv) {
+            Some(lv) => Ok(lv),
+            None => self.error(pos, format!("Expected a variable or '?', found '{}'", &self.text[pos..self.pos])),
+        }
+    }
+
+    fn unary<F: Fn(Rvalue) -> Operation<Rvalue>>(&mut self, f: F) -> Result<Statement> {
+        let assignee = self.lvalue()?;
+        self.expect(',')?;
+        let a = self.rvalue()?;
+
+        Ok(Statement { assignee: assignee, op: f(a) })
+    }
+
+    fn binary<F: Fn(Rvalue, Rvalue) -> Operation<Rvalue>>(&mut self, f: F) -> Result<Statement> {
+        let assignee = self.lvalue()?;
+        self.expect(',')?;
+        let a = self.rvalue()?;
+        self.expect(',')?;
+        let b = self.rvalue()?;
+
+        Ok(Statement { assignee: assignee, op: f(a, b) })
+    }
+
+    /// Parses the `<n>` in opcodes like `convert_<n>`.
+    fn parameter(&self, pos: usize, opcode: &str, param: &str) -> Result<usize> {
+        match parse_number(param) {
+            Some(n) if n <= usize::max_value() as u64 => Ok(n as usize),
+            _ => self.error(pos, format!("Invalid size in '{}'", opcode)),
+        }
+    }
+
+    /// Parses the `<bank>/<endianess>/<size>` part of `load` and `store` opcodes.
+    fn memory(&self, pos: usize, opcode: &str, param: &str) -> Result<(Cow<'static, str>, Endianess, usize)> {
+        let parts = param.rsplitn(3, '/').collect::<Vec<_>>();
+
+        if parts.len() != 3 || parts[2].is_empty() {
+            return self.error(pos, format!("Expected '<bank>/<endianess>/<size>' in '{}'", opcode));
+        }
+
+        let endianess = match parts[1] {
+            "le" => Endianess::Little,
+            "be" => Endianess::Big,
+            e => return self.error(pos, format!("Unknown endianess '{}' in '{}'", e, opcode)),
+        };
+        let size = self.parameter(pos, opcode, parts[0])?;
+
+        Ok((Cow::Owned(parts[2].to_string()), endianess, size))
+    }
+
+    fn statement(&mut self) -> Result<Statement> {
+        let (pos, opcode) = self.token(|c| !c.is_whitespace());
+        let stmt = match opcode {
+            "add" => self.binary(Operation::Add)?,
+            "sub" => self.binary(Operation::Subtract)?,
+            "mul" => self.binary(Operation::Multiply)?,
+            "divu" => self.binary(Operation::DivideUnsigned)?,
+            "divs" => self.binary(Operation::DivideSigned)?,
+            "shl" => self.binary(Operation::ShiftLeft)?,
+            "shru" => self.binary(Operation::ShiftRightUnsigned)?,
+            "shrs" => self.binary(Operation::ShiftRightSigned)?,
+            "mod" => self.binary(Operation::Modulo)?,
+            "and" => self.binary(Operation::And)?,
+            "or" => self.binary(Operation::InclusiveOr)?,
+            "xor" => self.binary(Operation::ExclusiveOr)?,
+
+            "cmpeq" => self.binary(Operation::Equal)?,
+            "cmpleu" => self.binary(Operation::LessOrEqualUnsigned)?,
+            "cmples" => self.binary(Operation::LessOrEqualSigned)?,
+            "cmplu" => self.binary(Operation::LessUnsigned)?,
+            "cmpls" => self.binary(Operation::LessSigned)?,
+
+            "mov" => self.unary(Operation::Move)?,
+            "call" => self.unary(Operation::Call)?,
+
+            "init" => {
+                let assignee = self.lvalue()?;
+                self.expect(',')?;
+                self.skip_whitespace();
+                let (_, name) = self.token(is_name_char);
+
+                if name.is_empty() {
+                    return self.unexpected("a name");
+                }
+
+                self.expect(':')?;
+                let size = self.size()?;
+
+                Statement { assignee: assignee, op: Operation::Initialize(Cow::Owned(name.to_string()), size) }
+            }
+
+            "phi" => {
+                let assignee = self.lvalue()?;
+                let mut args = vec![];
+
+                while self.eat(',') {
+                    args.push(self.rvalue()?);
+                }
+
+                Statement { assignee: assignee, op: Operation::Phi(args) }
+            }
+
+            _ if opcode.starts_with("convert_") => {
+                let sz = self.parameter(pos, opcode, &opcode["convert_".len()..])?;
+                self.unary(|a| Operation::ZeroExtend(sz, a))?
+            }
+            _ if opcode.starts_with("sign-extend_") => {
+                let sz = self.parameter(pos, opcode, &opcode["sign-extend_".len()..])?;
+                self.unary(|a| Operation::SignExtend(sz, a))?
+            }
+            _ if opcode.starts_with("select_") => {
+                let off = self.parameter(pos, opcode, &opcode["select_".len()..])?;
+                self.binary(|a, b| Operation::Select(off, a, b))?
+            }
+            _ if opcode.starts_with("load_") => {
+                let (bank, endianess, sz) = self.memory(pos, opcode, &opcode["load_".len()..])?;
+                self.unary(|a| Operation::Load(bank.clone(), endianess, sz, a))?
+            }
+            _ if opcode.starts_with("store_") => {
+                let (bank, endianess, sz) = self.memory(pos, opcode, &opcode["store_".len()..])?;
+                self.binary(|a, b| Operation::Store(bank.clone(), endianess, sz, a, b))?
+            }
+
+            _ => return self.error(pos, format!("Unknown operation '{}'", opcode)),
+        };
+
+        self.skip_whitespace();
+        if !self.at_end() {
+            return self.unexpected("end of line");
+        }
+
+        Ok(stmt)
+    }
+}
+
+/// Tree of RREIL operations.
+///
+/// Expression trees are built from basic blocks in SSA form by `ExpressionBuilder`. Their `Display`
+/// implementation uses C-like operator syntax, e.g. `(RDI_0 + 8) * 4`. Signed variants of division,
+/// shifts and comparisons are suffixed with `s`.
+#[derive(Clone,PartialEq,Eq,Debug,Serialize,Deserialize)]
+pub enum Expression {
+    /// Leaf of the tree.
+    Value(Rvalue),
+    /// Operation whose operands are expressions themselves.
+    Operation(Box<Operation<Expression>>),
+}
+
+impl Expression {
+    // binding strength of the outermost operator, higher binds stronger.
+    fn precedence(&self) -> usize {
+        match self {
+            &Expression::Value(_) => 12,
+            &Expression::Operation(ref op) => {
+                match **op {
+                    Operation::Move(ref a) => a.precedence(),
+                    Operation::ZeroExtend(_, _) | Operation::SignExtend(_, _) => 11,
+                    Operation::Multiply(_, _) | Operation::DivideUnsigned(_, _) | Operation::DivideSigned(_, _) | Operation::Modulo(_, _) => 10,
+                    Operation::Add(_, _) | Operation::Subtract(_, _) => 9,
+                    Operation::ShiftLeft(_, _) | Operation::ShiftRightUnsigned(_, _) | Operation::ShiftRightSigned(_, _) => 8,
+                    Operation::LessOrEqualUnsigned(_, _) | Operation::LessOrEqualSigned(_, _) | Operation::LessUnsigned(_, _) | Operation::LessSigned(_, _) => 7,
+                    Operation::Equal(_, _) => 6,
+                    Operation::And(_, _) => 5,
+                    Operation::ExclusiveOr(_, _) => 4,
+                    Operation::InclusiveOr(_, _) => 3,
+                    Operation::Store(_, _, _, _, _) => 0,
+                    Operation::Call(_) | Operation::Initialize(_, _) | Operation::Select(_, _, _) | Operation::Load(_, _, _, _) | Operation::Phi(_) => 12,
+                }
+            }
+        }
+    }
+
+    fn write_operand(&self, f: &mut Formatter, prec: usize) -> result::Result<(), Error> {
+        if self.precedence() < prec {
+            f.write_fmt(format_args!("({})", self))
+        } else {
+            f.write_fmt(format_args!("{}", self))
+        }
+    }
+
+    fn write_infix(f: &mut Formatter, a: &Expression, op: &str, b: &Expression, prec: usize) -> result::Result<(), Error> {
+        // the precedence of shifts and bitwise operators is easy to get wrong, always add parens
+        // around their operands.
+        let (left, right) = match op {
+            "<<" | ">>" | ">>s" | "&" | "|" | "^" => (11, 11),
+            _ => (prec, prec + 1),
+        };
+
+        // all operators are left associative
+        a.write_operand(f, left)?;
+        f.write_fmt(format_args!(" {} ", op))?;
+        b.write_operand(f, right)
+    }
+}
+
+fn write_memory(f: &mut Formatter, bank: &str, endianess: Endianess, size: usize, addr: &Expression) -> result::Result<(), Error> {
+    match endianess {
+        Endianess::Little => f.write_fmt(format_args!("{}[{}]:{}", bank, addr, size)),
+        Endianess::Big => f.write_fmt(format_args!("{}[{}]:{}be", bank, addr, size)),
+    }
+}
+
+impl Display for Expression {
+    fn fmt(&self, f: &mut Formatter) -> result::Result<(), Error> {
+        let op = match self {
+            &Expression::Value(Rvalue::Undefined) => return f.write_str("?"),
+            &Expression::Value(Rvalue::Constant { value, .. }) if value < 10 => return f.write_fmt(format_args!("{}", value)),
+            &Expression::Value(Rvalue::Constant { value, .. }) => return f.write_fmt(format_args!("0x{:x}", value)),
+            &Expression::Value(ref rv @ Rvalue::Wide { .. }) => return f.write_str(&rv.hex_digits().unwrap_or_default()),
+            &Expression::Value(Rvalue::Variable { ref name, ref subscript, offset, size }) => {
+                f.write_str(name)?;
+                if let &Some(ss) = subscript {
+                    f.write_fmt(format_args!("_{}", ss))?;
+                }
+                if offset > 0 {
+                    f.write_fmt(format_args!("[{}..{}]", offset, offset + size))?;
+                }
+                return Ok(());
+            }
+            &Expression::Operation(ref op) => &**op,
+        };
+        let prec = self.precedence();
+
+        match op {
+            &Operation::Add(ref a, ref b) => Expression::write_infix(f, a, "+", b, prec),
+            &Operation::Subtract(ref a, ref b) => Expression::write_infix(f, a, "-", b, prec),
+            &Operation::Multiply(ref a, ref b) => Expression::write_infix(f, a, "*", b, prec),
+            &Operation::DivideUnsigned(ref a, ref b) => Expression::write_infix(f, a, "/", b, prec),
+            &Operation::DivideSigned(ref a, ref b) => Expression::write_infix(f, a, "/s", b, prec),
+            &Operation::ShiftLeft(ref a, ref b) => Expression::write_infix(f, a, "<<", b, prec),
+            &Operation::ShiftRightUnsigned(ref a, ref b) => Expression::write_infix(f, a, ">>", b, prec),
+            &Operation::ShiftRightSigned(ref a, ref b) => Expression::write_infix(f, a, ">>s", b, prec),
+            &Operation::Modulo(ref a, ref b) => Expression::write_infix(f, a, "%", b, prec),
+            &Operation::And(ref a, ref b) => Expression::write_infix(f, a, "&", b, prec),
+            &Operation::InclusiveOr(ref a, ref b) => Expression::write_infix(f, a, "|", b, prec),
+            &Operation::ExclusiveOr(ref a, ref b) => Expression::write_infix(f, a, "^", b, prec),
+            &Operation::Equal(ref a, ref b) => Expression::write_infix(f, a, "==", b, prec),
+            &Operation::LessOrEqualUnsigned(ref a, ref b) => Expression::write_infix(f, a, "<=", b, prec),
+            &Operation::LessOrEqualSigned(ref a, ref b) => Expression::write_infix(f, a, "<=s", b, prec),
+            &Operation::LessUnsigned(ref a, ref b) => Expression::write_infix(f, a, "<", b, prec),
+            &Operation::LessSigned(ref a, ref b) => Expression::write_infix(f, a, "<s", b, prec),
+            &Operation::ZeroExtend(sz, ref a) => {
+                f.write_fmt(format_args!("(u{})", sz))?;
+                a.write_operand(f, prec)
+            }
+            &Operation::SignExtend(sz, ref a) => {
+                f.write_fmt(format_args!("(s{})", sz))?;
+                a.write_operand(f, prec)
+            }
+            &Operation::Move(ref a) => f.write_fmt(format_args!("{}", a)),
+            &Operation::Call(ref a) => f.write_fmt(format_args!("call({})", a)),
+            &Operation::Initialize(ref name, _) => f.write_fmt(format_args!("init({})", name)),
+            &Operation::Select(off, ref a, ref b) => f.write_fmt(format_args!("select_{}({}, {})", off, a, b)),
+            &Operation::Load(ref bank, endianess, sz, ref a) => write_memory(f, bank, endianess, sz, a),
+            &Operation::Store(ref bank, endianess, sz, ref a, ref b) => {
+                write_memory(f, bank, endianess, sz, a)?;
+                f.write_fmt(format_args!(" = {}", b))
+            }
+            &Operation::Phi(ref args) => {
+                f.write_str("phi(")?;
+                for (i, a) in args.iter().enumerate() {
+                    if i > 0 {
                         f.write_str(", ")?;
                     }
+                    f.write_fmt(format_args!("{}", a))?;
+                }
+                f.write_str(")")
+            }
+        }
+    }
+}
+
+/// Assignment of an expression tree to a variable.
+#[derive(Clone,PartialEq,Eq,Debug,Serialize,Deserialize)]
+pub struct Assignment {
+    /// Variable the value of `expression` is assigned to
+    pub assignee: Lvalue,
+    /// Computed value
+    pub expression: Expression,
+}
+
+impl Display for Assignment {
+    fn fmt(&self, f: &mut Formatter) -> result::Result<(), Error> {
+        match self.assignee {
+            Lvalue::Undefined => f.write_fmt(format_args!("{}", self.expression)),
+            Lvalue::Variable { ref name, subscript: Some(ss), .. } => f.write_fmt(format_args!("{}_{} = {}", name, ss, self.expression)),
+            Lvalue::Variable { ref name, subscript: None, .. } => f.write_fmt(format_args!("{} = {}", name, self.expression)),
+        }
+    }
+}
+
+/// Folds RREIL code in SSA form into expression trees.
+///
+/// The definition of a SSA variable that is read exactly once in the function is moved into the
+/// expression reading it, if both are in the same basic block. Loads are never moved past a store
+/// or a call. Phi functions and calls are never folded.
+#[derive(Clone,Debug)]
+pub struct ExpressionBuilder {
+    uses: HashMap<(Cow<'static, str>, usize), usize>,
+}
+
+impl ExpressionBuilder {
+    /// Counts the uses of all SSA variables in `func`. Call `data_flow::ssa_convertion` on `func`
+    /// before, variables without subscript are never folded.
+    pub fn new(func: &Function) -> ExpressionBuilder {
+        let mut uses = HashMap::new();
+        {
+            let mut count = |rv: &Rvalue| if let &Rvalue::Variable { ref name, subscript: Some(ss), .. } = rv {
+                *uses.entry((name.clone(), ss)).or_insert(0) += 1;
+            };
+
+            for stmt in func.statements() {
+                for rv in stmt.op.operands() {
+                    count(rv);
+                }
+            }
+
+            for vx in func.cfg().vertices() {
+                if let Some(&ControlFlowTarget::Unresolved(ref rv)) = func.cfg().vertex_label(vx) {
+                    count(rv);
                 }
-                Ok(())
+            }
+
+            for e in func.cfg().edges() {
+                if let Some(&Guard::Predicate { ref flag, .. }) = func.cfg().edge_label(e) {
+                    count(flag);
+                }
+            }
+        }
+
+        ExpressionBuilder { uses: uses }
+    }
+
+    /// Returns the expression trees computed by `bb`, one vector for each mnemonic. Assignments
+    /// folded into later ones are removed.
+    pub fn basic_block(&self, bb: &BasicBlock) -> Vec<Vec<Assignment>> {
+        let mut ret = Vec::<Vec<Option<Assignment>>>::new();
+        // folding candidates: (mnemonic, position, size, reads memory)
+        let mut pending = HashMap::<(Cow<'static, str>, usize), (usize, usize, usize, bool)>::new();
+
+        for mne in bb.mnemonics.iter() {
+            ret.push(vec![]);
+
+            for stmt in mne.instructions.iter() {
+                let mut op = lift(&stmt.op, &|rv: &Rvalue| Expression::Value(rv.clone()));
+
+                if let Operation::Phi(_) = op {
+                } else {
+                    for e in op.operands_mut() {
+                        let key = match e {
+                            &mut Expression::Value(Rvalue::Variable { ref name, subscript: Some(ss), offset: 0, size }) => {
+                                match pending.get(&(name.clone(), ss)) {
+                                    Some(&(_, _, sz, _)) if sz == size => Some((name.clone(), ss)),
+                                    _ => None,
+                                }
+                            }
+                            _ => None,
+                        };
+
+                        if let Some((m, i, _, _)) = key.and_then(|k| pending.remove(&k)) {
+                            if let Some(a) = ret[m][i].take() {
+                                *e = a.expression;
+                            }
+                        }
+                    }
+                }
+
+                match op {
+                    Operation::Store(_, _, _, _, _) | Operation::Call(_) => pending.retain(|_, v| !v.3),
+                    _ => {}
+                }
+
+                let foldable = match op {
+                    Operation::Phi(_) | Operation::Call(_) => false,
+                    _ => true,
+                };
+                let expr = match op {
+                    Operation::Move(e) => e,
+                    op => Expression::Operation(Box::new(op)),
+                };
+                let pos = (ret.len() - 1, ret[ret.len() - 1].len());
+
+                if let Lvalue::Variable { ref name, subscript: Some(ss), size } = stmt.assignee {
+                    let key = (name.clone(), ss);
+
+                    if foldable && self.uses.get(&key) == Some(&1) {
+                        pending.insert(key, (pos.0, pos.1, size, reads_memory(&expr)));
+                    }
+                }
+
+                ret[pos.0].push(Some(Assignment { assignee: stmt.assignee.clone(), expression: expr }));
+            }
+        }
+
+        ret.into_iter().map(|v| v.into_iter().filter_map(|x| x).collect()).collect()
+    }
+}
+
+fn reads_memory(expr: &Expression) -> bool {
+    match expr {
+        &Expression::Value(_) => false,
+        &Expression::Operation(ref op) => {
+            match **op {
+                Operation::Load(_, _, _, _) => true,
+                ref op => op.operands().into_iter().any(|e| reads_memory(e)),
             }
         }
     }
@@ -1224,7 +1892,7 @@ impl Display for Statement {
 
 impl Arbitrary for Rvalue {
     fn arbitrary<G: Gen>(g: &mut G) -> Self {
-        match g.gen_range(0, 3) {
+        match g.gen_range(0, 4) {
             0 => Rvalue::Undefined,
             1 => {
                 Rvalue::Variable {
@@ -1234,7 +1902,19 @@ impl Arbitrary for Rvalue {
                     offset: g.gen_range(0, 512),
                 }
             }
-            2 => Rvalue::Constant { value: g.gen(), size: g.gen_range(1, 513) },
+            2 => {
+                let size = g.gen_range(1, 513);
+                let value: u64 = g.gen();
+
+                // constants must fit into their size
+                Rvalue::Constant { value: if size < 64 { value & ((1 << size) - 1) } else { value }, size: size }
+            }
+            3 => {
+                let size = g.gen_range(65, 513);
+                let words = (0..(size + 63) / 64).map(|_| g.gen()).collect();
+
+                Rvalue::new_wide(words, size)
+            }
             _ => unreachable!(),
         }
     }
@@ -1322,10 +2002,21 @@ impl Arbitrary for Operation<Rvalue> {
                     if sz.is_none() {
                         sz = o.size();
                     } else {
-                        match o {
-                            &mut Rvalue::Undefined => {}
-                            &mut Rvalue::Constant { ref mut size, .. } => *size = sz.unwrap(),
-                            &mut Rvalue::Variable { ref mut size, .. } => *size = sz.unwrap(),
+                        let wide = match o {
+                            &mut Rvalue::Undefined => None,
+                            &mut Rvalue::Constant { ref mut size, .. } => {
+                                *size = sz.unwrap();
+                                None
+                            }
+                            &mut Rvalue::Wide { ref words, .. } => Some(Rvalue::new_wide(words.clone(), sz.unwrap())),
+                            &mut Rvalue::Variable { ref mut size, .. } => {
+                                *size = sz.unwrap();
+                                None
+                            }
+                        };
+
+                        if let Some(rv) = wide {
+                            *o = rv;
                         }
                     }
                 }
@@ -1636,6 +2327,192 @@ mod tests {
         }
     }
 
+    #[test]
+    fn parse_display_output() {
+        let mut stmts = setup();
+
+        stmts.push(
+            Statement {
+                op: Operation::Add(
+                    Rvalue::Variable { name: Cow::Borrowed("eax"), subscript: Some(3), offset: 8, size: 8 },
+                    Rvalue::Constant { value: 0xff, size: 8 }
+                ),
+                assignee: Lvalue::Variable { name: Cow::Borrowed("__tmp"), subscript: Some(12), size: 8 },
+            }
+        );
+        stmts.push(
+            Statement {
+                op: Operation::Load(Cow::Borrowed("ram"), Endianess::Big, 32, Rvalue::Variable { name: Cow::Borrowed("sp"), subscript: None, offset: 0, size: 32 }),
+                assignee: Lvalue::Variable { name: Cow::Borrowed("a"), subscript: None, size: 32 },
+            }
+        );
+        stmts.push(
+            Statement {
+                op: Operation::Initialize(Cow::Borrowed("R0"), 8),
+                assignee: Lvalue::Variable { name: Cow::Borrowed("R0"), subscript: Some(0), size: 8 },
+            }
+        );
+        stmts.push(
+            Statement {
+                op: Operation::Phi(vec![]),
+                assignee: Lvalue::Undefined,
+            }
+        );
+        stmts.push(
+            Statement {
+                op: Operation::Xor(
+                    Rvalue::Variable { name: Cow::Borrowed("xmm0"), subscript: None, offset: 0, size: 128 },
+                    Rvalue::new_wide(vec![0, 1], 128)
+                ),
+                assignee: Lvalue::Variable { name: Cow::Borrowed("xmm0"), subscript: None, size: 128 },
+            }
+        );
+
+        let text = stmts.iter().map(|x| format!("{}", x)).collect::<Vec<_>>().join("\n");
+        assert_eq!(parse_statements(&text).unwrap(), stmts);
+    }
+
+    #[test]
+    fn parse_display_output_arbitrary() {
+        fn prop(op: Operation<Rvalue>, assignee: Lvalue) -> bool {
+            let stmt = Statement { op: op, assignee: assignee };
+            parse_statements(&format!("{}", stmt)).ok() == Some(vec![stmt])
+        }
+
+        ::quickcheck::quickcheck(prop as fn(Operation<Rvalue>, Lvalue) -> bool);
+    }
+
+    #[test]
+    fn parse_listing() {
+        let stmts = parse_statements(
+            "
+            // comment
+            mov a:8, 0x10:8 // trailing comment
+
+            store_ram/le/16 ?, sp_1:16, a:16
+            phi b_2:8, b_0:8, b_1:8
+            "
+        ).unwrap();
+
+        assert_eq!(
+            stmts,
+            vec![
+                Statement {
+                    op: Operation::Move(Rvalue::Constant { value: 16, size: 8 }),
+                    assignee: Lvalue::Variable { name: Cow::Borrowed("a"), subscript: None, size: 8 },
+                },
+                Statement {
+                    op: Operation::Store(
+                        Cow::Borrowed("ram"),
+                        Endianess::Little,
+                        16,
+                        Rvalue::Variable { name: Cow::Borrowed("sp"), subscript: Some(1), offset: 0, size: 16 },
+                        Rvalue::Variable { name: Cow::Borrowed("a"), subscript: None, offset: 0, size: 16 }
+                    ),
+                    assignee: Lvalue::Undefined,
+                },
+                Statement {
+                    op: Operation::Phi(
+                        vec![
+                            Rvalue::Variable { name: Cow::Borrowed("b"), subscript: Some(0), offset: 0, size: 8 },
+                            Rvalue::Variable { name: Cow::Borrowed("b"), subscript: Some(1), offset: 0, size: 8 },
+                        ]
+                    ),
+                    assignee: Lvalue::Variable { name: Cow::Borrowed("b"), subscript: Some(2), size: 8 },
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        let err = |s: &str| format!("{}", parse_statements(s).err().unwrap());
+
+        assert_eq!(err("mov a:8, b:8\nfoo a:8, b:8"), "2:1: Unknown operation 'foo'");
+        assert_eq!(err("  add a:8, b:8"), "1:15: Expected ',', found end of line");
+        assert_eq!(err("mov 1:8, b:8"), "1:5: Expected a variable or '?', found '1:8'");
+        assert_eq!(err("mov a:x, b:8"), "1:7: Invalid number 'x'");
+        assert_eq!(err("load_ram/me/8 a:8, b:8"), "1:1: Unknown endianess 'me' in 'load_ram/me/8'");
+        assert_eq!(err("mov a:8, b:8 c:8"), "1:14: Expected end of line, found 'c'");
+        assert_eq!(err("mov a:8, 0x10000000000000000:64"), "1:10: Constant 0x10000000000000000 does not fit into 64 bits");
+        assert_eq!(err("mov a:8, 0x1ff:8"), "1:10: Constant 0x1ff does not fit into 8 bits");
+        assert_eq!(err("mov a:1, 2:1"), "1:10: Constant 2 does not fit into 1 bits");
+        assert!(parse_statements("mov a:8, 0xff:8").is_ok());
+    }
+
+    #[test]
+    fn wide_constants() {
+        let w = Rvalue::new_wide(vec![0xffffffffffffffff, 0xffffffffffffffff, 0x1], 160);
+
+        assert_eq!(w.size(), Some(160));
+        assert_eq!(format!("{}", w), "0x1ffffffffffffffffffffffffffffffff:160");
+        assert_eq!(w.hex_digits(), Some("0x1ffffffffffffffffffffffffffffffff".to_string()));
+        assert_eq!(Rvalue::new_wide(vec![0, 0x10], 128).hex_digits(), Some("0x100000000000000000".to_string()));
+        assert_eq!(Rvalue::new_wide(vec![0x1, 0x0], 128), Rvalue::Constant { value: 1, size: 128 });
+        assert_eq!(Rvalue::new_wide(vec![0x1, 0x2], 64), Rvalue::Constant { value: 1, size: 64 });
+        assert_eq!(w.extract(8, 124).ok(), Some(Rvalue::Constant { value: 0x1f, size: 8 }));
+        assert_eq!(w.extract(64, 96).ok(), Some(Rvalue::Constant { value: 0x1ffffffff, size: 64 }));
+        assert!(w.extract(64, 100).is_err());
+
+        let stmt = Statement { op: Operation::Move(w.clone()), assignee: Lvalue::Undefined };
+        assert_eq!(parse_statements(&format!("{}", stmt)).unwrap(), vec![stmt]);
+        assert_eq!(execute(Operation::Add(w, Rvalue::Constant { value: 1, size: 160 })), Rvalue::new_wide(vec![0, 0, 2], 160));
+    }
+
+    #[test]
+    fn expression_trees() {
+        use crate::{BasicBlock, ControlFlowGraph, ControlFlowTarget, Function, Mnemonic};
+        use panopticon_graph_algos::MutableGraphTrait;
+
+        let mnemonic = |addr: u64, text: &str| {
+            let stmts = parse_statements(text).unwrap();
+            Mnemonic::new(addr..addr + 1, "test".to_string(), "".to_string(), vec![].iter(), stmts.iter()).unwrap()
+        };
+        let bb = BasicBlock::from_vec(
+            vec![
+                mnemonic(0, "add t_0:64, RDI_0:64, 0x8:64\nmul t_1:64, t_0:64, 0x4:64\nmov RAX_1:64, t_1:64"),
+                mnemonic(
+                    1,
+                    "
+                    load_ram/le/64 t_2:64, RSP_0:64
+                    store_ram/le/64 ?, RSP_0:64, RAX_1:64
+                    add t_3:64, t_2:64, 0x1:64
+                    cmpeq ZF_0:1, t_3:64, 0x0:64
+                    sub t_4:32, RAX_1:32, 0x1:32
+                    mov RCX_0:32, t_4:32
+                    "
+                ),
+                mnemonic(2, "sign-extend_64 RDX_0:64, RCX_0:32\nsub a_0:8, b_0:8, c_0:8\nsub d_0:8, e_0:8, a_0:8\nshrs f_0:8, d_0:8, 0x10:8"),
+            ]
+        );
+        let mut cfg = ControlFlowGraph::new();
+        let vx = cfg.add_vertex(ControlFlowTarget::Resolved(bb));
+        let exit = cfg.add_vertex(ControlFlowTarget::Unresolved(Rvalue::Undefined));
+        let zf = Rvalue::Variable { name: Cow::Borrowed("ZF"), subscript: Some(0), offset: 0, size: 1 };
+
+        cfg.add_edge(Guard::from_flag(&zf).unwrap(), vx, exit);
+        let mut func = Function::undefined(0, None, &Region::undefined("ram".to_owned(), 100), None);
+
+        *func.cfg_mut() = cfg;
+        func.set_entry_point_ref(vx);
+
+        let builder = ExpressionBuilder::new(&func);
+        let exprs = match func.cfg().vertex_label(vx) {
+            Some(&ControlFlowTarget::Resolved(ref bb)) => builder.basic_block(bb),
+            _ => unreachable!(),
+        };
+        let text = exprs.iter().map(|x| x.iter().map(|a| format!("{}", a)).collect::<Vec<_>>()).collect::<Vec<_>>();
+
+        assert_eq!(
+            text,
+            vec![
+                vec!["RAX_1 = (RDI_0 + 8) * 4".to_string()],
+                vec!["t_2 = ram[RSP_0]:64".to_string(), "ram[RSP_0]:64 = RAX_1".to_string(), "ZF_0 = t_2 + 1 == 0".to_string()],
+                vec!["RDX_0 = (s64)(RAX_1 - 1)".to_string(), "f_0 = (e_0 - (b_0 - c_0)) >>s 0x10".to_string()],
+            ]
+        );
+    }
+
     #[test]
     fn operands() {
         for mut x in setup() {
@@ -0,0 +1,509 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Concrete interpreter for RREIL code.
+//!
+//! The `Interpreter` keeps a machine state consisting of variables and memory banks. Variables
+//! are identified by name and SSA subscript and hold a constant of fixed size or are undefined.
+//! Memory banks are byte addressed and named after the first argument of `load`/`store`. A bank
+//! can be backed by a `Region`, writes never change the `Region` but are kept in the interpreter.
+//!
+//! Arithmetic is done by `il::execute`, the interpreter only replaces variables with their
+//! current values. `call` does not transfer control, the call targets are recorded instead.
+//! Values wider than 64 bits are kept as `Rvalue::Wide`.
+
+use crate::{BasicBlock, ControlFlowEdge, ControlFlowGraph, ControlFlowRef, ControlFlowTarget, Endianess, Guard, Lvalue, Operation, Region, Result, Rvalue,
+            Statement, execute};
+use crate::il::lift;
+use panopticon_graph_algos::{GraphTrait, IncidenceGraphTrait};
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+#[derive(Clone,Debug)]
+struct Value {
+    // either `Rvalue::Constant` or `Rvalue::Wide`, truncated to the size of the variable.
+    value: Rvalue,
+    // value of `Interpreter::clock` when the variable was assigned.
+    clock: usize,
+}
+
+#[derive(Clone,Debug)]
+struct Bank<'a> {
+    region: Option<&'a Region>,
+    cells: HashMap<u64, Option<u8>>,
+}
+
+/// Machine state of the RREIL interpreter.
+#[derive(Clone,Debug)]
+pub struct Interpreter<'a> {
+    variables: HashMap<Cow<'static, str>, HashMap<Option<usize>, Value>>,
+    banks: HashMap<Cow<'static, str>, Bank<'a>>,
+    calls: Vec<Rvalue>,
+    clock: usize,
+}
+
+impl<'a> Default for Interpreter<'a> {
+    fn default() -> Interpreter<'a> {
+        Interpreter::new()
+    }
+}
+
+impl<'a> Interpreter<'a> {
+    /// Creates an interpreter with all variables undefined and no memory banks.
+    pub fn new() -> Interpreter<'a> {
+        Interpreter {
+            variables: HashMap::new(),
+            banks: HashMap::new(),
+            calls: Vec::new(),
+            clock: 0,
+        }
+    }
+
+    /// Uses `region` as initial contents of memory bank `bank`. Cells written before are kept.
+    pub fn map_bank(&mut self, bank: &str, region: &'a Region) {
+        self.bank_mut(bank).region = Some(region);
+    }
+
+    /// Returns the value of the byte at `addr` in memory bank `bank` or None if it's undefined.
+    pub fn read_memory(&self, bank: &str, addr: u64) -> Option<u8> {
+        match self.banks.get(bank) {
+            Some(&Bank { ref region, ref cells }) => {
+                match cells.get(&addr) {
+                    Some(cell) => *cell,
+                    None => region.and_then(|r| r.read_u8(addr)),
+                }
+            }
+            None => None,
+        }
+    }
+
+    /// Sets the byte at `addr` in memory bank `bank` to `value`. A value of None makes the cell undefined.
+    pub fn write_memory(&mut self, bank: &str, addr: u64, value: Option<u8>) {
+        self.bank_mut(bank).cells.insert(addr, value);
+    }
+
+    /// Returns the value of variable `name` with SSA subscript `subscript` or None if it's undefined
+    /// or does not fit into 64 bits.
+    pub fn variable(&self, name: &str, subscript: Option<usize>) -> Option<u64> {
+        match self.value(name, subscript) {
+            Rvalue::Constant { value, .. } => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of variable `name` with SSA subscript `subscript`. The result is either a
+    /// constant, a wide constant or undefined.
+    pub fn value(&self, name: &str, subscript: Option<usize>) -> Rvalue {
+        match self.variables.get(name).and_then(|x| x.get(&subscript)) {
+            Some(val) => val.value.clone(),
+            None => Rvalue::Undefined,
+        }
+    }
+
+    /// Returns the current value of `value`. Variables are replaced by their contents, the result is
+    /// either a constant or undefined.
+    pub fn evaluate(&self, value: &Rvalue) -> Rvalue {
+        match value {
+            &Rvalue::Undefined => Rvalue::Undefined,
+            &Rvalue::Constant { value, size } => Rvalue::new_wide(vec![value], size),
+            &Rvalue::Wide { ref words, size } => Rvalue::new_wide(words.clone(), size),
+            &Rvalue::Variable { ref name, ref subscript, offset, size } => {
+                match self.variables.get(name.as_ref()).and_then(|x| x.get(subscript)) {
+                    Some(val) => val.value.extract(size, offset).unwrap_or(Rvalue::Undefined),
+                    None => Rvalue::Undefined,
+                }
+            }
+        }
+    }
+
+    /// Sets `var` to `value`. The value is truncated to the size of `var`. Assigning an undefined
+    /// value makes the variable undefined. Fails if `value` is not a constant or undefined.
+    pub fn assign(&mut self, var: &Lvalue, value: Rvalue) -> Result<()> {
+        if let &Lvalue::Variable { ref name, ref subscript, size } = var {
+            let words = match value {
+                Rvalue::Constant { value, .. } => vec![value],
+                Rvalue::Wide { words, .. } => words,
+                Rvalue::Undefined => {
+                    if let Some(x) = self.variables.get_mut(name.as_ref()) {
+                        x.remove(subscript);
+                    }
+                    return Ok(());
+                }
+                Rvalue::Variable { .. } => {
+                    return Err(format!("Can't assign variable {} to {}", value, var).into());
+                }
+            };
+
+            self.clock += 1;
+
+            let val = Value { value: Rvalue::new_wide(words, size), clock: self.clock };
+            self.variables.entry(name.clone()).or_insert_with(HashMap::new).insert(*subscript, val);
+        }
+
+        Ok(())
+    }
+
+    /// Targets of all `call` operations executed so far, in order.
+    pub fn calls(&self) -> &[Rvalue] {
+        &self.calls
+    }
+
+    /// Executes a single RREIL statement.
+    pub fn execute(&mut self, stmt: &Statement) -> Result<()> {
+        let value = match stmt.op {
+            Operation::Load(ref bank, endianess, size, ref addr) => {
+                let addr = self.evaluate(addr);
+                self.load(bank, endianess, size, &addr)?
+            }
+            Operation::Store(ref bank, endianess, size, ref addr, ref value) => {
+                let addr = self.evaluate(addr);
+                let value = self.evaluate(value);
+
+                self.store(bank, endianess, size, &addr, &value)?;
+                Rvalue::Undefined
+            }
+            Operation::Call(ref target) => {
+                let target = self.evaluate(target);
+
+                self.calls.push(target);
+                Rvalue::Undefined
+            }
+            Operation::Initialize(ref name, size) => {
+                self.evaluate(&Rvalue::Variable { name: name.clone(), subscript: None, offset: 0, size: size })
+            }
+            Operation::Phi(ref args) => {
+                // the argument assigned last is the one defined on the path we came from
+                let mut ret = (0, Rvalue::Undefined);
+
+                for arg in args.iter() {
+                    if let &Rvalue::Variable { ref name, ref subscript, .. } = arg {
+                        if let Some(val) = self.variables.get(name.as_ref()).and_then(|x| x.get(subscript)) {
+                            if val.clock > ret.0 {
+                                ret = (val.clock, self.evaluate(arg));
+                            }
+                        }
+                    }
+                }
+
+                ret.1
+            }
+            ref op => execute(lift(op, &|x| self.evaluate(x))),
+        };
+
+        self.assign(&stmt.assignee, value)
+    }
+
+    /// Executes all statements of `bb` in order.
+    pub fn execute_basic_block(&mut self, bb: &BasicBlock) -> Result<()> {
+        for stmt in bb.statements() {
+            self.execute(stmt)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns whenever `guard` is true or None if the guard depends on an undefined value.
+    pub fn evaluate_guard(&self, guard: &Guard) -> Option<bool> {
+        match guard {
+            &Guard::True => Some(true),
+            &Guard::False => Some(false),
+            &Guard::Predicate { ref flag, expected } => {
+                match self.evaluate(flag) {
+                    Rvalue::Constant { value, .. } => Some((value & 1 == 1) == expected),
+                    _ => None,
+                }
+            }
+        }
+    }
+
+    /// Executes the basic block `vx` of `cfg` and returns the outgoing edge whose guard is true
+    /// afterwards. Returns None if the basic block has no edge that is taken. Fails if `vx` is not a
+    /// resolved basic block, if a guard is undefined or if more than one edge is taken.
+    pub fn step(&mut self, cfg: &ControlFlowGraph, vx: ControlFlowRef) -> Result<Option<ControlFlowEdge>> {
+        match cfg.vertex_label(vx) {
+            Some(&ControlFlowTarget::Resolved(ref bb)) => self.execute_basic_block(bb)?,
+            Some(&ControlFlowTarget::Unresolved(ref rv)) => return Err(format!("Can't execute unresolved jump to {}", rv).into()),
+            Some(&ControlFlowTarget::Failed(addr, ref msg)) => return Err(format!("Can't execute failed basic block at {:#x}: {}", addr, msg).into()),
+            None => return Err("Unknown basic block".into()),
+        }
+
+        let mut ret = None;
+
+        for e in cfg.out_edges(vx) {
+            let guard = cfg.edge_label(e).ok_or("Unknown edge")?;
+
+            match self.evaluate_guard(guard) {
+                Some(true) if ret.is_some() => return Err("More than one outgoing edge taken".into()),
+                Some(true) => ret = Some(e),
+                Some(false) => {}
+                None => return Err(format!("Guard {} is undefined", guard).into()),
+            }
+        }
+
+        Ok(ret)
+    }
+
+    fn bank_mut(&mut self, bank: &str) -> &mut Bank<'a> {
+        if !self.banks.contains_key(bank) {
+            self.banks.insert(Cow::Owned(bank.to_string()), Bank { region: None, cells: HashMap::new() });
+        }
+
+        self.banks.get_mut(bank).unwrap()
+    }
+
+    fn load(&self, bank: &str, endianess: Endianess, size: usize, addr: &Rvalue) -> Result<Rvalue> {
+        if size == 0 || size % 8 != 0 {
+            return Err(format!("Memory operation of size {} not byte aligned", size).into());
+        }
+
+        let addr = match addr {
+            &Rvalue::Constant { value, .. } => value,
+            _ => return Ok(Rvalue::Undefined),
+        };
+
+        let bytes = size / 8;
+        let mut words = vec![0u64; (size + 63) / 64];
+
+        for i in 0..bytes {
+            let byte = match self.read_memory(bank, addr.wrapping_add(i as u64)) {
+                Some(b) => b as u64,
+                None => return Ok(Rvalue::Undefined),
+            };
+            let shift = match endianess {
+                Endianess::Little => i * 8,
+                Endianess::Big => (bytes - i - 1) * 8,
+            };
+
+            words[shift / 64] |= byte << (shift % 64);
+        }
+
+        Ok(Rvalue::new_wide(words, size))
+    }
+
+    fn store(&mut self, bank: &str, endianess: Endianess, size: usize, addr: &Rvalue, value: &Rvalue) -> Result<()> {
+        if size == 0 || size % 8 != 0 {
+            return Err(format!("Memory operation of size {} not byte aligned", size).into());
+        }
+
+        let addr = match addr {
+            &Rvalue::Constant { value, .. } => value,
+            _ => return Err(format!("Store to undefined address in bank {}", bank).into()),
+        };
+        let words = match value {
+            &Rvalue::Constant { value, .. } => Some(vec![value]),
+            &Rvalue::Wide { ref words, .. } => Some(words.clone()),
+            _ => None,
+        };
+        let bytes = size / 8;
+
+        for i in 0..bytes {
+            let shift = match endianess {
+                Endianess::Little => i * 8,
+                Endianess::Big => (bytes - i - 1) * 8,
+            };
+            let byte = words.as_ref().map(|w| (w.get(shift / 64).cloned().unwrap_or(0) >> (shift % 64)) as u8);
+
+            self.write_memory(bank, addr.wrapping_add(i as u64), byte);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Bound, Mnemonic, parse_statements};
+    use panopticon_graph_algos::MutableGraphTrait;
+    use std::borrow::Cow;
+
+    fn basic_block(addr: u64, text: &str) -> BasicBlock {
+        let stmts = parse_statements(text).unwrap();
+        let mne = Mnemonic::new(addr..addr + 1, "test".to_string(), "".to_string(), vec![].iter(), stmts.iter()).unwrap();
+
+        BasicBlock::from_vec(vec![mne])
+    }
+
+    fn flag(name: &'static str) -> Guard {
+        Guard::from_flag(&Rvalue::Variable { name: Cow::Borrowed(name), subscript: None, offset: 0, size: 1 }).unwrap()
+    }
+
+    #[test]
+    fn arithmetic() {
+        let mut int = Interpreter::new();
+        let stmts = parse_statements(
+            "
+            mov a:16, 0x1234:16
+            add b:8, a:8/8, 0xff:8
+            sign-extend_16 c:16, b:8
+            convert_32 d:32, a:16
+            cmplu f:1, b:8, a:8
+            mov e:8, x:8
+            "
+        ).unwrap();
+
+        for stmt in stmts.iter() {
+            int.execute(stmt).unwrap();
+        }
+
+        assert_eq!(int.variable("a", None), Some(0x1234));
+        assert_eq!(int.variable("b", None), Some(0x11));
+        assert_eq!(int.variable("c", None), Some(0x11));
+        assert_eq!(int.variable("d", None), Some(0x1234));
+        assert_eq!(int.variable("f", None), Some(1));
+        assert_eq!(int.variable("e", None), None);
+        assert_eq!(int.evaluate(&Rvalue::Variable { name: Cow::Borrowed("a"), subscript: None, offset: 8, size: 16 }), Rvalue::Undefined);
+    }
+
+    #[test]
+    fn memory() {
+        let reg = Region::wrap("ram".to_string(), vec![1, 2, 3, 4]);
+        let mut int = Interpreter::new();
+        let stmts = parse_statements(
+            "
+            load_ram/le/16 a:16, 0x0:32
+            load_ram/be/16 b:16, 0x2:32
+            store_ram/be/16 ?, 0x1:32, 0xaabb:16
+            load_ram/le/32 c:32, 0x0:32
+            load_ram/le/16 d:16, 0x3:32
+            load_rom/le/8 e:8, 0x0:32
+            "
+        ).unwrap();
+
+        int.map_bank("ram", &reg);
+        for stmt in stmts.iter() {
+            int.execute(stmt).unwrap();
+        }
+
+        assert_eq!(int.variable("a", None), Some(0x0201));
+        assert_eq!(int.variable("b", None), Some(0x0304));
+        assert_eq!(int.variable("c", None), Some(0x04bbaa01));
+        assert_eq!(int.variable("d", None), None);
+        assert_eq!(int.variable("e", None), None);
+        assert_eq!(int.read_memory("ram", 1), Some(0xaa));
+        assert_eq!(reg.read_u8(1), Some(2));
+    }
+
+    #[test]
+    fn wide_values() {
+        let reg = Region::wrap("ram".to_string(), (0..16).collect());
+        let mut int = Interpreter::new();
+        let stmts = parse_statements(
+            "
+            load_ram/le/128 x:128, 0x0:32
+            load_ram/be/128 y:128, 0x0:32
+            xor z:128, x:128, x:128
+            mov lo:64, x:64
+            mov hi:32, x:32/96
+            store_ram/le/128 ?, 0x10:32, y:128
+            load_ram/le/64 w:64, 0x10:32
+            "
+        ).unwrap();
+
+        int.map_bank("ram", &reg);
+        for stmt in stmts.iter() {
+            int.execute(stmt).unwrap();
+        }
+
+        assert_eq!(int.value("x", None), Rvalue::new_wide(vec![0x0706050403020100, 0x0f0e0d0c0b0a0908], 128));
+        assert_eq!(int.value("y", None), Rvalue::new_wide(vec![0x08090a0b0c0d0e0f, 0x0001020304050607], 128));
+        assert_eq!(int.variable("x", None), None);
+        assert_eq!(int.value("z", None), Rvalue::Constant { value: 0, size: 128 });
+        assert_eq!(int.variable("lo", None), Some(0x0706050403020100));
+        assert_eq!(int.variable("hi", None), Some(0x0f0e0d0c));
+        assert_eq!(int.variable("w", None), Some(0x08090a0b0c0d0e0f));
+    }
+
+    #[test]
+    fn ssa() {
+        let mut int = Interpreter::new();
+        let stmts = parse_statements(
+            "
+            init a_0:8, a:8
+            add a_1:8, a_0:8, 0x1:8
+            mov a_2:8, 0x10:8
+            phi a_3:8, a_2:8, a_1:8
+            call ?, a_3:8
+            "
+        ).unwrap();
+
+        int.assign(&Lvalue::Variable { name: Cow::Borrowed("a"), subscript: None, size: 8 }, Rvalue::new_u8(41)).unwrap();
+        for stmt in stmts.iter() {
+            int.execute(stmt).unwrap();
+        }
+
+        assert_eq!(int.variable("a", Some(0)), Some(41));
+        assert_eq!(int.variable("a", Some(3)), Some(0x10));
+        assert_eq!(int.calls(), &[Rvalue::new_u8(0x10)]);
+    }
+
+    #[test]
+    fn checksum_loop() {
+        // sums up the bytes at 0..4
+        let reg = Region::wrap("ram".to_string(), vec![1, 2, 3, 250]);
+        let mut cfg = ControlFlowGraph::new();
+        let entry = cfg.add_vertex(
+            ControlFlowTarget::Resolved(basic_block(0, "mov sum:8, 0x0:8\nmov i:32, 0x0:32"))
+        );
+        let body = cfg.add_vertex(
+            ControlFlowTarget::Resolved(
+                basic_block(
+                    1,
+                    "
+                    load_ram/le/8 t:8, i:32
+                    add sum:8, sum:8, t:8
+                    add i:32, i:32, 0x1:32
+                    cmplu c:1, i:32, 0x4:32
+                    "
+                )
+            )
+        );
+        let exit = cfg.add_vertex(ControlFlowTarget::Resolved(BasicBlock { area: Bound::new(2, 2), mnemonics: vec![] }));
+
+        cfg.add_edge(Guard::always(), entry, body);
+        cfg.add_edge(flag("c"), body, body);
+        cfg.add_edge(flag("c").negation(), body, exit);
+
+        let mut int = Interpreter::new();
+        let mut vx = entry;
+        let mut trace = vec![];
+
+        int.map_bank("ram", &reg);
+        while let Some(e) = int.step(&cfg, vx).unwrap() {
+            vx = cfg.target(e);
+            trace.push(vx);
+        }
+
+        assert_eq!(trace, vec![body, body, body, body, exit]);
+        assert_eq!(int.variable("sum", None), Some(0));
+        assert_eq!(int.variable("i", None), Some(4));
+    }
+
+    #[test]
+    fn undefined_guard() {
+        let mut cfg = ControlFlowGraph::new();
+        let a = cfg.add_vertex(ControlFlowTarget::Resolved(basic_block(0, "mov x:8, 0x1:8")));
+        let b = cfg.add_vertex(ControlFlowTarget::Unresolved(Rvalue::Undefined));
+
+        cfg.add_edge(flag("c"), a, b);
+
+        let mut int = Interpreter::new();
+        assert!(int.step(&cfg, a).is_err());
+        assert!(int.step(&cfg, b).is_err());
+    }
+}
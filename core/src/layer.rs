@@ -38,14 +38,33 @@
 //! reg.cover(Bound::new(0x100,0x100 + mapping.len()),Layer::Opaque(mapping));
 //! ```
 //! Loading a Windows COM file.
+//!
+//! Encrypted parts of a `Region` can be decoded in place with a `KeyStream` layer. Compressed or
+//! encoded data changes size when decoded, the result is added to the `World` as a new `Region`
+//! using a `Transform`.
+//!
+//! ```
+//! use panopticon_core::{Bound, Layer, Region};
+//!
+//! let mut reg = Region::wrap("packed".to_string(), vec![0x39, 0x34, 0x3d, 0x3d, 0x3e]);
+//!
+//! reg.cover(Bound::new(0, 5), Layer::xor(vec![0x51]));
+//! assert_eq!(reg.iter().map(|c| c.unwrap()).collect::<Vec<u8>>(), b"hello".to_vec());
+//! ```
 
 
 use crate::Result;
+use flate2::read::ZlibDecoder;
+use lzma_rs;
+use memmap::Mmap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::de::Error as DeError;
 use std::collections::HashMap;
-use std::fs::File;
-use std::io::Read;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{Cursor, Read};
 use std::ops::Range;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 /// A cell represents a single, possible undefined, byte.
@@ -58,6 +77,31 @@ pub enum OpaqueLayer {
     Undefined(u64),
     /// Layer consisting of fixed byte values.
     Defined(Arc<Vec<u8>>),
+    /// Layer consisting of the contents of a memory mapped file.
+    Mapped(MappedLayer),
+}
+
+/// Read-only memory mapping of a range of a file.
+///
+/// Clones and slices share the mapping. Serializing a `MappedLayer` only writes the path of the
+/// file, the range and a hash of its contents. Deserializing maps the file again and fails if the
+/// range changed in the meantime.
+#[derive(Clone)]
+pub struct MappedLayer {
+    path: PathBuf,
+    // empty files cannot be mapped
+    map: Option<Arc<Mmap>>,
+    offset: u64,
+    len: u64,
+}
+
+/// On-disk representation of a `MappedLayer`.
+#[derive(Serialize,Deserialize)]
+struct MappedLayerRef {
+    path: PathBuf,
+    offset: u64,
+    len: u64,
+    hash: u64,
 }
 
 /// Iterator over a range of `Cell`s.
@@ -76,6 +120,17 @@ pub enum LayerIter<'a> {
         /// Starting point
         pos: u64,
     },
+    /// Layer combining cells with a repeating key.
+    KeyStream {
+        /// Operation used to combine cell and key
+        op: KeyOp,
+        /// Key bytes
+        key: &'a [u8],
+        /// Layer to be decoded
+        mapped: Box<LayerIter<'a>>,
+        /// Starting point
+        pos: u64,
+    },
     /// Concatenation of two layers
     Concat {
         /// First layer
@@ -115,6 +170,16 @@ impl<'a> Iterator for LayerIter<'a> {
                     None
                 }
             }
+            LayerIter::KeyStream { op, key: ref k, mapped: ref mut i, pos: ref mut p } => {
+                if let Some(covered) = i.next() {
+                    let key = k[(*p % k.len() as u64) as usize];
+
+                    *p += 1;
+                    Some(covered.map(|c| op.apply(c, key)))
+                } else {
+                    None
+                }
+            }
             LayerIter::Concat { car: ref mut a, cdr: ref mut b } => {
                 if let Some(aa) = a.next() {
                     Some(aa)
@@ -158,6 +223,7 @@ impl<'a> LayerIter<'a> {
             LayerIter::Defined(None) => LayerIter::Defined(None),
             LayerIter::Defined(Some(ref buf)) => LayerIter::Defined(Some(&buf[r.start as usize..real_end as usize])),
             LayerIter::Sparse { map: ref m, mapped: ref i, pos: ref p, .. } => LayerIter::Sparse { map: m, mapped: Box::new(i.cut(r)), pos: p + r.start },
+            LayerIter::KeyStream { op, key: ref k, mapped: ref i, pos: ref p } => LayerIter::KeyStream { op: op, key: k, mapped: Box::new(i.cut(r)), pos: p + r.start },
             LayerIter::Concat { car: ref a, cdr: ref b } => {
                 if r.start < a.len() && real_end <= a.len() {
                     a.cut(r)
@@ -194,6 +260,7 @@ impl<'a> LayerIter<'a> {
             LayerIter::Defined(None) => 0,
             LayerIter::Defined(Some(ref r)) => r.len() as u64,
             LayerIter::Sparse { mapped: ref m, .. } => m.len(),
+            LayerIter::KeyStream { mapped: ref m, .. } => m.len(),
             LayerIter::Concat { car: ref a, cdr: ref b } => a.len() + b.len(),
         }
     }
@@ -209,6 +276,204 @@ pub enum Layer {
     Opaque(OpaqueLayer),
     /// Layer overwriting single cells with new values.
     Sparse(HashMap<u64, Cell>),
+    /// Layer combining each cell with a repeating key, starting with the first key byte at the
+    /// beginning of the layer. Undefined cells stay undefined.
+    KeyStream {
+        /// Operation used to combine cell and key.
+        op: KeyOp,
+        /// Key bytes.
+        key: Vec<u8>,
+    },
+}
+
+/// Operation a `KeyStream` layer applies to each cell.
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Serialize,Deserialize)]
+pub enum KeyOp {
+    /// Cell XOR key.
+    Xor,
+    /// Cell plus key, modulo 256.
+    Add,
+}
+
+impl KeyOp {
+    /// Combines `cell` with `key`.
+    pub fn apply(&self, cell: u8, key: u8) -> u8 {
+        match *self {
+            KeyOp::Xor => cell ^ key,
+            KeyOp::Add => cell.wrapping_add(key),
+        }
+    }
+}
+
+/// Decoder turning the cells of a range into a new `Region` of different size.
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Serialize,Deserialize)]
+pub enum Transform {
+    /// zlib (RFC 1950) compressed data.
+    Zlib,
+    /// LZMA compressed data with a 13 byte `.lzma` header.
+    Lzma,
+    /// Base64 (RFC 4648) encoded data. Whitespace is ignored.
+    Base64,
+}
+
+impl Transform {
+    /// Decodes `data`.
+    pub fn apply(&self, data: &[u8]) -> Result<Vec<u8>> {
+        match *self {
+            Transform::Zlib => {
+                let mut ret = Vec::new();
+
+                ZlibDecoder::new(data).read_to_end(&mut ret)?;
+                Ok(ret)
+            }
+            Transform::Lzma => {
+                let mut ret = Vec::new();
+
+                lzma_rs::lzma_decompress(&mut Cursor::new(data), &mut ret)?;
+                Ok(ret)
+            }
+            Transform::Base64 => base64_decode(data),
+        }
+    }
+}
+
+impl fmt::Display for Transform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Transform::Zlib => f.write_str("zlib"),
+            Transform::Lzma => f.write_str("lzma"),
+            Transform::Base64 => f.write_str("base64"),
+        }
+    }
+}
+
+fn base64_decode(data: &[u8]) -> Result<Vec<u8>> {
+    let mut ret = Vec::with_capacity(data.len() / 4 * 3);
+    let mut acc = 0u32;
+    let mut bits = 0;
+
+    for &c in data.iter().take_while(|&&c| c != b'=') {
+        let v = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            b' ' | b'\t' | b'\r' | b'\n' => continue,
+            _ => return Err(format!("Invalid base64 character {:?}", c as char).into()),
+        };
+
+        // at most 14 bits are pending
+        acc = (acc << 6 | v as u32) & 0x3fff;
+        bits += 6;
+
+        if bits >= 8 {
+            bits -= 8;
+            ret.push((acc >> bits) as u8);
+        }
+    }
+
+    // a single character left over cannot encode a whole byte
+    if bits >= 6 {
+        Err("Truncated base64 data".into())
+    } else {
+        Ok(ret)
+    }
+}
+
+impl MappedLayer {
+    /// Maps the whole file at `p` into memory.
+    pub fn open(p: &Path) -> Result<MappedLayer> {
+        let path = fs::canonicalize(p)?;
+        let file = File::open(&path)?;
+        let len = file.metadata()?.len();
+        // mapping is unsafe because other processes may change the file underneath us. Like the
+        // loaders we assume it is neither modified nor truncated while the project is open.
+        let map = if len > 0 { Some(Arc::new(unsafe { Mmap::map(&file)? })) } else { None };
+
+        Ok(MappedLayer { path: path, map: map, offset: 0, len: len })
+    }
+
+    /// Absolute path of the mapped file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Offset of the first `Cell` in the file.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Number of `Cell`s in the `Layer`.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Contents of the mapped range.
+    pub fn as_slice(&self) -> &[u8] {
+        match self.map {
+            Some(ref map) => &map[self.offset as usize..(self.offset + self.len) as usize],
+            None => &[],
+        }
+    }
+
+    /// Returns `len` `Cell`s starting `offset` `Cell`s into this `Layer`, sharing the mapping.
+    /// Returns `None` if the range exceeds the `Layer`.
+    pub fn slice(&self, offset: u64, len: u64) -> Option<MappedLayer> {
+        match offset.checked_add(len) {
+            Some(end) if end <= self.len => {
+                Some(MappedLayer { path: self.path.clone(), map: self.map.clone(), offset: self.offset + offset, len: len })
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the part of this `Layer` that `data` points into. Returns `None` if `data` is not
+    /// inside the mapped range, e.g. because it is a copy.
+    pub fn subslice(&self, data: &[u8]) -> Option<MappedLayer> {
+        let start = self.as_slice().as_ptr() as usize;
+        let addr = data.as_ptr() as usize;
+
+        if addr >= start && addr - start + data.len() <= self.len as usize {
+            self.slice((addr - start) as u64, data.len() as u64)
+        } else {
+            None
+        }
+    }
+
+    /// 64-bit FNV-1a hash of the mapped range.
+    fn hash(&self) -> u64 {
+        self.as_slice().iter().fold(0xcbf29ce484222325, |h, &b| (h ^ b as u64).wrapping_mul(0x100000001b3))
+    }
+}
+
+impl fmt::Debug for MappedLayer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "MappedLayer({}, {:#x}, {:#x})", self.path.display(), self.offset, self.len)
+    }
+}
+
+impl Serialize for MappedLayer {
+    fn serialize<S: Serializer>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error> {
+        let r = MappedLayerRef { path: self.path.clone(), offset: self.offset, len: self.len, hash: self.hash() };
+
+        r.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for MappedLayer {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> ::std::result::Result<MappedLayer, D::Error> {
+        let r = MappedLayerRef::deserialize(deserializer)?;
+        let file = MappedLayer::open(&r.path).map_err(|e| D::Error::custom(format!("Cannot map {}: {}", r.path.display(), e)))?;
+        let layer = file.slice(r.offset, r.len)
+            .ok_or_else(|| D::Error::custom(format!("{} is too short for range {:#x}+{:#x}", r.path.display(), r.offset, r.len)))?;
+
+        if layer.hash() != r.hash {
+            Err(D::Error::custom(format!("Contents of {} changed", r.path.display())))
+        } else {
+            Ok(layer)
+        }
+    }
 }
 
 impl OpaqueLayer {
@@ -217,6 +482,7 @@ impl OpaqueLayer {
         match *self {
             OpaqueLayer::Undefined(ref len) => LayerIter::Undefined(*len),
             OpaqueLayer::Defined(ref v) => LayerIter::Defined(Some(v)),
+            OpaqueLayer::Mapped(ref m) => LayerIter::Defined(Some(m.as_slice())),
         }
     }
 
@@ -225,16 +491,37 @@ impl OpaqueLayer {
         match *self {
             OpaqueLayer::Undefined(ref len) => *len,
             OpaqueLayer::Defined(ref v) => v.len() as u64,
+            OpaqueLayer::Mapped(ref m) => m.len(),
+        }
+    }
+
+    /// Copies the `Cell`s starting `offset` `Cell`s into the `Layer` to `buf`. The range must be
+    /// inside the `Layer`.
+    pub fn read(&self, offset: u64, buf: &mut [Cell]) {
+        let bytes = match *self {
+            OpaqueLayer::Undefined(_) => {
+                for c in buf.iter_mut() {
+                    *c = None;
+                }
+                return;
+            }
+            OpaqueLayer::Defined(ref v) => &v[..],
+            OpaqueLayer::Mapped(ref m) => m.as_slice(),
+        };
+
+        let start = offset as usize;
+        let end = start + buf.len();
+
+        for (c, &b) in buf.iter_mut().zip(bytes[start..end].iter()) {
+            *c = Some(b);
         }
     }
 
     /// Create a new `Layer` that replaces overlapped `Cell`s with the contents of the file at
-    /// `path`. The `Layer` will have the size of the file.
+    /// `path`. The `Layer` will have the size of the file. The file is mapped into memory, not
+    /// read.
     pub fn open(p: &Path) -> Result<OpaqueLayer> {
-        let mut buf: Vec<u8> = Vec::new();
-        let mut fd = File::open(p)?;
-        fd.read_to_end(&mut buf)?;
-        Ok(Self::wrap(buf))
+        MappedLayer::open(p).map(OpaqueLayer::Mapped)
     }
 
     /// Create a new `Layer` that replaces overlapped `Cell`s with the contents of `data`.
@@ -255,6 +542,8 @@ impl Layer {
         match *self {
             Layer::Opaque(ref o) => o.iter(),
             Layer::Sparse(ref m) => LayerIter::Sparse { map: m, mapped: Box::new(i), pos: 0 },
+            Layer::KeyStream { ref key, .. } if key.is_empty() => i,
+            Layer::KeyStream { op, ref key } => LayerIter::KeyStream { op: op, key: key, mapped: Box::new(i), pos: 0 },
         }
     }
 
@@ -275,6 +564,16 @@ impl Layer {
         OpaqueLayer::open(p).map(|x| Layer::Opaque(x))
     }
 
+    /// Create a new `Layer` that XORs overlapped `Cell`s with the repeating `key`.
+    pub fn xor(key: Vec<u8>) -> Layer {
+        Layer::KeyStream { op: KeyOp::Xor, key: key }
+    }
+
+    /// Create a new `Layer` that adds the repeating `key` to overlapped `Cell`s.
+    pub fn add(key: Vec<u8>) -> Layer {
+        Layer::KeyStream { op: KeyOp::Add, key: key }
+    }
+
     /// Returns a new `Layer` that allows sparse replacement of `Cell`s
     pub fn writable() -> Layer {
         Layer::Sparse(HashMap::new())
@@ -408,6 +707,51 @@ mod tests {
         assert_eq!(s.collect::<Vec<Cell>>(), e);
     }
 
+    #[test]
+    fn key_stream() {
+        let l1 = OpaqueLayer::wrap(vec![0x10, 0x20, 0x30, 0x40, 0x50]);
+        let l2 = Layer::xor(vec![0x01, 0x02]);
+        let l3 = Layer::add(vec![0xf0]);
+        let s = l2.filter(l1.iter());
+
+        assert_eq!(s.clone().collect::<Vec<Cell>>(), vec![Some(0x11), Some(0x22), Some(0x31), Some(0x42), Some(0x51)]);
+        // key position is kept when seeking
+        assert_eq!(s.seek(3).collect::<Vec<Cell>>(), vec![Some(0x42), Some(0x51)]);
+        assert_eq!(l3.filter(OpaqueLayer::undefined(2).iter()).collect::<Vec<Cell>>(), vec![None, None]);
+        assert_eq!(l3.filter(l1.iter()).next(), Some(Some(0x00)));
+        assert_eq!(Layer::xor(vec![]).filter(l1.iter()).len(), 5);
+    }
+
+    #[test]
+    fn transforms() {
+        assert_eq!(Transform::Base64.apply(b"aGVs\nbG8=").unwrap(), b"hello".to_vec());
+        assert_eq!(Transform::Base64.apply(b"aGVsbG8h").unwrap(), b"hello!".to_vec());
+        assert!(Transform::Base64.apply(b"aGVsb").is_err());
+        assert!(Transform::Base64.apply(b"aGV*").is_err());
+        assert_eq!(Transform::Zlib.apply(&[0x78, 0x9c, 0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0x07, 0x00, 0x06, 0x2c, 0x02, 0x15]).unwrap(), b"hello".to_vec());
+        assert!(Transform::Zlib.apply(b"hello").is_err());
+
+        // unknown size, terminated by an end marker
+        let lzma = [
+            0x5d, 0x00, 0x00, 0x80, 0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00, 0x24, 0x19, 0x49, 0x98, 0x6f, 0x16, 0x02, 0xa5, 0xfd, 0xcc,
+            0x3f, 0x5a, 0x87, 0xff, 0xff, 0xe0, 0x17, 0x80, 0x00,
+        ];
+        assert_eq!(Transform::Lzma.apply(&lzma).unwrap(), b"Hello, Hello, Hello!".to_vec());
+        assert!(Transform::Lzma.apply(&lzma[..lzma.len() - 4]).is_err());
+        assert!(Transform::Lzma.apply(b"").is_err());
+    }
+
+    #[test]
+    fn mapped() {
+        let path = Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/../test-data/hello.prg"));
+        let layer = MappedLayer::open(path).unwrap();
+
+        assert_eq!(layer.len(), fs::metadata(path).unwrap().len());
+        assert_eq!(&layer.as_slice()[0..2], &[0x01, 0x08]);
+        assert_eq!(layer.slice(1, 2).unwrap().as_slice(), &layer.as_slice()[1..3]);
+        assert!(layer.slice(1, layer.len()).is_none());
+    }
+
     #[test]
     fn random_access_iter() {
         let l1 = OpaqueLayer::undefined(0xffffffff);
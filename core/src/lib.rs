@@ -84,6 +84,11 @@ extern crate panopticon_graph_algos;
 extern crate uuid;
 extern crate byteorder;
 extern crate goblin;
+extern crate pdb as ms_pdb;
+extern crate minidump;
+extern crate lzma_rs;
+extern crate memmap;
+extern crate libc;
 extern crate quickcheck;
 extern crate serde;
 #[macro_use] extern crate serde_derive;
@@ -98,7 +103,7 @@ pub use crate::disassembler::{Architecture, Disassembler, Match, State};
 
 #[macro_use]
 pub mod il;
-pub use crate::il::{Guard, Lvalue, Operation, Rvalue, Statement, execute, Endianess};
+pub use crate::il::{Assignment, Expression, ExpressionBuilder, Guard, Lvalue, Operation, Rvalue, Statement, execute, parse_statements, Endianess};
 
 pub mod mnemonic;
 pub use crate::mnemonic::{Bound, Mnemonic, MnemonicFormatToken};
@@ -106,23 +111,35 @@ pub mod basic_block;
 pub use crate::basic_block::BasicBlock;
 
 pub mod function;
-pub use crate::function::{ControlFlowEdge, ControlFlowGraph, ControlFlowRef, ControlFlowTarget, Function, FunctionKind};
+pub use crate::function::{ControlFlowEdge, ControlFlowGraph, ControlFlowRef, ControlFlowTarget, DisassemblyOptions, Function, FunctionKind};
+
+pub mod interpreter;
+pub use crate::interpreter::Interpreter;
 
 pub mod program;
 pub use crate::program::{CallGraph, CallGraphRef, CallTarget, Program};
 
 pub mod project;
-pub use crate::project::Project;
+pub use crate::project::{FileMapping, Project, Relocation};
 
 pub mod region;
-pub use crate::region::{Region, World};
+pub use crate::region::{Region, Section, Segment, World};
 
 pub mod layer;
-pub use crate::layer::{Layer, LayerIter, OpaqueLayer};
+pub use crate::layer::{KeyOp, Layer, LayerIter, MappedLayer, OpaqueLayer, Transform};
 
 pub mod result;
 pub use crate::result::{Error, Result};
 
 // file formats
 pub mod loader;
-pub use crate::loader::{Machine, load};
+pub use crate::loader::{Machine, load, load_arch, load_raw};
+
+pub mod dwarf;
+pub use crate::dwarf::{DebugFunction, DebugVariable, InlinedSubroutine};
+
+pub mod pdb;
+
+pub mod coredump;
+
+mod wide;
@@ -16,21 +16,41 @@
  * along with this program.  If not, see <http://www.gnu.org/licenses/>.
  */
 
-//! Loader for 32 and 64-bit ELF, PE, and Mach-o files.
+//! Loader for 32 and 64-bit ELF, PE, and Mach-o files, static archives of ELF objects,
+//! Intel HEX/Motorola S-record firmware images and C64, NES and Atari 6502 programs.
+//!
+//! DWARF debug information in ELF executables is used to name functions that have no symbol and
+//! is kept in `Program::debug_info`. PE images get their function symbols from a matching PDB
+//! file next to them. Mach-o images are scanned for `LC_FUNCTION_STARTS` and symbol stubs.
+//!
+//! Linux ELF core files and Windows minidumps are loaded as a `Region` holding the dumped memory.
+//! The register state of the dumped threads is added as comments.
+//!
+//! Files are memory mapped. Segments of executables and dumps are `MappedLayer`s slicing the
+//! mapping, so loading large files does not copy their contents.
+//!
+//! The access rights of ELF program headers, PE sections and Mach-o segments are kept as
+//! `Segment`s of the `Region`.
 
 
-use crate::{Bound, CallTarget, Layer, Program, Project, Region, Result, Rvalue};
+use crate::{Architecture, Bound, CallTarget, DebugFunction, Error, FileMapping, Layer, MappedLayer, OpaqueLayer, Program, Project, Region, Relocation, Result, Rvalue, Section, Segment};
+use crate::{coredump, dwarf, pdb};
+use crate::region::{SECTION_ALLOC, SECTION_EXECINSTR, SECTION_WRITE, SEGMENT_EXECUTE, SEGMENT_READ, SEGMENT_WRITE};
 use goblin::{self, Hint, archive, elf, mach, pe};
 use goblin::elf::program_header;
 
-use panopticon_graph_algos::MutableGraphTrait;
+use byteorder::{ByteOrder, LittleEndian, ReadBytesExt};
+use panopticon_graph_algos::{MutableGraphTrait, VertexListGraphTrait};
+use std::cmp;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
-use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::io::{Cursor, Read};
 use std::path::Path;
+use std::str::FromStr;
 use uuid::Uuid;
 
 /// CPU the binary file is intended for.
-#[derive(Clone,Copy,Debug)]
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
 pub enum Machine {
     /// 8-bit AVR
     Avr,
@@ -38,50 +58,163 @@ pub enum Machine {
     Amd64,
     /// Intel x86
     Ia32,
+    /// MOS 6502
+    Mos6502,
+}
+
+impl FromStr for Machine {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Machine> {
+        match s.to_lowercase().as_str() {
+            "avr" => Ok(Machine::Avr),
+            "amd64" | "x86_64" | "x86-64" => Ok(Machine::Amd64),
+            "ia32" | "x86" | "i386" => Ok(Machine::Ia32),
+            "mos6502" | "6502" => Ok(Machine::Mos6502),
+            _ => Err(format!("Unknown architecture: {}", s).into()),
+        }
+    }
+}
+
+/// Single architecture slice of a fat Mach-o binary.
+#[derive(Clone,Copy,Debug)]
+pub struct FatArch {
+    /// Mach-o CPU type of the slice.
+    pub cputype: u32,
+    /// CPU the slice is intended for or `None` if Panopticon does not support it.
+    pub machine: Option<Machine>,
+    /// Start of the slice in bytes from the beginning of the file.
+    pub offset: usize,
+    /// Size of the slice in bytes.
+    pub size: usize,
+}
+
+// Mach-o segment protection bits
+const VM_PROT_READ: u32 = 0x1;
+const VM_PROT_WRITE: u32 = 0x2;
+const VM_PROT_EXECUTE: u32 = 0x4;
+const INDIRECT_SYMBOL_LOCAL: u32 = 0x8000_0000;
+const INDIRECT_SYMBOL_ABS: u32 = 0x4000_0000;
+
+/// Maps a Mach-o CPU type to the `Machine` we use to disassemble it.
+fn mach_cputype_to_machine(cputype: u32) -> Option<Machine> {
+    match cputype {
+        mach::cputype::CPU_TYPE_X86 => Some(Machine::Ia32),
+        mach::cputype::CPU_TYPE_X86_64 => Some(Machine::Amd64),
+        _ => None,
+    }
+}
+
+/// Lists all architecture slices of the fat Mach-o binary in `bytes`.
+pub fn fat_arches(bytes: &[u8]) -> Result<Vec<FatArch>> {
+    let multi = mach::MultiArch::new(bytes)?;
+    let mut ret = Vec::new();
+
+    for arch in multi.arches()? {
+        let offset = arch.offset as usize;
+        let size = arch.size as usize;
+
+        if offset + size > bytes.len() {
+            return Err(
+                format!(
+                    "Fat arch {} out of bounds: range {:?} greater than len {}",
+                    mach::cputype::cpu_type_to_str(arch.cputype),
+                    offset..offset + size,
+                    bytes.len()
+                )
+                        .into()
+            );
+        }
+
+        ret.push(
+            FatArch {
+                cputype: arch.cputype,
+                machine: mach_cputype_to_machine(arch.cputype),
+                offset: offset,
+                size: size,
+            }
+        );
+    }
+
+    Ok(ret)
+}
+
+/// Parses the slice of the fat Mach-o binary in `bytes` that is intended for `machine` and
+/// creates a `Project` from it.
+fn load_fat(bytes: &[u8], file: Option<&MappedLayer>, machine: Machine, name: String) -> Result<(Project, Machine)> {
+    let arches = fat_arches(bytes)?;
+    debug!("fat arches: {:#?}", &arches);
+
+    match arches.iter().find(|arch| arch.machine == Some(machine)) {
+        Some(arch) => {
+            debug!("Loading {:?} slice at {:#x}", machine, arch.offset);
+            // keep the bytes before the slice so file mappings are relative to the fat file
+            match arch.offset.checked_add(arch.size).and_then(|end| bytes.get(..end)) {
+                Some(bytes) => load_mach_file(bytes, arch.offset, file, name),
+                None => Err(format!("{:?} slice at {:#x} is out of range", machine, arch.offset).into()),
+            }
+        }
+        None => {
+            let available = arches.iter().map(|arch| mach::cputype::cpu_type_to_str(arch.cputype)).collect::<Vec<_>>();
+            Err(format!("No {:?} slice in fat binary, available: {}", machine, available.join(", ")).into())
+        }
+    }
 }
 
 /// Parses a non-fat Mach-o binary from `bytes` at `offset` and creates a `Project` from it. Returns the `Project` instance and
 /// the CPU its intended for.
 pub fn load_mach(bytes: &[u8], offset: usize, name: String) -> Result<(Project, Machine)> {
-    let binary = mach::MachO::parse(&bytes, offset)?;
+    load_mach_file(bytes, offset, None, name)
+}
+
+/// Like `load_mach` but slices segments from `file` if `bytes` are part of it.
+fn load_mach_file(bytes: &[u8], offset: usize, file: Option<&MappedLayer>, name: String) -> Result<(Project, Machine)> {
+    // offsets inside the binary are relative to its header, not to `bytes`
+    let binary = match bytes.get(offset..) {
+        Some(slice) => mach::MachO::parse(slice, 0)?,
+        None => return Err(format!("Mach-o header at {:#x} is out of range", offset).into()),
+    };
     debug!("mach: {:#?}", &binary);
     let mut base = 0x0;
     let cputype = binary.header.cputype;
-    let (machine, mut reg) = match cputype {
-        mach::cputype::CPU_TYPE_X86 => {
+    let (machine, mut reg) = match mach_cputype_to_machine(cputype) {
+        Some(Machine::Ia32) => {
             let reg = Region::undefined("RAM".to_string(), 0x1_0000_0000);
             (Machine::Ia32, reg)
         }
-        mach::cputype::CPU_TYPE_X86_64 => {
+        Some(Machine::Amd64) => {
             let reg = Region::undefined("RAM".to_string(), 0xFFFF_FFFF_FFFF_FFFF);
             (Machine::Amd64, reg)
         }
-        machine => {
+        _ => {
             return Err(
                 format!(
                     "Unsupported machine ({:#x}): {}",
-                    machine,
-                    mach::cputype::cpu_type_to_str(machine)
+                    cputype,
+                    mach::cputype::cpu_type_to_str(cputype)
                 )
                         .into()
             )
         }
     };
 
+    let mut mappings = vec![];
+
     for segment in &*binary.segments {
-        let offset = segment.fileoff as usize;
+        // file offsets are relative to the start of the Mach-o header
+        let fileoff = offset + segment.fileoff as usize;
         let filesize = segment.filesize as usize;
-        if offset + filesize > bytes.len() {
+        if fileoff + filesize > bytes.len() {
             return Err(
                 format!(
                     "Failed to read segment: range {:?} greater than len {}",
-                    offset..offset + filesize,
+                    fileoff..fileoff + filesize,
                     bytes.len()
                 )
                         .into()
             );
         }
-        let section = &bytes[offset..offset + filesize];
+        let section = &bytes[fileoff..fileoff + filesize];
         let start = segment.vmaddr;
         let end = start + segment.vmsize;
         let name = segment.name()?;
@@ -91,7 +224,24 @@ pub fn load_mach(bytes: &[u8], offset: usize, name: String) -> Result<(Project,
             segment.vmsize,
             start
         );
-        reg.cover(Bound::new(start, end), Layer::wrap(Vec::from(section)));
+        reg.cover(Bound::new(start, end), file_layer(file, section));
+        if segment.vmsize > 0 {
+            let mut flags = 0;
+            if segment.initprot & VM_PROT_READ != 0 {
+                flags |= SEGMENT_READ;
+            }
+            if segment.initprot & VM_PROT_WRITE != 0 {
+                flags |= SEGMENT_WRITE;
+            }
+            if segment.initprot & VM_PROT_EXECUTE != 0 {
+                flags |= SEGMENT_EXECUTE;
+            }
+
+            reg.add_segment(Segment { name: Some(name.to_string()), bound: Bound::new(start, end), flags: flags });
+        }
+        if filesize > 0 {
+            mappings.push(FileMapping { bound: Bound::new(start, start + filesize as u64), offset: fileoff as u64 });
+        }
         if name == "__TEXT" {
             base = segment.vmaddr;
             debug!("Setting vm address base to {:#x}", base);
@@ -107,6 +257,8 @@ pub fn load_mach(bytes: &[u8], offset: usize, name: String) -> Result<(Project,
     let mut prog = Program::new("prog0");
     let mut proj = Project::new(name.clone(), reg);
 
+    proj.file_mappings = mappings;
+
     let entry = binary.entry;
 
     if entry != 0 {
@@ -128,11 +280,98 @@ pub fn load_mach(bytes: &[u8], offset: usize, name: String) -> Result<(Project,
     }
 
     for import in binary.imports()? {
-        debug!("Import {}: {:#x}", import.name, import.offset);
-        proj.imports.insert(import.offset, import.name.to_string());
+        // calls go through the symbol pointer, not the offset of the bind opcode
+        debug!("Import {}: {:#x}", import.name, import.address);
+        proj.imports.insert(import.address, import.name.to_string());
     }
 
     debug!("Imports: {:?}", &proj.imports);
+
+    let mut known = todo_addresses(&prog);
+
+    for cmd in &binary.load_commands {
+        if let mach::load_command::CommandVariant::FunctionStarts(ref starts) = cmd.command {
+            let start = offset + starts.dataoff as usize;
+            let data = bytes.get(start..start + starts.datasize as usize).unwrap_or(&[]);
+
+            for addr in mach_function_starts(data, base) {
+                if known.insert(addr) {
+                    debug!("Function start at {:#x}", addr);
+                    prog.call_graph.add_vertex(CallTarget::Todo(Rvalue::new_u64(addr), None, Uuid::new_v4()));
+                }
+            }
+        }
+    }
+
+    // symbol stubs are named after the indirect symbol table entry of their slot and become
+    // `FunctionKind::Stub` in `Program::insert`
+    let indirect = binary
+        .load_commands
+        .iter()
+        .filter_map(
+            |cmd| match cmd.command {
+                mach::load_command::CommandVariant::Dysymtab(ref dysymtab) => Some(dysymtab),
+                _ => None,
+            }
+        )
+        .next()
+        .map(
+            |dysymtab| {
+                let start = offset + dysymtab.indirectsymoff as usize;
+                let data = bytes.get(start..start + dysymtab.nindirectsyms as usize * 4).unwrap_or(&[]);
+                data.chunks(4).map(LittleEndian::read_u32).collect::<Vec<u32>>()
+            }
+        )
+        .unwrap_or_default();
+
+    // the lazy symbol pointer each stub jumps through has the same indirect symbol table entry
+    let ptr_size = if machine == Machine::Amd64 { 8 } else { 4 };
+    let mut lazy_ptrs = HashMap::<u32, u64>::new();
+
+    for segment in &*binary.segments {
+        for section in segment.sections()? {
+            if section.flags & mach::constants::SECTION_TYPE != mach::constants::S_LAZY_SYMBOL_POINTERS {
+                continue;
+            }
+
+            for slot in 0..section.size / ptr_size {
+                if let Some(&sym) = indirect.get(section.reserved1 as usize + slot as usize) {
+                    lazy_ptrs.entry(sym).or_insert(section.addr + slot * ptr_size);
+                }
+            }
+        }
+    }
+
+    for segment in &*binary.segments {
+        for section in segment.sections()? {
+            if section.flags & mach::constants::SECTION_TYPE != mach::constants::S_SYMBOL_STUBS || section.reserved2 == 0 {
+                continue;
+            }
+
+            let start = offset + section.offset as usize;
+            let contents = bytes.get(start..start + section.size as usize).unwrap_or(&[]);
+
+            for (i, stub) in contents.chunks(section.reserved2 as usize).enumerate() {
+                let addr = section.addr + i as u64 * section.reserved2 as u64;
+                // reserved1 is the index of the section's first entry in the indirect symbol table
+                let sym = match indirect.get(section.reserved1 as usize + i) {
+                    Some(&sym) if sym & (INDIRECT_SYMBOL_LOCAL | INDIRECT_SYMBOL_ABS) == 0 => sym,
+                    _ => continue,
+                };
+                let name = binary.symbols.as_ref().and_then(|syms| syms.get(sym as usize).ok()).map(|(name, _)| name.to_string());
+                let ptr = lazy_ptrs.get(&sym).cloned().or_else(|| mach_stub_target(stub, addr, machine));
+
+                if let (Some(name), Some(ptr)) = (name, ptr) {
+                    debug!("Stub for {} at {:#x} through {:#x}", name, addr, ptr);
+                    if known.insert(addr) {
+                        prog.call_graph.add_vertex(CallTarget::Todo(Rvalue::new_u64(addr), None, Uuid::new_v4()));
+                    }
+                    prog.stubs.insert(addr, (name, ptr));
+                }
+            }
+        }
+    }
+
     prog.imports = proj.imports.clone();
     proj.comments.insert(("base".to_string(), entry), "main".to_string());
     proj.code.push(prog);
@@ -140,230 +379,1704 @@ pub fn load_mach(bytes: &[u8], offset: usize, name: String) -> Result<(Project,
     Ok((proj, machine))
 }
 
-/// Parses an ELF 32/64-bit binary from `bytes` and creates a `Project` from it. Returns the `Project` instance and
-/// the CPU its intended for.
-fn load_elf(bytes: &[u8], name: String) -> Result<(Project, Machine)> {
-    use std::collections::HashSet;
-
-    let mut cursor = Cursor::new(&bytes);
-    let binary = elf::Elf::parse(&bytes)?;
-    debug!("elf: {:#?}", &binary);
+/// Decodes the ULEB128 encoded offsets of a `LC_FUNCTION_STARTS` table into addresses. The first
+/// offset is relative to `base`, all others to the previous function.
+fn mach_function_starts(data: &[u8], base: u64) -> Vec<u64> {
+    let mut ret = vec![];
+    let mut addr = base;
+    let mut delta = 0u64;
+    let mut shift = 0;
 
-    let entry = binary.entry;
-    let (machine, mut reg) = match binary.header.e_machine {
-        elf::header::EM_X86_64 => {
-            let reg = Region::undefined("RAM".to_string(), 0xFFFF_FFFF_FFFF_FFFF);
-            (Machine::Amd64, reg)
-        }
-        elf::header::EM_386 => {
-            let reg = Region::undefined("RAM".to_string(), 0x1_0000_0000);
-            (Machine::Ia32, reg)
+    for &b in data {
+        if shift < 64 {
+            delta |= ((b & 0x7f) as u64) << shift;
         }
-        elf::header::EM_AVR => {
-            let reg = Region::undefined("Flash".to_string(), 0x2_0000);
-            (Machine::Avr, reg)
-        }
-        machine => return Err(format!("Unsupported machine: {}", machine).into()),
-    };
-
-    for ph in &binary.program_headers {
-        if ph.p_type == program_header::PT_LOAD {
-            let mut buf = vec![0u8; ph.p_filesz as usize];
-
-            debug!(
-                "Load ELF {} bytes segment to {:#x}",
-                ph.p_filesz,
-                ph.p_vaddr
-            );
+        shift += 7;
 
-            if cursor.seek(SeekFrom::Start(ph.p_offset)).ok() == Some(ph.p_offset) {
-                cursor.read_exact(&mut buf)?;
-                reg.cover(
-                    Bound::new(ph.p_vaddr, ph.p_vaddr + ph.p_filesz),
-                    Layer::wrap(buf),
-                );
-            } else {
-                return Err("Failed to read segment".into());
+        if b & 0x80 == 0 {
+            // the table is zero terminated
+            if delta == 0 {
+                break;
             }
+
+            addr = addr.wrapping_add(delta);
+            ret.push(addr);
+            delta = 0;
+            shift = 0;
         }
     }
 
-    let name = if let &Some(ref soname) = &binary.soname {
-        soname.to_string()
-    } else {
-        name
-    };
+    ret
+}
 
-    debug!("interpreter: {:?}", &binary.interpreter);
+/// Returns the address of the symbol pointer the stub `code` at `addr` jumps through.
+fn mach_stub_target(code: &[u8], addr: u64, machine: Machine) -> Option<u64> {
+    // jmp *ptr, RIP relative on AMD64
+    if code.len() < 6 || code[0] != 0xff || code[1] != 0x25 {
+        return None;
+    }
 
-    let mut prog = Program::new("prog0");
-    let mut proj = Project::new(name.clone(), reg);
+    let disp = LittleEndian::read_u32(&code[2..]);
 
-    prog.call_graph.add_vertex(CallTarget::Todo(Rvalue::new_u64(entry as u64), Some(name), Uuid::new_v4()));
+    match machine {
+        Machine::Amd64 => Some((addr + 6).wrapping_add(disp as i32 as i64 as u64)),
+        Machine::Ia32 => Some(disp as u64),
+        _ => None,
+    }
+}
 
-    let add_sym = |prog: &mut Program, sym: &elf::Sym, name: &str| {
-        let name = name.to_string();
-        let addr = sym.st_value;
-        debug!("Symbol: {} @ 0x{:x}: {:?}", name, addr, sym);
-        if sym.is_function() {
-            if sym.is_import() {
-                prog.call_graph.add_vertex(CallTarget::Symbolic(name, Uuid::new_v4()));
-            } else {
-                prog.call_graph.add_vertex(CallTarget::Todo(Rvalue::new_u64(addr), Some(name), Uuid::new_v4()));
+/// Returns address and file contents of the section called `name`.
+fn elf_section<'a>(binary: &elf::Elf, bytes: &'a [u8], name: &str) -> Option<(u64, &'a [u8])> {
+    binary
+        .section_headers
+        .iter()
+        .find(|sh| &binary.shdr_strtab[sh.sh_name] == name && sh.sh_type != elf::section_header::SHT_NOBITS)
+        .and_then(
+            |sh| {
+                let end = sh.sh_offset.checked_add(sh.sh_size)?;
+                bytes.get(sh.sh_offset as usize..end as usize).map(|b| (sh.sh_addr, b))
             }
+        )
+}
+
+/// Returns the file contents of the section called `name`.
+fn elf_section_contents<'a>(binary: &elf::Elf, bytes: &'a [u8], name: &str) -> Option<&'a [u8]> {
+    elf_section(binary, bytes, name).map(|(_, b)| b)
+}
+
+/// Returns the functions described by the `.eh_frame` FDEs and the `.eh_frame_hdr` search table.
+/// Functions only found in the latter have an empty `Bound`. The search table is found through
+/// the `PT_GNU_EH_FRAME` segment if the section headers are stripped.
+fn elf_unwind_info(binary: &elf::Elf, bytes: &[u8]) -> Vec<Bound> {
+    let address_size = if binary.is_64 { 8 } else { 4 };
+    let mut ret = match elf_section(binary, bytes, ".eh_frame") {
+        Some((addr, data)) => {
+            dwarf::parse_eh_frame(data, addr, address_size).unwrap_or_else(
+                |e| {
+                    warn!("Failed to read .eh_frame: {}", e);
+                    vec![]
+                }
+            )
         }
+        None => vec![],
     };
+    let hdr = elf_section(binary, bytes, ".eh_frame_hdr").or_else(
+        || {
+            binary
+                .program_headers
+                .iter()
+                .find(|ph| ph.p_type == program_header::PT_GNU_EH_FRAME)
+                .and_then(|ph| bytes.get(ph.p_offset as usize..(ph.p_offset + ph.p_filesz) as usize).map(|b| (ph.p_vaddr, b)))
+        }
+    );
 
-    let resolve_import_address = |proj: &mut Project, relocs: &[elf::Reloc], name: &str| {
-        for reloc in relocs {
-            let pltsym = &binary.dynsyms[reloc.r_sym];
-            let pltname = &binary.dynstrtab[pltsym.st_name];
-            if pltname == name {
-                debug!("Import match {}: {:#x} {:?}", name, reloc.r_offset, pltsym);
-                proj.imports.insert(reloc.r_offset as u64, name.to_string());
-                return true;
+    if let Some((addr, data)) = hdr {
+        match dwarf::parse_eh_frame_hdr(data, addr, address_size) {
+            Ok(starts) => {
+                for start in starts {
+                    if !ret.iter().any(|b| b.start == start) {
+                        ret.push(Bound::new(start, start));
+                    }
+                }
             }
+            Err(e) => warn!("Failed to read .eh_frame_hdr: {}", e),
         }
-        false
-    };
-
-    let mut seen_syms = HashSet::<u64>::new();
+    }
 
-    // add dynamic symbol information (non-strippable)
-    for sym in &binary.dynsyms {
-        let name = &binary.dynstrtab[sym.st_name];
+    ret
+}
 
-        add_sym(&mut prog, sym, name);
-        seen_syms.insert(sym.st_value);
+/// Reads the DWARF function descriptions from the `.debug_*` sections of `binary`. Malformed debug
+/// information is logged and ignored.
+fn elf_debug_info(binary: &elf::Elf, bytes: &[u8], machine: Machine) -> Vec<DebugFunction> {
+    match (elf_section_contents(binary, bytes, ".debug_info"), elf_section_contents(binary, bytes, ".debug_abbrev")) {
+        (Some(info), Some(abbrev)) => {
+            let sections = dwarf::Sections {
+                debug_abbrev: abbrev,
+                debug_str: elf_section_contents(binary, bytes, ".debug_str"),
+                debug_addr: elf_section_contents(binary, bytes, ".debug_addr"),
+                debug_str_offsets: elf_section_contents(binary, bytes, ".debug_str_offsets"),
+                debug_rnglists: elf_section_contents(binary, bytes, ".debug_rnglists"),
+                debug_ranges: elf_section_contents(binary, bytes, ".debug_ranges"),
+            };
 
-        let name = &binary.dynstrtab[sym.st_name];
-        if !resolve_import_address(&mut proj, &binary.pltrelocs, name) {
-            if sym.is_function() {
-                if !resolve_import_address(&mut proj, &binary.dynrelas, name) {
-                    resolve_import_address(&mut proj, &binary.dynrels, name);
+            match dwarf::parse(machine, info, &sections) {
+                Ok(funcs) => funcs,
+                Err(e) => {
+                    warn!("Failed to read DWARF debug information: {}", e);
+                    vec![]
                 }
             }
         }
+        _ => vec![],
     }
-    debug!("Imports: {:#?}", &proj.imports);
+}
 
-    // add strippable symbol information
-    for sym in &binary.syms {
-        let name = &binary.strtab[sym.st_name];
-        if !seen_syms.contains(&sym.st_value) {
-            add_sym(&mut prog, sym, &name);
-        }
-        seen_syms.insert(sym.st_value);
+/// Returns an empty `Region` spanning the address space of `machine`.
+fn address_space(machine: Machine) -> Region {
+    match machine {
+        Machine::Avr => Region::undefined("Flash".to_string(), 0x2_0000),
+        Machine::Ia32 => Region::undefined("RAM".to_string(), 0x1_0000_0000),
+        Machine::Amd64 => Region::undefined("RAM".to_string(), 0xFFFF_FFFF_FFFF_FFFF),
+        Machine::Mos6502 => Region::undefined("RAM".to_string(), 0x1_0000),
     }
-    prog.imports = proj.imports.clone();
-    proj.comments.insert(("base".to_string(), entry), "main".to_string());
-    proj.code.push(prog);
+}
 
-    Ok((proj, machine))
+/// Returns the CPU for the ELF `e_machine` value and an empty `Region` spanning its address space.
+fn elf_machine(e_machine: u16) -> Result<(Machine, Region)> {
+    let machine = match e_machine {
+        elf::header::EM_X86_64 => Machine::Amd64,
+        elf::header::EM_386 => Machine::Ia32,
+        elf::header::EM_AVR => Machine::Avr,
+        machine => return Err(format!("Unsupported machine: {}", machine).into()),
+    };
+
+    Ok((machine, address_space(machine)))
 }
 
-/// Parses a PE32/PE32+ file from `bytes` and create a project from it.
-fn load_pe(bytes: &[u8], name: String) -> Result<(Project, Machine)> {
-    let pe = pe::PE::parse(&bytes)?;
-    debug!("pe: {:#?}", &pe);
-    let image_base = pe.image_base as u64;
-    let mut ram = Region::undefined("RAM".to_string(), 0x100000000);
-    for section in &pe.sections {
-        let name = String::from_utf8_lossy(&section.name);
-        debug!("section: {}", name);
-        let virtual_address = section.virtual_address as u64;
-        let offset = section.pointer_to_raw_data as usize;
-        let (layer, size) = {
-            let vsize = section.virtual_size as u64;
-            let size = section.size_of_raw_data as usize;
-            if size > 0 {
-                if offset + size >= bytes.len() {
-                    debug!(
-                        "bad section pointer: {:#x} + {:#x} >= {:#x}",
-                        offset,
-                        size,
-                        bytes.len()
-                    );
-                    (Layer::undefined(0), 0)
-                } else {
-                    debug!("mapped '{}': {:?}", name, offset..offset + size);
-                    (Layer::wrap(bytes[offset..offset + size].to_vec()), size as u64)
-                }
-            } else {
-                debug!("bss '{}'", name);
-                (Layer::undefined(vsize), vsize)
-            }
-        };
-        let begin = image_base + virtual_address;
-        let end = image_base + virtual_address + size as u64;
-        let bound = Bound::new(begin, end);
-        debug!("bound: {:?}", &bound);
-        if !ram.cover(bound, layer) {
-            debug!("bad cover");
-            return Err(format!("Cannot cover bound: {:?}", Bound::new(begin, end)).into());
-        }
+/// Rounds `addr` up to the next multiple of `align`.
+fn align_up(addr: u64, align: u64) -> u64 {
+    if align > 1 {
+        (addr + align - 1) / align * align
+    } else {
+        addr
     }
-    let entry = (pe.image_base + pe.entry) as u64;
-    debug!("entry: {:#x}", entry);
-    let mut prog = Program::new("prog0");
-    let mut proj = Project::new(name.to_string(), ram);
+}
 
-    prog.call_graph
-        .add_vertex(
-            CallTarget::Todo(
-                Rvalue::new_u64(entry),
-                Some(name.to_string()),
-                Uuid::new_v4(),
+// x86-64 relocation types
+const R_X86_64_NONE: u32 = 0;
+const R_X86_64_64: u32 = 1;
+const R_X86_64_PC32: u32 = 2;
+const R_X86_64_PLT32: u32 = 4;
+const R_X86_64_32: u32 = 10;
+const R_X86_64_32S: u32 = 11;
+const R_X86_64_PC64: u32 = 24;
+
+// i386 relocation types
+const R_386_NONE: u32 = 0;
+const R_386_32: u32 = 1;
+const R_386_PC32: u32 = 2;
+const R_386_PLT32: u32 = 4;
+
+// AVR relocation types
+const R_AVR_NONE: u32 = 0;
+const R_AVR_32: u32 = 1;
+const R_AVR_7_PCREL: u32 = 2;
+const R_AVR_13_PCREL: u32 = 3;
+const R_AVR_16: u32 = 4;
+const R_AVR_16_PM: u32 = 5;
+const R_AVR_LO8_LDI: u32 = 6;
+const R_AVR_HI8_LDI: u32 = 7;
+const R_AVR_HH8_LDI: u32 = 8;
+const R_AVR_LO8_LDI_PM: u32 = 12;
+const R_AVR_HI8_LDI_PM: u32 = 13;
+const R_AVR_CALL: u32 = 18;
+
+/// Start of the synthetic address space relocatable objects are mapped to.
+const OBJECT_BASE: u64 = 0x1000;
+
+/// Single entry of a `SHT_REL` or `SHT_RELA` section.
+struct ElfReloc {
+    /// Offset of the relocated bytes from the start of the section
+    offset: u64,
+    /// Symbol table index
+    sym: usize,
+    /// Machine specific relocation type
+    typ: u32,
+    /// Explicit addend, `None` for `SHT_REL` entries.
+    addend: Option<i64>,
+}
+
+/// Parses the relocation section `sh` of a little endian ELF object.
+fn parse_elf_relocs(sh: &elf::section_header::SectionHeader, bytes: &[u8], is_64: bool) -> Result<Vec<ElfReloc>> {
+    let offset = sh.sh_offset as usize;
+    let size = sh.sh_size as usize;
+    let rela = sh.sh_type == elf::section_header::SHT_RELA;
+
+    if offset + size > bytes.len() {
+        return Err(
+            format!(
+                "Failed to read relocations: range {:?} greater than len {}",
+                offset..offset + size,
+                bytes.len()
             )
+                    .into()
         );
-
-    for export in pe.exports {
-        debug!("adding export: {:?}", &export);
-        prog.call_graph
-            .add_vertex(
-                CallTarget::Todo(
-                    Rvalue::new_u64(export.rva as u64 + image_base),
-                    Some(export.name.to_string()),
-                    Uuid::new_v4(),
-                )
-            );
     }
 
-    for import in pe.imports {
-        debug!(
-            "adding import: {:?} @ {:#x}",
-            &import,
-            import.rva + pe.image_base
-        );
-        prog.call_graph.add_vertex(CallTarget::Symbolic(import.name.into_owned(), Uuid::new_v4()));
+    let mut cursor = Cursor::new(&bytes[offset..offset + size]);
+    let mut ret = Vec::new();
+
+    while (cursor.position() as usize) < size {
+        let reloc = if is_64 {
+            let r_offset = cursor.read_u64::<LittleEndian>()?;
+            let r_info = cursor.read_u64::<LittleEndian>()?;
+            let addend = if rela { Some(cursor.read_i64::<LittleEndian>()?) } else { None };
+
+            ElfReloc { offset: r_offset, sym: (r_info >> 32) as usize, typ: r_info as u32, addend: addend }
+        } else {
+            let r_offset = cursor.read_u32::<LittleEndian>()?;
+            let r_info = cursor.read_u32::<LittleEndian>()?;
+            let addend = if rela { Some(cursor.read_i32::<LittleEndian>()? as i64) } else { None };
+
+            ElfReloc { offset: r_offset as u64, sym: (r_info >> 8) as usize, typ: r_info & 0xff, addend: addend }
+        };
+
+        ret.push(reloc);
     }
 
-    proj.comments.insert(("base".to_string(), entry), "main".to_string());
-    proj.code.push(prog);
-    Ok((proj, Machine::Ia32))
+    Ok(ret)
 }
 
-/// Load an ELF or PE file from disk and creates a `Project` from it. Returns the `Project` instance and
-/// the CPU its intended for.
-pub fn load(path: &Path) -> Result<(Project, Machine)> {
-    let name = path.file_name().map(|x| x.to_string_lossy().to_string()).unwrap_or("(encoding error)".to_string());
-    let mut fd = File::open(path)?;
-    let peek = goblin::peek(&mut fd)?;
-    if let Hint::Unknown(magic) = peek {
-        Err(format!("Tried to load an unknown file. Magic: {}", magic).into())
+/// Number of bytes patched by the relocation `typ` for the ELF machine `e_machine`.
+fn elf_reloc_width(e_machine: u16, typ: u32) -> usize {
+    match (e_machine, typ) {
+        (elf::header::EM_X86_64, R_X86_64_64) |
+        (elf::header::EM_X86_64, R_X86_64_PC64) => 8,
+        (elf::header::EM_X86_64, _) |
+        (elf::header::EM_386, _) |
+        (elf::header::EM_AVR, R_AVR_32) |
+        (elf::header::EM_AVR, R_AVR_CALL) => 4,
+        _ => 2,
+    }
+}
+
+/// Patches the relocation `typ` for the ELF machine `e_machine` into `data` at `offset`. `s` is the
+/// symbol address, `a` the addend and `p` the address of the patched bytes. Returns `false` if the
+/// relocation type is not supported.
+fn apply_elf_reloc(e_machine: u16, typ: u32, data: &mut [u8], offset: usize, s: u64, a: i64, p: u64) -> Result<bool> {
+    let value = (s as i64).wrapping_add(a) as u64;
+    let pcrel = value.wrapping_sub(p);
+    let width = elf_reloc_width(e_machine, typ);
+
+    if offset + width > data.len() {
+        return Err(format!("Relocation at {:#x} outside of section", p).into());
+    }
+
+    let buf = &mut data[offset..offset + width];
+
+    match (e_machine, typ) {
+        (elf::header::EM_X86_64, R_X86_64_NONE) |
+        (elf::header::EM_386, R_386_NONE) |
+        (elf::header::EM_AVR, R_AVR_NONE) => {}
+
+        (elf::header::EM_X86_64, R_X86_64_64) => LittleEndian::write_u64(buf, value),
+        (elf::header::EM_X86_64, R_X86_64_PC64) => LittleEndian::write_u64(buf, pcrel),
+        (elf::header::EM_X86_64, R_X86_64_PC32) |
+        (elf::header::EM_X86_64, R_X86_64_PLT32) |
+        (elf::header::EM_386, R_386_PC32) |
+        (elf::header::EM_386, R_386_PLT32) => LittleEndian::write_u32(buf, pcrel as u32),
+        (elf::header::EM_X86_64, R_X86_64_32) |
+        (elf::header::EM_X86_64, R_X86_64_32S) |
+        (elf::header::EM_386, R_386_32) |
+        (elf::header::EM_AVR, R_AVR_32) => LittleEndian::write_u32(buf, value as u32),
+
+        (elf::header::EM_AVR, R_AVR_16) => LittleEndian::write_u16(buf, value as u16),
+        (elf::header::EM_AVR, R_AVR_16_PM) => LittleEndian::write_u16(buf, (value >> 1) as u16),
+        (elf::header::EM_AVR, R_AVR_7_PCREL) => {
+            // brXX k: 1111 0xkk kkkk ksss, k in words relative to the next instruction
+            let k = ((pcrel.wrapping_sub(2) >> 1) & 0x7f) as u16;
+            let insn = LittleEndian::read_u16(buf);
+            LittleEndian::write_u16(buf, (insn & 0xfc07) | (k << 3));
+        }
+        (elf::header::EM_AVR, R_AVR_13_PCREL) => {
+            // rjmp/rcall k: xxxx kkkk kkkk kkkk, k in words relative to the next instruction
+            let k = ((pcrel.wrapping_sub(2) >> 1) & 0xfff) as u16;
+            let insn = LittleEndian::read_u16(buf);
+            LittleEndian::write_u16(buf, (insn & 0xf000) | k);
+        }
+        (elf::header::EM_AVR, R_AVR_CALL) => {
+            // jmp/call k: 1001 010k kkkk 11xk kkkk kkkk kkkk kkkk, k is a word address
+            let k = value >> 1;
+            let insn = LittleEndian::read_u16(&buf[0..2]);
+            let hi = (insn & 0xfe0e) | ((k >> 16) & 1) as u16 | ((((k >> 17) & 0x1f) as u16) << 4);
+            LittleEndian::write_u16(&mut buf[0..2], hi);
+            LittleEndian::write_u16(&mut buf[2..4], k as u16);
+        }
+        (elf::header::EM_AVR, R_AVR_LO8_LDI) |
+        (elf::header::EM_AVR, R_AVR_HI8_LDI) |
+        (elf::header::EM_AVR, R_AVR_HH8_LDI) |
+        (elf::header::EM_AVR, R_AVR_LO8_LDI_PM) |
+        (elf::header::EM_AVR, R_AVR_HI8_LDI_PM) => {
+            // ldi Rd, K: 1110 KKKK dddd KKKK
+            let k = match typ {
+                R_AVR_LO8_LDI => value,
+                R_AVR_HI8_LDI => value >> 8,
+                R_AVR_HH8_LDI => value >> 16,
+                R_AVR_LO8_LDI_PM => value >> 1,
+                _ => value >> 9,
+            } as u16 & 0xff;
+            let insn = LittleEndian::read_u16(buf);
+            LittleEndian::write_u16(buf, (insn & 0xf0f0) | (k & 0x0f) | ((k & 0xf0) << 4));
+        }
+
+        _ => return Ok(false),
+    }
+
+    Ok(true)
+}
+
+/// Maps the ELF object `binary` into `reg`, starting at `base`. Object files have no program headers,
+/// so all allocatable sections are laid out one after another. Relocations are applied, with each
+/// undefined symbol getting a slot after the last section. These slots are recorded in `imports`
+/// and the undefined symbols are added to `prog` as `CallTarget::Symbolic`, calls to the slots are
+/// linked to them by `Program::insert`. Function symbols are added to `prog`. Returns the first
+/// address after the object.
+fn load_elf_object(
+    binary: &elf::Elf,
+    bytes: &[u8],
+    base: u64,
+    reg: &mut Region,
+    prog: &mut Program,
+    imports: &mut HashMap<u64, String>,
+) -> Result<u64> {
+    let mut addrs = vec![None; binary.section_headers.len()];
+    let mut contents: Vec<Option<Vec<u8>>> = vec![None; binary.section_headers.len()];
+    let mut next = base;
+
+    for (idx, sh) in binary.section_headers.iter().enumerate() {
+        if sh.sh_flags & elf::section_header::SHF_ALLOC as u64 == 0 || sh.sh_size == 0 {
+            continue;
+        }
+
+        let start = align_up(next, sh.sh_addralign);
+
+        if sh.sh_type != elf::section_header::SHT_NOBITS {
+            let offset = sh.sh_offset as usize;
+            let size = sh.sh_size as usize;
+
+            if offset + size > bytes.len() {
+                return Err(
+                    format!(
+                        "Failed to read section: range {:?} greater than len {}",
+                        offset..offset + size,
+                        bytes.len()
+                    )
+                            .into()
+                );
+            }
+            contents[idx] = Some(bytes[offset..offset + size].to_vec());
+        }
+
+        debug!(
+            "Map ELF section {} ({} bytes) to {:#x}",
+            &binary.shdr_strtab[sh.sh_name],
+            sh.sh_size,
+            start
+        );
+
+        addrs[idx] = Some(start);
+        next = start + sh.sh_size;
+    }
+
+    let slot_size = if binary.is_64 { 8 } else { 4 };
+    let mut externs = HashMap::<usize, u64>::new();
+    let mut next_extern = align_up(next, 16);
+
+    for sh in binary.section_headers.iter() {
+        if sh.sh_type != elf::section_header::SHT_RELA && sh.sh_type != elf::section_header::SHT_REL {
+            continue;
+        }
+
+        let target = sh.sh_info as usize;
+        let section = match addrs.get(target) {
+            Some(&Some(addr)) => addr,
+            _ => continue,
+        };
+
+        for reloc in parse_elf_relocs(sh, bytes, binary.is_64)? {
+            let sym = match binary.syms.get(reloc.sym) {
+                Some(sym) => sym,
+                None => return Err(format!("Relocation against unknown symbol {}", reloc.sym).into()),
+            };
+            let s = if sym.st_shndx == elf::section_header::SHN_UNDEF as usize || sym.st_shndx == elf::section_header::SHN_COMMON as usize {
+                let name = &binary.strtab[sym.st_name];
+                *externs.entry(reloc.sym).or_insert_with(
+                    || {
+                        let slot = next_extern;
+                        next_extern += slot_size;
+                        debug!("Import {}: {:#x}", name, slot);
+                        imports.insert(slot, name.to_string());
+                        prog.call_graph.add_vertex(CallTarget::Symbolic(name.to_string(), Uuid::new_v4()));
+                        slot
+                    }
+                )
+            } else if sym.st_shndx == elf::section_header::SHN_ABS as usize {
+                sym.st_value
+            } else if let Some(&Some(addr)) = addrs.get(sym.st_shndx) {
+                addr + sym.st_value
+            } else {
+                debug!("Relocation against symbol in unmapped section {}", sym.st_shndx);
+                continue;
+            };
+
+            let p = section + reloc.offset;
+            let data = match contents[target].as_mut() {
+                Some(data) => data,
+                None => return Err(format!("Relocation at {:#x} inside section without contents", p).into()),
+            };
+            let a = match reloc.addend {
+                Some(a) => a,
+                // SHT_REL keeps the addend in the relocated bytes
+                None => {
+                    let offset = reloc.offset as usize;
+                    let width = elf_reloc_width(binary.header.e_machine, reloc.typ);
+                    if offset + width > data.len() {
+                        return Err(format!("Relocation at {:#x} outside of section", p).into());
+                    }
+                    match width {
+                        8 => LittleEndian::read_i64(&data[offset..offset + 8]),
+                        4 => LittleEndian::read_i32(&data[offset..offset + 4]) as i64,
+                        _ => LittleEndian::read_i16(&data[offset..offset + 2]) as i64,
+                    }
+                }
+            };
+
+            if !apply_elf_reloc(binary.header.e_machine, reloc.typ, data, reloc.offset as usize, s, a, p)? {
+                debug!("Unsupported relocation type {} at {:#x}", reloc.typ, p);
+            }
+        }
+    }
+
+    for (idx, start) in addrs.iter().enumerate() {
+        if let &Some(start) = start {
+            let sh = &binary.section_headers[idx];
+            let size = sh.sh_size;
+            let layer = match contents[idx].take() {
+                Some(data) => Layer::wrap(data),
+                None => Layer::undefined(size),
+            };
+
+            if !reg.cover(Bound::new(start, start + size), layer) {
+                return Err(format!("Cannot cover bound: {:?}", Bound::new(start, start + size)).into());
+            }
+            reg.add_section(
+                Section {
+                    name: binary.shdr_strtab[sh.sh_name].to_string(),
+                    bound: Bound::new(start, start + size),
+                    flags: sh.sh_flags,
+                }
+            );
+
+            let mut flags = SEGMENT_READ;
+            if sh.sh_flags & elf::section_header::SHF_WRITE as u64 != 0 {
+                flags |= SEGMENT_WRITE;
+            }
+            if sh.sh_flags & elf::section_header::SHF_EXECINSTR as u64 != 0 {
+                flags |= SEGMENT_EXECUTE;
+            }
+            reg.add_segment(Segment { name: Some(binary.shdr_strtab[sh.sh_name].to_string()), bound: Bound::new(start, start + size), flags: flags });
+        }
+    }
+
+    for sym in &binary.syms {
+        // undefined symbols have section index 0, which is never mapped
+        if sym.is_function() {
+            if let Some(&Some(section)) = addrs.get(sym.st_shndx) {
+                let name = binary.strtab[sym.st_name].to_string();
+                let addr = section + sym.st_value;
+
+                debug!("Symbol: {} @ {:#x}", name, addr);
+                prog.call_graph.add_vertex(CallTarget::Todo(Rvalue::new_u64(addr), Some(name), Uuid::new_v4()));
+                if sym.st_size > 0 {
+                    prog.symbol_sizes.insert(addr, sym.st_size);
+                }
+            }
+        }
+    }
+
+    Ok(next_extern)
+}
+
+/// Parses a static archive and creates a `Project` with one `Program` for each ELF object inside.
+/// Each object is mapped into its own part of the address space, named after the archive member.
+fn load_archive(bytes: &[u8], name: String) -> Result<(Project, Machine)> {
+    let archive = archive::Archive::parse(&bytes)?;
+    debug!("archive: {:#?}", &archive);
+
+    let mut target: Option<(Machine, u16, Region)> = None;
+    let mut programs = Vec::new();
+    let mut imports = HashMap::new();
+    let mut base = OBJECT_BASE;
+
+    for member in archive.members() {
+        let member_bytes = archive.extract(member, &bytes)?;
+        let binary = match elf::Elf::parse(member_bytes) {
+            Ok(binary) => binary,
+            Err(e) => {
+                debug!("Skipping archive member {}: {}", member, e);
+                continue;
+            }
+        };
+
+        if target.is_none() {
+            let (machine, reg) = elf_machine(binary.header.e_machine)?;
+            target = Some((machine, binary.header.e_machine, reg));
+        }
+
+        let &mut (_, e_machine, ref mut reg) = target.as_mut().unwrap();
+        if binary.header.e_machine != e_machine {
+            return Err(
+                format!(
+                    "Archive member {} is for machine {}, expected {}",
+                    member,
+                    binary.header.e_machine,
+                    e_machine
+                )
+                        .into()
+            );
+        }
+
+        let mut prog = Program::new(member);
+        let mut prog_imports = HashMap::new();
+        let end = load_elf_object(&binary, member_bytes, base, reg, &mut prog, &mut prog_imports)?;
+
+        imports.extend(prog_imports.iter().map(|(k, v)| (*k, v.clone())));
+        prog.imports = prog_imports;
+        programs.push(prog);
+        base = align_up(end, 0x1000);
+    }
+
+    match target {
+        Some((machine, _, reg)) => {
+            let mut proj = Project::new(name, reg);
+            proj.code = programs;
+            proj.imports = imports;
+            Ok((proj, machine))
+        }
+        None => Err("Archive contains no ELF objects".into()),
+    }
+}
+
+/// Parses an ELF 32/64-bit binary from `bytes` and creates a `Project` from it. Returns the `Project` instance and
+/// the CPU its intended for. Segments are sliced from `file` if `bytes` are its contents.
+fn load_elf(bytes: &[u8], file: Option<&MappedLayer>, name: String) -> Result<(Project, Machine)> {
+    let binary = elf::Elf::parse(&bytes)?;
+    debug!("elf: {:#?}", &binary);
+
+    let entry = binary.entry;
+    if binary.header.e_type == elf::header::ET_CORE {
+        return load_elf_core(&binary, bytes, file, name);
+    }
+
+    let (machine, mut reg) = elf_machine(binary.header.e_machine)?;
+
+    if binary.header.e_type == elf::header::ET_REL {
+        let mut prog = Program::new("prog0");
+        let mut imports = HashMap::new();
+
+        load_elf_object(&binary, bytes, OBJECT_BASE, &mut reg, &mut prog, &mut imports)?;
+        debug!("Imports: {:#?}", &imports);
+
+        let mut proj = Project::new(name, reg);
+        prog.imports = imports.clone();
+        proj.imports = imports;
+        proj.code.push(prog);
+
+        return Ok((proj, machine));
+    }
+
+    let mut mappings = vec![];
+
+    for ph in &binary.program_headers {
+        if ph.p_type == program_header::PT_LOAD {
+            let start = ph.p_offset as usize;
+
+            debug!(
+                "Load ELF {} bytes segment to {:#x}",
+                ph.p_filesz,
+                ph.p_vaddr
+            );
+
+            match start.checked_add(ph.p_filesz as usize) {
+                Some(end) if end <= bytes.len() => {
+                    reg.cover(
+                        Bound::new(ph.p_vaddr, ph.p_vaddr + ph.p_filesz),
+                        file_layer(file, &bytes[start..end]),
+                    );
+                    mappings.push(FileMapping { bound: Bound::new(ph.p_vaddr, ph.p_vaddr + ph.p_filesz), offset: ph.p_offset });
+                }
+                _ => return Err("Failed to read segment".into()),
+            }
+
+            add_elf_segment(&mut reg, ph);
+        }
+    }
+
+    for sh in &binary.section_headers {
+        if sh.sh_flags & elf::section_header::SHF_ALLOC as u64 != 0 && sh.sh_addr != 0 {
+            let section = Section {
+                name: binary.shdr_strtab[sh.sh_name].to_string(),
+                bound: Bound::new(sh.sh_addr, sh.sh_addr + sh.sh_size),
+                flags: sh.sh_flags,
+            };
+
+            debug!("Section {} at {:?}", section.name, section.bound);
+            if !reg.add_section(section) {
+                debug!("Section {} outside of address space", &binary.shdr_strtab[sh.sh_name]);
+            }
+        }
+    }
+
+    let name = if let &Some(ref soname) = &binary.soname {
+        soname.to_string()
+    } else {
+        name
+    };
+
+    debug!("interpreter: {:?}", &binary.interpreter);
+
+    let mut prog = Program::new("prog0");
+    let mut proj = Project::new(name.clone(), reg);
+
+    proj.file_mappings = mappings;
+    prog.call_graph.add_vertex(CallTarget::Todo(Rvalue::new_u64(entry as u64), Some(name), Uuid::new_v4()));
+
+    let add_sym = |prog: &mut Program, sym: &elf::Sym, name: &str| {
+        let name = name.to_string();
+        let addr = sym.st_value;
+        debug!("Symbol: {} @ 0x{:x}: {:?}", name, addr, sym);
+        if sym.is_function() {
+            if sym.is_import() {
+                prog.call_graph.add_vertex(CallTarget::Symbolic(name, Uuid::new_v4()));
+            } else {
+                prog.call_graph.add_vertex(CallTarget::Todo(Rvalue::new_u64(addr), Some(name), Uuid::new_v4()));
+                if sym.st_size > 0 {
+                    prog.symbol_sizes.insert(addr, sym.st_size);
+                }
+            }
+        }
+    };
+
+    let resolve_import_address = |proj: &mut Project, relocs: &[elf::Reloc], name: &str| {
+        for reloc in relocs {
+            let pltsym = &binary.dynsyms[reloc.r_sym];
+            let pltname = &binary.dynstrtab[pltsym.st_name];
+            if pltname == name {
+                debug!("Import match {}: {:#x} {:?}", name, reloc.r_offset, pltsym);
+                proj.imports.insert(reloc.r_offset as u64, name.to_string());
+                return true;
+            }
+        }
+        false
+    };
+
+    let mut seen_syms = HashSet::<u64>::new();
+
+    // add dynamic symbol information (non-strippable)
+    for sym in &binary.dynsyms {
+        let name = &binary.dynstrtab[sym.st_name];
+
+        add_sym(&mut prog, sym, name);
+        seen_syms.insert(sym.st_value);
+
+        let name = &binary.dynstrtab[sym.st_name];
+        if !resolve_import_address(&mut proj, &binary.pltrelocs, name) {
+            if sym.is_function() {
+                if !resolve_import_address(&mut proj, &binary.dynrelas, name) {
+                    resolve_import_address(&mut proj, &binary.dynrels, name);
+                }
+            }
+        }
+    }
+    debug!("Imports: {:#?}", &proj.imports);
+
+    // add strippable symbol information
+    for sym in &binary.syms {
+        let name = &binary.strtab[sym.st_name];
+        if !seen_syms.contains(&sym.st_value) {
+            add_sym(&mut prog, sym, &name);
+        }
+        seen_syms.insert(sym.st_value);
+    }
+
+    // add functions only known from the debug information
+    for func in elf_debug_info(&binary, bytes, machine) {
+        if !seen_syms.contains(&func.bound.start) {
+            seen_syms.insert(func.bound.start);
+            prog.call_graph.add_vertex(CallTarget::Todo(Rvalue::new_u64(func.bound.start), func.name.clone(), Uuid::new_v4()));
+        }
+        prog.debug_info.insert(func.bound.start, func);
+    }
+
+    // unwind tables survive stripping
+    for bound in elf_unwind_info(&binary, bytes) {
+        if seen_syms.insert(bound.start) {
+            debug!("Unwind info for {:?}", bound);
+            prog.call_graph.add_vertex(CallTarget::Todo(Rvalue::new_u64(bound.start), None, Uuid::new_v4()));
+        }
+        if bound.end > bound.start {
+            prog.symbol_sizes.entry(bound.start).or_insert(bound.end - bound.start);
+        }
+    }
+
+    prog.imports = proj.imports.clone();
+    proj.comments.insert(("base".to_string(), entry), "main".to_string());
+    proj.code.push(prog);
+
+    Ok((proj, machine))
+}
+
+// PE section characteristics
+const IMAGE_SCN_MEM_EXECUTE: u32 = 0x20000000;
+const IMAGE_SCN_MEM_READ: u32 = 0x40000000;
+const IMAGE_SCN_MEM_WRITE: u32 = 0x80000000;
+
+/// Returns the name of a PE import qualified with its DLL, i.e. `KERNEL32.dll!GetProcAddress`.
+/// Imports by ordinal only are named `KERNEL32.dll!#42`.
+fn pe_import_name(import: &pe::import::Import) -> String {
+    // goblin synthesizes a "ORDINAL <n>" name for imports without a hint/name entry
+    if import.name.starts_with("ORDINAL ") {
+        format!("{}!#{}", import.dll, import.ordinal)
+    } else {
+        format!("{}!{}", import.dll, import.name)
+    }
+}
+
+/// Converts `rva` into a file offset using the section table of `pe`.
+fn pe_rva_to_offset(pe: &pe::PE, rva: u32) -> Option<usize> {
+    pe.sections
+        .iter()
+        .find(|s| s.virtual_address <= rva && rva < s.virtual_address + s.size_of_raw_data)
+        .map(|s| (rva - s.virtual_address + s.pointer_to_raw_data) as usize)
+}
+
+fn read_u32_at(bytes: &[u8], off: usize) -> Option<u32> {
+    if off.checked_add(4).map(|e| e <= bytes.len()).unwrap_or(false) {
+        Some(LittleEndian::read_u32(&bytes[off..]))
+    } else {
+        None
+    }
+}
+
+/// Returns file offset and size of the data directory entry `index` of `pe`.
+fn pe_data_directory(pe: &pe::PE, bytes: &[u8], index: usize) -> Option<(usize, usize)> {
+    let optional_header = read_u32_at(bytes, 0x3c)? as usize + 24;
+    let data_directories = match pe.header.optional_header {
+        Some(ref oh) if oh.standard_fields.magic == pe::optional_header::MAGIC_64 => optional_header + 112,
+        Some(_) => optional_header + 96,
+        None => return None,
+    };
+    let rva = read_u32_at(bytes, data_directories + index * 8)?;
+    let size = read_u32_at(bytes, data_directories + index * 8 + 4)? as usize;
+
+    if rva == 0 || size == 0 {
+        None
+    } else {
+        Some((pe_rva_to_offset(pe, rva)?, size))
+    }
+}
+
+/// Returns the addresses patched by the base relocations of `pe`.
+fn pe_relocations(pe: &pe::PE, bytes: &[u8]) -> Vec<Relocation> {
+    const IMAGE_DIRECTORY_ENTRY_BASERELOC: usize = 5;
+    const IMAGE_REL_BASED_ABSOLUTE: u16 = 0;
+    const IMAGE_REL_BASED_HIGHLOW: u16 = 3;
+    const IMAGE_REL_BASED_DIR64: u16 = 10;
+
+    let mut ret = vec![];
+    let (mut pos, size) = match pe_data_directory(pe, bytes, IMAGE_DIRECTORY_ENTRY_BASERELOC) {
+        Some(dir) => dir,
+        None => return ret,
+    };
+    let end = ::std::cmp::min(pos + size, bytes.len());
+
+    // blocks of 16 bit entries for one 4K page each
+    while pos + 8 <= end {
+        let page = read_u32_at(bytes, pos).unwrap() as u64;
+        let block_size = read_u32_at(bytes, pos + 4).unwrap() as usize;
+
+        if block_size < 8 || pos + block_size > end {
+            debug!("bad base relocation block at {:#x}", pos);
+            break;
+        }
+
+        for entry in bytes[pos + 8..pos + block_size].chunks(2).filter(|x| x.len() == 2) {
+            let entry = LittleEndian::read_u16(entry);
+            let address = pe.image_base as u64 + page + (entry & 0xfff) as u64;
+
+            match entry >> 12 {
+                IMAGE_REL_BASED_ABSOLUTE => {}
+                IMAGE_REL_BASED_HIGHLOW => ret.push(Relocation { address: address, size: 4 }),
+                IMAGE_REL_BASED_DIR64 => ret.push(Relocation { address: address, size: 8 }),
+                typ => debug!("unsupported base relocation type {} at {:#x}", typ, address),
+            }
+        }
+        pos += block_size;
+    }
+
+    ret
+}
+
+/// Returns the functions listed in the x64 exception directory (`.pdata`) of `pe`. Entries for
+/// chained unwind info describe parts of other functions and are skipped.
+fn pe_runtime_functions(pe: &pe::PE, bytes: &[u8]) -> Vec<Bound> {
+    const IMAGE_DIRECTORY_ENTRY_EXCEPTION: usize = 3;
+    const UNW_FLAG_CHAININFO: u8 = 0x4;
+
+    let image_base = pe.image_base as u64;
+    let (pos, size) = match pe_data_directory(pe, bytes, IMAGE_DIRECTORY_ENTRY_EXCEPTION) {
+        Some(dir) => dir,
+        None => return vec![],
+    };
+    let mut ret = vec![];
+
+    // RUNTIME_FUNCTION entries are three RVAs: begin, end and unwind info
+    for entry in (0..size / 12).map(|i| pos + i * 12) {
+        let (begin, end, unwind) = match (read_u32_at(bytes, entry), read_u32_at(bytes, entry + 4), read_u32_at(bytes, entry + 8)) {
+            (Some(b), Some(e), Some(u)) => (b, e, u),
+            _ => break,
+        };
+        let flags = pe_rva_to_offset(pe, unwind).and_then(|off| bytes.get(off)).map(|&b| b >> 3);
+
+        if begin == 0 || end < begin || flags.map(|f| f & UNW_FLAG_CHAININFO != 0).unwrap_or(false) {
+            continue;
+        }
+        ret.push(Bound::new(image_base + begin as u64, image_base + end as u64));
+    }
+
+    ret
+}
+
+/// Returns the addresses of all named and unnamed `CallTarget::Todo`s in `prog`.
+fn todo_addresses(prog: &Program) -> HashSet<u64> {
+    prog.call_graph
+        .vertex_labels()
+        .filter_map(
+            |ct| match ct {
+                &CallTarget::Todo(Rvalue::Constant { value, .. }, _, _) => Some(value),
+                _ => None,
+            }
+        )
+        .collect()
+}
+
+/// Returns GUID, age and PDB path of the CodeView entry in the debug directory of `pe`.
+fn pe_codeview(pe: &pe::PE, bytes: &[u8]) -> Option<([u8; 16], u32, String)> {
+    const IMAGE_DEBUG_TYPE_CODEVIEW: u32 = 2;
+    const IMAGE_DIRECTORY_ENTRY_DEBUG: usize = 6;
+
+    let (dir, dir_size) = pe_data_directory(pe, bytes, IMAGE_DIRECTORY_ENTRY_DEBUG)?;
+
+    // IMAGE_DEBUG_DIRECTORY entries are 28 bytes long
+    for entry in (0..dir_size / 28).map(|i| dir + i * 28) {
+        if read_u32_at(bytes, entry + 12)? != IMAGE_DEBUG_TYPE_CODEVIEW {
+            continue;
+        }
+
+        let size = read_u32_at(bytes, entry + 16)? as usize;
+        let cv = read_u32_at(bytes, entry + 24)? as usize;
+
+        if size < 24 || cv + size > bytes.len() || &bytes[cv..cv + 4] != b"RSDS" {
+            continue;
+        }
+
+        let mut guid = [0u8; 16];
+        guid.copy_from_slice(&bytes[cv + 4..cv + 20]);
+
+        let age = LittleEndian::read_u32(&bytes[cv + 20..]);
+        let path = &bytes[cv + 24..cv + size];
+        let path = String::from_utf8_lossy(&path[..path.iter().position(|&b| b == 0).unwrap_or(path.len())]).to_string();
+
+        return Some((guid, age, path));
+    }
+
+    None
+}
+
+/// Looks for the PDB of the PE image at `path` next to it and adds its function symbols to
+/// `prog`. The PDB is only used if GUID and age match the image's CodeView entry.
+fn load_pdb(path: &Path, pe: &pe::PE, bytes: &[u8], prog: &mut Program) {
+    let (guid, age, pdb_path) = match pe_codeview(pe, bytes) {
+        Some(cv) => cv,
+        None => return,
+    };
+    // the recorded path is the one on the build machine
+    let pdb_name = pdb_path.rsplit(|c| c == '\\' || c == '/').next().unwrap_or("").to_string();
+    let mut candidates = vec![path.with_extension("pdb")];
+
+    if !pdb_name.is_empty() {
+        candidates.push(path.with_file_name(pdb_name));
+    }
+
+    for candidate in candidates {
+        let mut pdb_bytes = Vec::new();
+
+        if File::open(&candidate).and_then(|mut fd| fd.read_to_end(&mut pdb_bytes)).is_err() {
+            continue;
+        }
+
+        let pdb = match pdb::parse(&pdb_bytes) {
+            Ok(pdb) => pdb,
+            Err(e) => {
+                warn!("Failed to read {}: {}", candidate.display(), e);
+                continue;
+            }
+        };
+
+        if pdb.guid != guid || pdb.age != age {
+            warn!("{} does not match the image (age {} vs. {})", candidate.display(), pdb.age, age);
+            continue;
+        }
+
+        let image_base = pe.image_base as u64;
+        let mut seen = todo_addresses(prog);
+
+        for func in pdb.functions {
+            let section = match pe.sections.get((func.segment as usize).wrapping_sub(1)) {
+                Some(section) => section,
+                None => continue,
+            };
+            let addr = image_base + section.virtual_address as u64 + func.offset as u64;
+
+            debug!("PDB symbol: {} @ {:#x}", func.name, addr);
+
+            if seen.insert(addr) {
+                prog.call_graph.add_vertex(CallTarget::Todo(Rvalue::new_u64(addr), Some(func.name), Uuid::new_v4()));
+            }
+            if let Some(size) = func.size {
+                prog.symbol_sizes.entry(addr).or_insert(size as u64);
+            }
+        }
+        return;
+    }
+}
+
+/// Parses a PE32/PE32+ file from `bytes` and create a project from it.
+fn load_pe(bytes: &[u8], file: Option<&MappedLayer>, path: &Path, name: String) -> Result<(Project, Machine)> {
+    let pe = pe::PE::parse(&bytes)?;
+    debug!("pe: {:#?}", &pe);
+    let image_base = pe.image_base as u64;
+    let (machine, mut ram) = match pe.header.coff_header.machine {
+        pe::header::COFF_MACHINE_X86 => {
+            let reg = Region::undefined("RAM".to_string(), 0x1_0000_0000);
+            (Machine::Ia32, reg)
+        }
+        pe::header::COFF_MACHINE_X86_64 => {
+            let reg = Region::undefined("RAM".to_string(), 0xFFFF_FFFF_FFFF_FFFF);
+            (Machine::Amd64, reg)
+        }
+        machine => return Err(format!("Unsupported machine: {:#x}", machine).into()),
+    };
+
+    // PE32 images carry 32-bit code, PE32+ images 64-bit code. A mismatch with the COFF machine
+    // field means we would decode the image in the wrong mode.
+    if let Some(ref optional_header) = pe.header.optional_header {
+        let is_pe32_plus = optional_header.standard_fields.magic == pe::optional_header::MAGIC_64;
+        if is_pe32_plus != (machine == Machine::Amd64) {
+            return Err(
+                format!(
+                    "Optional header magic {:#x} does not match machine {:?}",
+                    optional_header.standard_fields.magic,
+                    machine
+                )
+                        .into()
+            );
+        }
+    }
+
+    let mut mappings = vec![];
+
+    for section in &pe.sections {
+        let name = String::from_utf8_lossy(&section.name);
+        debug!("section: {}", name);
+        let virtual_address = section.virtual_address as u64;
+        let offset = section.pointer_to_raw_data as usize;
+        let (layer, size) = {
+            let vsize = section.virtual_size as u64;
+            let size = section.size_of_raw_data as usize;
+            if size > 0 {
+                if offset + size >= bytes.len() {
+                    debug!(
+                        "bad section pointer: {:#x} + {:#x} >= {:#x}",
+                        offset,
+                        size,
+                        bytes.len()
+                    );
+                    (Layer::undefined(0), 0)
+                } else {
+                    debug!("mapped '{}': {:?}", name, offset..offset + size);
+                    (file_layer(file, &bytes[offset..offset + size]), size as u64)
+                }
+            } else {
+                debug!("bss '{}'", name);
+                (Layer::undefined(vsize), vsize)
+            }
+        };
+        let begin = image_base + virtual_address;
+        let end = image_base + virtual_address + size as u64;
+        let bound = Bound::new(begin, end);
+        debug!("bound: {:?}", &bound);
+        if !layer.is_undefined() && size > 0 {
+            mappings.push(FileMapping { bound: bound.clone(), offset: offset as u64 });
+        }
+
+        let mut flags = 0;
+        if section.characteristics & IMAGE_SCN_MEM_EXECUTE != 0 {
+            flags |= SEGMENT_EXECUTE;
+        }
+        if section.characteristics & IMAGE_SCN_MEM_READ != 0 {
+            flags |= SEGMENT_READ;
+        }
+        if section.characteristics & IMAGE_SCN_MEM_WRITE != 0 {
+            flags |= SEGMENT_WRITE;
+        }
+
+        let vsize = cmp::max(section.virtual_size as u64, size);
+        let segment = Segment { name: Some(name.trim_end_matches('\0').to_string()), bound: Bound::new(begin, begin + vsize), flags: flags };
+        if vsize > 0 && !ram.add_segment(segment) {
+            debug!("Section {} outside of address space", name);
+        }
+
+        if !ram.cover(bound, layer) {
+            debug!("bad cover");
+            return Err(format!("Cannot cover bound: {:?}", Bound::new(begin, end)).into());
+        }
+    }
+    let entry = (pe.image_base + pe.entry) as u64;
+    debug!("entry: {:#x}", entry);
+    let mut prog = Program::new("prog0");
+    let mut proj = Project::new(name.to_string(), ram);
+
+    proj.file_mappings = mappings;
+
+    prog.call_graph
+        .add_vertex(
+            CallTarget::Todo(
+                Rvalue::new_u64(entry),
+                Some(name.to_string()),
+                Uuid::new_v4(),
+            )
+        );
+
+    for export in pe.exports {
+        debug!("adding export: {:?}", &export);
+        prog.call_graph
+            .add_vertex(
+                CallTarget::Todo(
+                    Rvalue::new_u64(export.rva as u64 + image_base),
+                    Some(export.name.to_string()),
+                    Uuid::new_v4(),
+                )
+            );
+    }
+
+    load_pdb(path, &pe, bytes, &mut prog);
+
+    // unwind information lists every non-leaf function of x64 images, even stripped ones
+    if machine == Machine::Amd64 {
+        let mut seen = todo_addresses(&prog);
+
+        for bound in pe_runtime_functions(&pe, bytes) {
+            if seen.insert(bound.start) {
+                prog.call_graph.add_vertex(CallTarget::Todo(Rvalue::new_u64(bound.start), None, Uuid::new_v4()));
+            }
+            prog.symbol_sizes.entry(bound.start).or_insert(bound.end - bound.start);
+        }
+    }
+    proj.relocations = pe_relocations(&pe, bytes);
+    debug!("{} base relocations", proj.relocations.len());
+
+    for import in pe.imports {
+        // the IAT slot is what `call [addr]` and `jmp [addr]` stubs dereference
+        let address = image_base + import.rva as u64;
+        let name = pe_import_name(&import);
+        debug!("adding import: {:?} @ {:#x}", &import, address);
+        prog.call_graph.add_vertex(CallTarget::Symbolic(name.clone(), Uuid::new_v4()));
+        proj.imports.insert(address, name);
+    }
+
+    debug!("Imports: {:#?}", &proj.imports);
+    prog.imports = proj.imports.clone();
+    proj.comments.insert(("base".to_string(), entry), "main".to_string());
+    proj.code.push(prog);
+    Ok((proj, machine))
+}
+
+/// Adds the memory image of the `PT_LOAD` program header `ph` as segment to `reg`.
+fn add_elf_segment(reg: &mut Region, ph: &program_header::ProgramHeader) {
+    let segment = Segment {
+        name: None,
+        bound: Bound::new(ph.p_vaddr, ph.p_vaddr + ph.p_memsz),
+        flags: ph.p_flags as u64 & (SEGMENT_READ | SEGMENT_WRITE | SEGMENT_EXECUTE),
+    };
+
+    if ph.p_memsz > 0 && !reg.add_segment(segment) {
+        debug!("Segment at {:#x} outside of address space", ph.p_vaddr);
+    }
+}
+
+/// Returns a `Layer` with the contents of `data`. If `data` is part of `file` the `Layer` shares
+/// its mapping, otherwise `data` is copied.
+fn file_layer(file: Option<&MappedLayer>, data: &[u8]) -> Layer {
+    match file.and_then(|f| f.subslice(data)) {
+        Some(mapped) => Layer::Opaque(OpaqueLayer::Mapped(mapped)),
+        None => Layer::wrap(data.to_vec()),
+    }
+}
+
+/// Maps `data` to `addr` in `reg`. `data` is sliced from `file` if possible.
+fn cover_bytes(reg: &mut Region, file: Option<&MappedLayer>, addr: u64, data: &[u8]) -> Result<()> {
+    let bound = Bound::new(addr, addr + data.len() as u64);
+
+    if reg.cover(bound.clone(), file_layer(file, data)) {
+        Ok(())
+    } else {
+        Err(format!("Cannot cover bound: {:?}", bound).into())
+    }
+}
+
+/// Creates the project for a process dump. The dumped threads' registers become comments at their
+/// program counters and the thread that crashed is disassembled starting at its program counter.
+fn load_dump(reg: Region, machine: Machine, threads: &[coredump::Thread], name: String) -> Project {
+    let mut prog = Program::new("prog0");
+
+    if let Some(thread) = threads.iter().find(|t| t.reason.is_some()) {
+        prog.call_graph.add_vertex(CallTarget::Todo(Rvalue::new_u64(thread.pc), None, Uuid::new_v4()));
+    }
+
+    let mut proj = Project::new(name, reg);
+
+    for thread in threads {
+        let comment = thread.describe(machine);
+        let key = ("base".to_string(), thread.pc);
+        let comment = match proj.comments.remove(&key) {
+            Some(prev) => format!("{}\n{}", prev, comment),
+            None => comment,
+        };
+
+        proj.comments.insert(key, comment);
+    }
+
+    proj.code.push(prog);
+    proj
+}
+
+/// Parses an ELF core file. All `PT_LOAD` segments with contents are mapped, the files listed in the
+/// `NT_FILE` note become sections.
+fn load_elf_core(binary: &elf::Elf, bytes: &[u8], file: Option<&MappedLayer>, name: String) -> Result<(Project, Machine)> {
+    let (machine, mut reg) = elf_machine(binary.header.e_machine)?;
+    let mut threads = vec![];
+    let mut files = vec![];
+
+    for ph in &binary.program_headers {
+        let start = ph.p_offset as usize;
+        let contents = match start.checked_add(ph.p_filesz as usize) {
+            Some(end) if end <= bytes.len() => &bytes[start..end],
+            _ => return Err("Failed to read segment".into()),
+        };
+
+        match ph.p_type {
+            program_header::PT_LOAD => {
+                if ph.p_filesz > 0 {
+                    debug!("Load core segment of {} bytes to {:#x}", ph.p_filesz, ph.p_vaddr);
+                    cover_bytes(&mut reg, file, ph.p_vaddr, contents)?;
+                }
+                add_elf_segment(&mut reg, ph);
+            }
+            program_header::PT_NOTE => {
+                let notes = coredump::parse_core_notes(contents, machine, binary.is_64)?;
+
+                threads.extend(notes.threads);
+                files.extend(notes.files);
+            }
+            _ => {}
+        }
+    }
+
+    for file in files {
+        let segment = binary
+            .program_headers
+            .iter()
+            .find(|ph| ph.p_type == program_header::PT_LOAD && ph.p_vaddr <= file.bound.start && file.bound.start < ph.p_vaddr + ph.p_memsz);
+        let mut flags = SECTION_ALLOC;
+
+        if let Some(ph) = segment {
+            if ph.p_flags & program_header::PF_X != 0 {
+                flags |= SECTION_EXECINSTR;
+            }
+            if ph.p_flags & program_header::PF_W != 0 {
+                flags |= SECTION_WRITE;
+            }
+        }
+
+        debug!("Mapped file {} at {:?}", file.name, file.bound);
+        reg.add_section(Section { name: file.name, bound: file.bound, flags: flags });
+    }
+
+    Ok((load_dump(reg, machine, &threads, name), machine))
+}
+
+/// Parses a Windows minidump. Saved memory ranges are mapped, loaded modules become sections.
+fn load_minidump(bytes: &[u8], name: String) -> Result<(Project, Machine)> {
+    let dump = coredump::parse_minidump(bytes)?;
+    let mut reg = address_space(dump.machine);
+
+    for &(addr, ref data) in dump.memory.iter() {
+        debug!("Load minidump range of {} bytes to {:#x}", data.len(), addr);
+        cover_bytes(&mut reg, None, addr, data)?;
+    }
+
+    for module in dump.modules.iter() {
+        debug!("Module {} at {:?}", module.name, module.bound);
+        reg.add_section(Section { name: module.name.clone(), bound: module.bound.clone(), flags: SECTION_ALLOC });
+    }
+
+    Ok((load_dump(reg, dump.machine, &dump.threads, name), dump.machine))
+}
+
+/// Parses a Commodore 64 program file. The first two bytes are the little endian load address,
+/// the rest is mapped there. Programs starting with a BASIC `SYS <addr>` line use `addr` as entry
+/// point, all others the load address.
+fn load_prg(bytes: &[u8], name: String) -> Result<Project> {
+    if bytes.len() < 2 {
+        return Err("PRG file too short".into());
+    }
+
+    let load = ((bytes[1] as u64) << 8) | bytes[0] as u64;
+    let data = &bytes[2..];
+    let mut reg = address_space(Machine::Mos6502);
+
+    debug!("Load {} bytes PRG to {:#x}", data.len(), load);
+    cover_bytes(&mut reg, None, load, data)?;
+
+    // BASIC line: next line pointer (2), line number (2), SYS token, decimal address
+    let sys = if data.len() > 5 && data[4] == 0x9e {
+        let digits = data[5..]
+            .iter()
+            .skip_while(|&&x| x == b' ')
+            .take_while(|&&x| x >= b'0' && x <= b'9')
+            .map(|&x| x as char)
+            .collect::<String>();
+        u64::from_str(&digits).ok().filter(|&x| x <= 0xffff)
+    } else {
+        None
+    };
+    let entry = sys.unwrap_or(load);
+    let mut prog = Program::new("prog0");
+    let mut proj = Project::new(name.clone(), reg);
+
+    proj.file_mappings.push(FileMapping { bound: Bound::new(load, load + data.len() as u64), offset: 2 });
+
+    prog.call_graph.add_vertex(CallTarget::Todo(Rvalue::new_u64(entry), Some(name), Uuid::new_v4()));
+    proj.comments.insert(("base".to_string(), entry), "main".to_string());
+    proj.code.push(prog);
+
+    Ok(proj)
+}
+
+/// Parses a NES iNES ROM image. The PRG-ROM is mapped to $8000. Images with a single 16k bank mirror
+/// it at $C000, larger images map the last bank there, as most mappers have it fixed there at
+/// reset. The NMI, RESET and IRQ/BRK vectors are added by `seed_entry_points`.
+fn load_ines(bytes: &[u8], name: String) -> Result<Project> {
+    const BANK: usize = 0x4000;
+
+    if bytes.len() < 16 || &bytes[0..4] != b"NES\x1a" {
+        return Err("Not an iNES image".into());
+    }
+
+    let banks = bytes[4] as usize;
+    let trainer = if bytes[6] & 0x04 != 0 { 512 } else { 0 };
+    let start = 16 + trainer;
+
+    if banks == 0 || start + banks * BANK > bytes.len() {
+        return Err(format!("iNES image with {} PRG-ROM banks too short: {} bytes", banks, bytes.len()).into());
+    }
+
+    let prg = &bytes[start..start + banks * BANK];
+    let mut reg = address_space(Machine::Mos6502);
+
+    debug!("Load {} PRG-ROM banks", banks);
+    match banks {
+        1 => {
+            cover_bytes(&mut reg, None, 0x8000, prg)?;
+            cover_bytes(&mut reg, None, 0xc000, prg)?;
+        }
+        2 => cover_bytes(&mut reg, None, 0x8000, prg)?,
+        _ => {
+            cover_bytes(&mut reg, None, 0x8000, &prg[0..BANK])?;
+            cover_bytes(&mut reg, None, 0xc000, &prg[(banks - 1) * BANK..])?;
+        }
+    }
+
+    let mut proj = Project::new(name, reg);
+
+    proj.code.push(Program::new("prog0"));
+
+    Ok(proj)
+}
+
+/// Parses an Atari 8-bit executable. Each segment is mapped to its load address. The addresses
+/// written to RUNAD ($2E0) and INITAD ($2E2) become entry points.
+fn load_xex(bytes: &[u8], name: String) -> Result<Project> {
+    let word = |p: usize| ((bytes[p + 1] as u64) << 8) | bytes[p] as u64;
+    let mut reg = address_space(Machine::Mos6502);
+    let mut entries = Vec::new();
+    let mut pos = 0;
+
+    if bytes.len() < 2 || word(0) != 0xffff {
+        return Err("Not an Atari executable".into());
+    }
+
+    while pos + 2 <= bytes.len() {
+        // segment headers can be preceded by another $FFFF marker
+        if word(pos) == 0xffff {
+            pos += 2;
+            continue;
+        }
+        if pos + 4 > bytes.len() {
+            return Err(format!("Truncated segment header at {:#x}", pos).into());
+        }
+
+        let start = word(pos);
+        let end = word(pos + 2);
+        let len = (end + 1).saturating_sub(start) as usize;
+
+        pos += 4;
+        if end < start || pos + len > bytes.len() {
+            return Err(format!("Invalid segment {:#x}-{:#x} at {:#x}", start, end, pos - 4).into());
+        }
+
+        let data = &bytes[pos..pos + len];
+
+        debug!("Load {} bytes segment to {:#x}", len, start);
+        cover_bytes(&mut reg, None, start, data)?;
+
+        for &(vector, label) in &[(0x2e0, "RUNAD"), (0x2e2, "INITAD")] {
+            if start <= vector && vector + 1 <= end {
+                let off = (vector - start) as usize;
+                let addr = ((data[off + 1] as u64) << 8) | data[off] as u64;
+                entries.push((label, addr));
+            }
+        }
+
+        pos += len;
+    }
+
+    let mut prog = Program::new("prog0");
+    let mut proj = Project::new(name, reg);
+
+    for (vector, addr) in entries {
+        debug!("{}: {:#x}", vector, addr);
+        prog.call_graph.add_vertex(CallTarget::Todo(Rvalue::new_u64(addr), Some(vector.to_string()), Uuid::new_v4()));
+    }
+    proj.code.push(prog);
+
+    Ok(proj)
+}
+
+/// Tries the 6502 container formats goblin doesn't know about. iNES images and Atari executables
+/// are recognized by their magic numbers. C64 programs have none and are only tried if the file
+/// extension is `.prg`.
+fn load_mos6502(bytes: &[u8], path: &Path, name: String) -> Option<Result<(Project, Machine)>> {
+    let ext = path.extension().map(|x| x.to_string_lossy().to_lowercase());
+    let maybe_proj = if bytes.starts_with(b"NES\x1a") {
+        load_ines(bytes, name)
+    } else if bytes.starts_with(&[0xff, 0xff]) {
+        load_xex(bytes, name)
+    } else if ext.as_ref().map(|x| x.as_str()) == Some("prg") {
+        load_prg(bytes, name)
+    } else {
+        return None;
+    };
+
+    Some(maybe_proj.map(|proj| (proj, Machine::Mos6502)))
+}
+
+/// Maps the flat image at `path` to `base` in the address space of `machine` and creates a `Project`
+/// from it. The `Project` has a single `Program` with a `CallTarget::Todo` for each address in
+/// `entry_points`. Entry points of the architecture, e.g. interrupt vectors, are added by
+/// `seed_entry_points`.
+pub fn load_raw(path: &Path, machine: Machine, base: u64, entry_points: &[u64]) -> Result<Project> {
+    let name = path.file_name().map(|x| x.to_string_lossy().to_string()).unwrap_or("(encoding error)".to_string());
+    let layer = OpaqueLayer::open(path)?;
+    let bound = Bound::new(base, base + layer.len());
+    let mut reg = address_space(machine);
+
+    debug!("Load raw {} bytes image to {:#x}", layer.len(), base);
+
+    if !reg.cover(bound.clone(), Layer::Opaque(layer)) {
+        return Err(format!("Cannot cover bound: {:?}", bound).into());
+    }
+
+    let mut prog = Program::new("prog0");
+    let mut proj = Project::new(name, reg);
+
+    proj.file_mappings.push(FileMapping { bound: bound, offset: 0 });
+
+    for &entry in entry_points {
+        debug!("adding entry point: {:#x}", entry);
+        prog.call_graph.add_vertex(CallTarget::Todo(Rvalue::new_u64(entry), None, Uuid::new_v4()));
+    }
+
+    proj.code.push(prog);
+    Ok(proj)
+}
+
+/// Contiguous runs of data and the start address of a firmware image.
+struct Firmware {
+    chunks: Vec<(u64, Vec<u8>)>,
+    entry: Option<u64>,
+}
+
+impl Firmware {
+    fn new() -> Firmware {
+        Firmware { chunks: Vec::new(), entry: None }
+    }
+
+    /// Adds a data record, extending the last chunk if the record directly follows it.
+    fn push(&mut self, addr: u64, data: Vec<u8>) {
+        if let Some(&mut (start, ref mut chunk)) = self.chunks.last_mut() {
+            if start + chunk.len() as u64 == addr {
+                chunk.extend(data);
+                return;
+            }
+        }
+        self.chunks.push((addr, data));
+    }
+
+    /// Covers `reg` with one `Layer` per chunk and returns the start address.
+    fn map(self, reg: &mut Region) -> Result<Option<u64>> {
+        for (addr, data) in self.chunks {
+            let bound = Bound::new(addr, addr + data.len() as u64);
+
+            debug!("Load {} bytes firmware record to {:#x}", data.len(), addr);
+            if !reg.cover(bound.clone(), Layer::wrap(data)) {
+                return Err(format!("Cannot cover bound: {:?}", bound).into());
+            }
+        }
+
+        Ok(self.entry)
+    }
+}
+
+/// Decodes a line of hexadecimal digits into bytes. `line` is only used in error messages.
+fn decode_hex_line(s: &str, line: usize) -> Result<Vec<u8>> {
+    if s.len() % 2 != 0 {
+        return Err(format!("{}: odd number of hex digits", line).into());
+    }
+
+    let mut ret = Vec::with_capacity(s.len() / 2);
+    for i in 0..s.len() / 2 {
+        match s.get(i * 2..i * 2 + 2).and_then(|x| u8::from_str_radix(x, 16).ok()) {
+            Some(b) => ret.push(b),
+            None => return Err(format!("{}:{}: invalid hex digit", line, i * 2 + 2).into()),
+        }
+    }
+
+    Ok(ret)
+}
+
+/// Parses an Intel HEX file.
+fn parse_ihex_records(text: &str) -> Result<Firmware> {
+    let mut fw = Firmware::new();
+    let mut base = 0u64;
+
+    for (idx, line) in text.lines().enumerate() {
+        let line_no = idx + 1;
+        let line = line.trim();
+
+        if line.is_empty() {
+            continue;
+        }
+        if !line.starts_with(':') {
+            return Err(format!("{}: record does not start with ':'", line_no).into());
+        }
+
+        let rec = decode_hex_line(&line[1..], line_no)?;
+        if rec.len() < 5 || rec.len() != rec[0] as usize + 5 {
+            return Err(format!("{}: record length mismatch", line_no).into());
+        }
+        if rec.iter().fold(0u8, |acc, &x| acc.wrapping_add(x)) != 0 {
+            return Err(format!("{}: checksum mismatch", line_no).into());
+        }
+
+        let offset = ((rec[1] as u64) << 8) | rec[2] as u64;
+        let data = &rec[4..rec.len() - 1];
+        let be = |d: &[u8]| d.iter().fold(0u64, |acc, &x| (acc << 8) | x as u64);
+
+        match (rec[3], data.len()) {
+            // data
+            (0x00, _) => fw.push(base + offset, data.to_vec()),
+            // end of file
+            (0x01, _) => break,
+            // extended segment address
+            (0x02, 2) => base = be(data) << 4,
+            // start segment address (CS:IP)
+            (0x03, 4) => fw.entry = Some((be(&data[0..2]) << 4) + be(&data[2..4])),
+            // extended linear address
+            (0x04, 2) => base = be(data) << 16,
+            // start linear address
+            (0x05, 4) => fw.entry = Some(be(data)),
+            (typ, _) => return Err(format!("{}: invalid record type {:#x}", line_no, typ).into()),
+        }
+    }
+
+    Ok(fw)
+}
+
+/// Parses a Motorola S-record file.
+fn parse_srec_records(text: &str) -> Result<Firmware> {
+    let mut fw = Firmware::new();
+
+    for (idx, line) in text.lines().enumerate() {
+        let line_no = idx + 1;
+        let line = line.trim();
+
+        if line.is_empty() {
+            continue;
+        }
+        if !line.starts_with('S') || line.len() < 2 {
+            return Err(format!("{}: record does not start with 'S'", line_no).into());
+        }
+
+        // the type digit may be followed by a multibyte character
+        let (typ, rest) = match (line.get(1..2), line.get(2..)) {
+            (Some(typ), Some(rest)) => (typ, rest),
+            _ => return Err(format!("{}: invalid record type", line_no).into()),
+        };
+        let rec = decode_hex_line(rest, line_no)?;
+        if rec.len() < 2 || rec.len() != rec[0] as usize + 1 {
+            return Err(format!("{}: record length mismatch", line_no).into());
+        }
+        if rec.iter().fold(0u8, |acc, &x| acc.wrapping_add(x)) != 0xff {
+            return Err(format!("{}: checksum mismatch", line_no).into());
+        }
+
+        let addr_len = match typ {
+            "0" | "1" | "5" | "9" => 2,
+            "2" | "6" | "8" => 3,
+            "3" | "7" => 4,
+            _ => return Err(format!("{}: invalid record type S{}", line_no, typ).into()),
+        };
+        if rec.len() < addr_len + 2 {
+            return Err(format!("{}: record too short", line_no).into());
+        }
+
+        let addr = rec[1..1 + addr_len].iter().fold(0u64, |acc, &x| (acc << 8) | x as u64);
+        let data = &rec[1 + addr_len..rec.len() - 1];
+
+        match typ {
+            "1" | "2" | "3" => fw.push(addr, data.to_vec()),
+            "7" | "8" | "9" => fw.entry = Some(addr),
+            // header and record counts
+            _ => {}
+        }
+    }
+
+    Ok(fw)
+}
+
+/// Parses the Intel HEX file in `text` and covers `reg` with one `Layer` for each run of
+/// contiguous data records. Extended segment and linear address records are honored. Returns the
+/// start address if the file has one.
+pub fn parse_ihex(text: &str, reg: &mut Region) -> Result<Option<u64>> {
+    parse_ihex_records(text)?.map(reg)
+}
+
+/// Parses the Motorola S-record file in `text` and covers `reg` with one `Layer` for each run of
+/// contiguous data records. Returns the start address if the file has one.
+pub fn parse_srec(text: &str, reg: &mut Region) -> Result<Option<u64>> {
+    parse_srec_records(text)?.map(reg)
+}
+
+fn load_firmware<A: Architecture>(
+    path: &Path,
+    machine: Machine,
+    config: &A::Configuration,
+    parse: fn(&str, &mut Region) -> Result<Option<u64>>,
+) -> Result<Project> {
+    let name = path.file_name().map(|x| x.to_string_lossy().to_string()).unwrap_or("(encoding error)".to_string());
+    let mut text = String::new();
+    let mut fd = File::open(path)?;
+    fd.read_to_string(&mut text)?;
+
+    let mut reg = address_space(machine);
+    let entry = parse(&text, &mut reg)?;
+    let mut prog = Program::new("prog0");
+    let mut proj = Project::new(name.clone(), reg);
+
+    if let Some(entry) = entry {
+        debug!("entry: {:#x}", entry);
+        prog.call_graph.add_vertex(CallTarget::Todo(Rvalue::new_u64(entry), Some(name), Uuid::new_v4()));
+        proj.comments.insert(("base".to_string(), entry), "main".to_string());
+    }
+
+    proj.code.push(prog);
+    seed_entry_points::<A>(&mut proj, config)?;
+    Ok(proj)
+}
+
+/// Loads the Intel HEX file at `path` into the address space of `machine` and creates a `Project`
+/// from it. The start address record, if any, becomes the entry point, as do the entry points
+/// `A::prepare` finds, e.g. interrupt vectors.
+pub fn load_ihex<A: Architecture>(path: &Path, machine: Machine, config: &A::Configuration) -> Result<Project> {
+    load_firmware::<A>(path, machine, config, parse_ihex)
+}
+
+/// Loads the Motorola S-record file at `path` into the address space of `machine` and creates a
+/// `Project` from it. The S7/S8/S9 start address, if any, becomes the entry point, as do the
+/// entry points `A::prepare` finds, e.g. interrupt vectors.
+pub fn load_srec<A: Architecture>(path: &Path, machine: Machine, config: &A::Configuration) -> Result<Project> {
+    load_firmware::<A>(path, machine, config, parse_srec)
+}
+
+/// Adds the entry points `A::prepare` finds in the root `Region` of `proj` to its first `Program`.
+/// For AVR these are the interrupt vectors, for the 6502 the NMI, RESET and IRQ vectors. `load_raw`
+/// and the 6502 container formats found by `load` leave this to the caller.
+pub fn seed_entry_points<A: Architecture>(proj: &mut Project, config: &A::Configuration) -> Result<()> {
+    let entries = A::prepare(proj.region(), config)?;
+    let prog = match proj.code.first_mut() {
+        Some(prog) => prog,
+        None => return Err("Project has no program".into()),
+    };
+
+    for (name, addr, comment) in entries {
+        debug!("adding entry point {} @ {:#x}: {}", name, addr, comment);
+        prog.call_graph.add_vertex(CallTarget::Todo(Rvalue::new_u64(addr), Some(name.to_string()), Uuid::new_v4()));
+        proj.comments.insert(("base".to_string(), addr), comment.to_string());
+    }
+
+    Ok(())
+}
+
+/// Load an ELF, PE, Mach-o, static archive or 6502 program file from disk and creates a `Project` from it. Returns the `Project` instance and
+/// the CPU its intended for.
+pub fn load(path: &Path) -> Result<(Project, Machine)> {
+    load_file(path, None)
+}
+
+/// Like `load` but picks the slice intended for `machine` if the file is a fat Mach-o binary.
+/// Other file formats are loaded as if `load` was called.
+pub fn load_arch(path: &Path, machine: Machine) -> Result<(Project, Machine)> {
+    load_file(path, Some(machine))
+}
+
+fn load_file(path: &Path, preferred: Option<Machine>) -> Result<(Project, Machine)> {
+    let name = path.file_name().map(|x| x.to_string_lossy().to_string()).unwrap_or("(encoding error)".to_string());
+    let file = MappedLayer::open(path)?;
+    let bytes = file.as_slice();
+    let peek = goblin::peek(&mut Cursor::new(bytes))?;
+    if let Hint::Unknown(magic) = peek {
+        if coredump::is_minidump(bytes) {
+            return load_minidump(bytes, name);
+        }
+        match load_mos6502(bytes, path, name) {
+            Some(ret) => ret,
+            None => Err(format!("Tried to load an unknown file. Magic: {}", magic).into()),
+        }
     } else {
-        let mut bytes = Vec::new();
-        fd.read_to_end(&mut bytes)?;
         match peek {
-            Hint::Elf(_) => load_elf(&bytes, name),
-            Hint::PE => load_pe(&bytes, name),
-            Hint::Mach(_) => load_mach(&bytes, 0, name),
-            Hint::MachFat(_) => Err("Cannot directly load a fat mach-o binary (e.g., which one do I load?)".into()),
-            Hint::Archive => {
-                let archive = archive::Archive::parse(&bytes)?;
-                debug!("archive: {:#?}", &archive);
-                Err("Tried to load an archive, unsupported format".into())
+            Hint::Elf(_) => load_elf(bytes, Some(&file), name),
+            Hint::PE => load_pe(bytes, Some(&file), path, name),
+            Hint::Mach(_) => load_mach_file(bytes, 0, Some(&file), name),
+            Hint::MachFat(_) => {
+                match preferred {
+                    Some(machine) => load_fat(bytes, Some(&file), machine, name),
+                    None => Err("Cannot directly load a fat mach-o binary (e.g., which one do I load?)".into()),
+                }
             }
+            Hint::Archive => load_archive(bytes, name),
             _ => {
                 println!(
                     "Loader branch hit wildcard, should be unreachable (a new variant must have been added but code was not updated)",
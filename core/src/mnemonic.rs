@@ -56,6 +56,11 @@ impl Bound {
     pub fn len(&self) -> u64 {
         self.end - self.start
     }
+
+    /// Returns the range moved by `delta` bytes.
+    pub fn shifted(&self, delta: i64) -> Bound {
+        Bound::new(self.start.wrapping_add(delta as u64), self.end.wrapping_add(delta as u64))
+    }
 }
 
 /// Internal to `Mnemonic`
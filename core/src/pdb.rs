@@ -0,0 +1,117 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Reader for function symbols in Microsoft Program Database (PDB) files.
+//!
+//! Parsing is left to the `pdb` crate. The PDB info stream carries the GUID and age that the
+//! CodeView entry of the PE debug directory refers to. Public symbols are read from the global
+//! symbol stream, private procedures from the per-module symbol streams.
+
+use crate::Result;
+use ms_pdb::{FallibleIterator, PDB, SymbolData};
+use std::io::Cursor;
+
+/// A function symbol.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct PdbFunction {
+    /// Decorated name for public symbols, undecorated name for procedures.
+    pub name: String,
+    /// 1-based index of the PE section the function is in.
+    pub segment: u16,
+    /// Offset from the start of the section.
+    pub offset: u32,
+    /// Size of the function's code. Only known for procedures.
+    pub size: Option<u32>,
+}
+
+/// Contents of a PDB file.
+#[derive(Clone,Debug)]
+pub struct Pdb {
+    /// GUID, in on-disk byte order, identifying the image the PDB belongs to.
+    pub guid: [u8; 16],
+    /// Number of times the PDB was written. Matches the age in the image's CodeView entry.
+    pub age: u32,
+    /// Public symbols and procedures that are functions.
+    pub functions: Vec<PdbFunction>,
+}
+
+/// Parses the PDB file in `bytes`.
+pub fn parse(bytes: &[u8]) -> Result<Pdb> {
+    let mut file = PDB::open(Cursor::new(bytes))?;
+    let info = file.pdb_information()?;
+    let dbi = file.debug_information()?;
+    let mut pdb = Pdb {
+        guid: info.guid.to_bytes_le(),
+        // the DBI age is the one written into the image
+        age: dbi.age().unwrap_or(info.age),
+        functions: vec![],
+    };
+
+    let globals = file.global_symbols()?;
+    let mut symbols = globals.iter();
+
+    while let Some(symbol) = symbols.next()? {
+        if let Ok(SymbolData::Public(public)) = symbol.parse() {
+            if public.function {
+                pdb.functions.push(
+                    PdbFunction {
+                        name: public.name.to_string().into_owned(),
+                        segment: public.offset.section,
+                        offset: public.offset.offset,
+                        size: None,
+                    }
+                );
+            }
+        }
+    }
+
+    let mut modules = dbi.modules()?;
+
+    while let Some(module) = modules.next()? {
+        let info = match file.module_info(&module)? {
+            Some(info) => info,
+            None => continue,
+        };
+        let mut symbols = info.symbols()?;
+
+        while let Some(symbol) = symbols.next()? {
+            if let Ok(SymbolData::Procedure(procedure)) = symbol.parse() {
+                pdb.functions.push(
+                    PdbFunction {
+                        name: procedure.name.to_string().into_owned(),
+                        segment: procedure.offset.section,
+                        offset: procedure.offset.offset,
+                        size: Some(procedure.len),
+                    }
+                );
+            }
+        }
+    }
+
+    Ok(pdb)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn not_msf() {
+        assert!(parse(b"Microsoft C/C++ program database 2.00\r\n\x1aJG\0\0").is_err());
+    }
+}
@@ -29,7 +29,7 @@
 //! error node.
 
 
-use crate::{Function, Statement, Operation, Rvalue};
+use crate::{Bound, DebugFunction, Function, FunctionKind, Statement, Operation, Rvalue};
 use panopticon_graph_algos::{AdjacencyList, AdjacencyMatrixGraphTrait, GraphTrait, MutableGraphTrait, VertexListGraphTrait};
 use panopticon_graph_algos::adjacency_list::{AdjacencyListVertexDescriptor, VertexLabelIterator, VertexLabelMutIterator};
 use uuid::Uuid;
@@ -126,6 +126,16 @@ pub struct Program {
     pub call_graph: CallGraph,
     /// Symbolic References (Imports)
     pub imports: ::std::collections::HashMap<u64, String>,
+    /// Size in bytes of the function symbols the binary declares, keyed by start address.
+    #[serde(default)]
+    pub symbol_sizes: ::std::collections::HashMap<u64, u64>,
+    /// DWARF descriptions of functions, keyed by start address.
+    #[serde(default)]
+    pub debug_info: ::std::collections::HashMap<u64, DebugFunction>,
+    /// Import stubs keyed by start address, with the import name and the address of the symbol
+    /// pointer they jump through.
+    #[serde(default)]
+    pub stubs: ::std::collections::HashMap<u64, (String, u64)>,
 }
 
 impl<'a> IntoIterator for &'a Program {
@@ -144,9 +154,59 @@ impl Program {
             name: n.to_string(),
             call_graph: CallGraph::new(),
             imports: ::std::collections::HashMap::new(),
+            symbol_sizes: ::std::collections::HashMap::new(),
+            debug_info: ::std::collections::HashMap::new(),
+            stubs: ::std::collections::HashMap::new(),
         }
     }
 
+    /// Attaches the debug information for `function`'s start address, if any. Functions without
+    /// a symbol get their source-level name.
+    pub fn attach_debug_info(&self, function: &mut Function) {
+        if let Some(info) = self.debug_info.get(&function.start()) {
+            if let Some(ref name) = info.name {
+                if function.name == format!("func_{:#x}", function.start()) {
+                    function.name = name.clone();
+                }
+            }
+            function.set_debug_info(info.clone());
+        }
+    }
+
+    /// Moves all functions, call targets and address keyed tables by `delta` bytes. Constants in
+    /// disassembled code are adjusted if they were read from relocated cells, see
+    /// `Function::rebase`.
+    pub fn rebase(&mut self, delta: i64, is_relocated: &Fn(&Bound, u64) -> bool) {
+        let shift = |a: u64| a.wrapping_add(delta as u64);
+
+        for ct in self.call_graph.vertex_labels_mut() {
+            match ct {
+                &mut CallTarget::Concrete(ref mut function) => function.rebase(delta, is_relocated),
+                &mut CallTarget::Todo(Rvalue::Constant { ref mut value, .. }, _, _) => *value = shift(*value),
+                _ => (),
+            }
+        }
+
+        self.imports = self.imports.drain().map(|(a, n)| (shift(a), n)).collect();
+        self.symbol_sizes = self.symbol_sizes.drain().map(|(a, s)| (shift(a), s)).collect();
+        self.debug_info = self.debug_info
+            .drain()
+            .map(
+                |(a, mut info)| {
+                    info.rebase(delta);
+                    (shift(a), info)
+                }
+            )
+            .collect();
+        self.stubs = self.stubs.drain().map(|(a, (n, p))| (shift(a), (n, shift(p)))).collect();
+    }
+
+    /// Returns the address range the function symbol starting at `start` was declared with, if
+    /// the binary has one.
+    pub fn symbol_extent(&self, start: u64) -> Option<Bound> {
+        self.symbol_sizes.get(&start).map(|&size| Bound::new(start, start + size))
+    }
+
     /// Returns a function if it matches the condition in the `filter` closure.
     pub fn find_function_by<'a, F: (Fn(&Function) -> bool)>(&'a self, filter: F) -> Option<&'a Function> {
         for ct in self.call_graph.vertex_labels() {
@@ -206,8 +266,16 @@ impl Program {
     }
 
     /// Puts `function` into the call graph, returning the UUIDs of all _new_ `Todo`s
-    /// that are called by `function`
-    pub fn insert(&mut self, function: Function) -> Vec<Uuid> {
+    /// that are called by `function`. Calls to addresses in `imports` are linked to the
+    /// `Symbolic` reference of the import. Functions starting at one of the `stubs` become
+    /// `FunctionKind::Stub`.
+    pub fn insert(&mut self, mut function: Function) -> Vec<Uuid> {
+        if let Some(&(ref name, plt_address)) = self.stubs.get(&function.start()) {
+            if let &FunctionKind::Regular = function.kind() {
+                function.set_plt(name, plt_address);
+            }
+        }
+
         let maybe_vx = self.call_graph.vertices().find(|ct| self.call_graph.vertex_label(*ct).unwrap().uuid() == function.uuid());
 
         let calls = function.collect_calls();
@@ -223,9 +291,19 @@ impl Program {
 
         for a in calls {
             let l = other_funs.len();
+            let import = match &a {
+                &Rvalue::Constant { value, .. } => self.imports.get(&value).cloned(),
+                _ => None,
+            };
 
             for w in self.call_graph.vertices() {
                 match self.call_graph.vertex_label(w) {
+                    Some(&CallTarget::Symbolic(ref name, _)) => {
+                        if Some(name) == import.as_ref() {
+                            other_funs.push(w);
+                            break;
+                        }
+                    }
                     Some(&CallTarget::Concrete(ref function)) => {
                         if let Rvalue::Constant { ref value, .. } = a {
                             if *value == function.start() {
@@ -246,10 +324,16 @@ impl Program {
 
             if l == other_funs.len() {
                 let uu = Uuid::new_v4();
-                let v = self.call_graph.add_vertex(CallTarget::Todo(a, None, uu));
 
-                self.call_graph.add_edge((), new_vx, v);
-                todos.push(uu);
+                if let Some(name) = import {
+                    let v = self.call_graph.add_vertex(CallTarget::Symbolic(name, uu));
+                    self.call_graph.add_edge((), new_vx, v);
+                } else {
+                    let v = self.call_graph.add_vertex(CallTarget::Todo(a, None, uu));
+
+                    self.call_graph.add_edge((), new_vx, v);
+                    todos.push(uu);
+                }
             }
         }
 
@@ -286,29 +370,37 @@ impl Program {
     pub fn functions_mut(&mut self) -> FunctionMutIterator {
         FunctionMutIterator::new(&mut self.call_graph)
     }
-    /// Calls [Function::set_plt](../function/struct.Function.html#method.set_plt) on all matching functions
+    /// Calls [Function::set_plt](../function/struct.Function.html#method.set_plt) on all functions
+    /// that consist of a single mnemonic jumping through an import's address, independent of how
+    /// many statements the mnemonic was simplified into.
     pub fn update_plt(&mut self) {
         for ct in self.call_graph.vertex_labels_mut() {
             match ct {
                 &mut CallTarget::Concrete(ref mut function) => {
                     let address = {
-                        let mut last = None;
-                        let mut count = 0;
-                        for statement in function.statements() {
-                            count += 1;
-                            last = Some(statement);
-                        }
-                        if count == 2 {
-                            if let Some( &Statement { op: Operation::Load(_, _, _, Rvalue::Constant { value, .. }), .. }) = last {
-                                Some(value)
-                            } else {
-                                None
+                        let mut bbs = function.basic_blocks();
+
+                        match (bbs.next(), bbs.next()) {
+                            (Some(bb), None) if bb.mnemonics.len() == 1 => {
+                                bb.mnemonics[0]
+                                    .instructions
+                                    .iter()
+                                    .filter_map(
+                                        |stmt| match stmt {
+                                            &Statement { op: Operation::Load(_, _, _, Rvalue::Constant { value, .. }), .. } => Some(value),
+                                            _ => None,
+                                        }
+                                    )
+                                    .last()
                             }
-                        } else {
-                            None
+                            _ => None,
                         }
                     };
-                    if let Some(address) = address {
+                    let is_regular = match function.kind() {
+                        &FunctionKind::Regular => true,
+                        _ => false,
+                    };
+                    if let (Some(address), true) = (address, is_regular) {
                         match self.imports.get(&address) {
                             Some(import) => {
                                 function.set_plt(import, address);
@@ -430,4 +522,28 @@ mod tests {
         assert_eq!(prog.call_graph.num_edges(), 1);
         assert_eq!(prog.call_graph.num_vertices(), 2);
     }
+    #[test]
+    fn insert_links_imports() {
+        let mut prog = Program::new("prog_test");
+        let mut func = Function::undefined(0, None, &Region::undefined("ram".to_owned(), 100), Some("test".to_owned()));
+        let i1 = vec![
+            Statement {
+                op: Operation::Call(Rvalue::new_u64(0x30)),
+                assignee: Lvalue::Undefined,
+            },
+        ];
+        let mne1 = Mnemonic::new(0..5, "call".to_string(), "".to_string(), vec![].iter(), i1.iter()).unwrap();
+        let vx = func.cfg_mut().add_vertex(ControlFlowTarget::Resolved(BasicBlock::from_vec(vec![mne1])));
+        func.set_entry_point_ref(vx);
+
+        prog.imports.insert(0x30, "printf".to_string());
+
+        let fvx = prog.call_graph.add_vertex(CallTarget::Symbolic("printf".to_string(), Uuid::new_v4()));
+        let new = prog.insert(func);
+
+        assert_eq!(new, vec![]);
+        assert_eq!(prog.call_graph.num_vertices(), 2);
+        assert_eq!(prog.call_graph.num_edges(), 1);
+        assert!(prog.call_graph.edges().all(|e| prog.call_graph.target(e) == fvx));
+    }
 }
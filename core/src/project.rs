@@ -21,19 +21,21 @@
 //! Projects are a set of `Program`s, associated memory `Region`s and comments.
 
 
-use crate::{CallGraphRef, Function, Program, Region, Result, World};
-use panopticon_graph_algos::GraphTrait;
-use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use crate::{Architecture, Bound, CallGraphRef, CallTarget, Function, Layer, Program, Region, Result, World};
+use crate::layer::Cell;
+use panopticon_graph_algos::{GraphTrait, IncidenceGraphTrait, MutableGraphTrait, VertexListGraphTrait};
+use byteorder::{BigEndian, ByteOrder, LittleEndian, ReadBytesExt, WriteBytesExt};
 use flate2::Compression;
 use flate2::read::ZlibDecoder;
 use flate2::write::ZlibEncoder;
 use serde_cbor::de::Deserializer;
 use serde_cbor::ser::Serializer;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
 use std::io::{Read, Write};
 use std::path::Path;
+use std::u64;
 
 use uuid::Uuid;
 
@@ -50,6 +52,33 @@ pub struct Project {
     pub comments: HashMap<(String, u64), String>,
     /// Symbolic References (Imports)
     pub imports: HashMap<u64, String>,
+    /// Absolute addresses stored in the image that need fixing up when it's moved
+    #[serde(default)]
+    pub relocations: Vec<Relocation>,
+    /// Parts of the input file mapped into the root `Region`
+    #[serde(default)]
+    pub file_mappings: Vec<FileMapping>,
+    /// Bytes written by `patch`, by address
+    #[serde(default)]
+    pub patches: BTreeMap<u64, u8>,
+}
+
+/// Little endian address of `size` bytes at `address` that is relative to the image base.
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Serialize,Deserialize)]
+pub struct Relocation {
+    /// Address of the first byte
+    pub address: u64,
+    /// Size in bytes, either 4 or 8
+    pub size: usize,
+}
+
+/// Range of the root `Region` filled with the contents of the input file.
+#[derive(Clone,Debug,PartialEq,Eq,Serialize,Deserialize)]
+pub struct FileMapping {
+    /// Addresses the file contents are mapped to
+    pub bound: Bound,
+    /// File offset of the first mapped byte
+    pub offset: u64,
 }
 
 impl Project {
@@ -61,7 +90,175 @@ impl Project {
             data: World::new(r),
             comments: HashMap::new(),
             imports: HashMap::new(),
+            relocations: Vec::new(),
+            file_mappings: Vec::new(),
+            patches: BTreeMap::new(),
+        }
+    }
+
+    /// Moves the project by `delta` bytes, as if the image was loaded at a different base.
+    ///
+    /// The layers of the root `Region` are shifted and all relocated cells are patched by a new
+    /// `Layer` on top. Functions, call targets, imports, patches and comments inside the image
+    /// move along. Constants in disassembled code that were read from relocated cells are
+    /// adjusted, so nothing needs to be disassembled again.
+    pub fn rebase(&mut self, delta: i64) -> Result<()> {
+        if delta == 0 {
+            return Ok(());
+        }
+
+        let shift = |a: u64| a.wrapping_add(delta as u64);
+        let root = self.data.root;
+        let image = self.region().stack().iter().skip(1).map(|&(ref b, _)| b.clone()).collect::<Vec<Bound>>();
+        let region = self.data.dependencies.vertex_label_mut(root).unwrap();
+
+        region.rebase(delta)?;
+
+        let mut patches = Vec::<(u64, Vec<u8>)>::with_capacity(self.relocations.len());
+        // address, size and value of each relocation before the move
+        let mut fixups = Vec::<(u64, u64, u64)>::with_capacity(self.relocations.len());
+
+        for reloc in self.relocations.iter_mut() {
+            let original = reloc.address;
+
+            reloc.address = shift(reloc.address);
+
+            let cells = region.iter().seek(reloc.address).take(reloc.size).collect::<Vec<Cell>>();
+            if cells.len() != reloc.size || cells.iter().any(|c| c.is_none()) {
+                return Err(format!("Relocation at {:#x} covers undefined cells", reloc.address).into());
+            }
+
+            let mut bytes = cells.into_iter().map(|c| c.unwrap()).collect::<Vec<u8>>();
+            let mask = if reloc.size >= 8 { u64::MAX } else { (1 << (reloc.size * 8)) - 1 };
+            let value = LittleEndian::read_uint(&bytes, reloc.size);
+
+            fixups.push((original, reloc.size as u64, value));
+            LittleEndian::write_uint(&mut bytes, shift(value) & mask, reloc.size);
+            patches.push((reloc.address, bytes));
+        }
+
+        if !patches.is_empty() {
+            let start = patches.iter().map(|&(a, _)| a).min().unwrap();
+            let end = patches.iter().map(|&(a, ref b)| a + b.len() as u64).max().unwrap();
+            let mut layer = Layer::writable();
+
+            for (address, bytes) in patches {
+                for (i, b) in bytes.into_iter().enumerate() {
+                    layer.write(address - start + i as u64, Some(b));
+                }
+            }
+
+            if !region.cover(Bound::new(start, end), layer) {
+                return Err(format!("Cannot cover bound: {:?}", Bound::new(start, end)).into());
+            }
         }
+
+        let is_image = |a: u64| image.iter().any(|b| b.start <= a && a < b.end);
+        let is_relocated = |area: &Bound, value: u64| fixups.iter().any(|&(a, s, v)| area.start <= a && a + s <= area.end && v == value);
+
+        for prog in self.code.iter_mut() {
+            prog.rebase(delta, &is_relocated);
+        }
+        self.comments = self.comments.drain().map(|((r, a), c)| if is_image(a) { ((r, shift(a)), c) } else { ((r, a), c) }).collect();
+        self.imports = self.imports.drain().map(|(a, n)| (shift(a), n)).collect();
+        self.patches = self.patches.iter().map(|(&a, &b)| (shift(a), b)).collect();
+        for mapping in self.file_mappings.iter_mut() {
+            mapping.bound = mapping.bound.shifted(delta);
+        }
+
+        Ok(())
+    }
+
+    /// Overwrites the cells of the root `Region` starting at `address` with `bytes` and
+    /// disassembles all functions containing one of them again. Returns the UUIDs of these
+    /// functions and of the new `Todo`s called by the new code.
+    ///
+    /// The bytes are written to a new writable `Layer` and recorded in `patches`. The call edges of
+    /// the functions are rebuilt by `Program::insert`.
+    pub fn patch<A: Architecture>(&mut self, address: u64, bytes: &[u8], configuration: A::Configuration) -> Result<(Vec<Uuid>, Vec<Uuid>)> {
+        if bytes.is_empty() {
+            return Ok((vec![], vec![]));
+        }
+
+        let end = address.checked_add(bytes.len() as u64).ok_or_else(|| "Patch overflows the address space")?;
+        let root = self.data.root;
+        let region = self.data.dependencies.vertex_label_mut(root).unwrap();
+        let mut layer = Layer::writable();
+
+        for (i, &b) in bytes.iter().enumerate() {
+            layer.write(i as u64, Some(b));
+        }
+
+        if !region.cover(Bound::new(address, end), layer) {
+            return Err(format!("Cannot cover bound: {:?}", Bound::new(address, end)).into());
+        }
+
+        self.patches.extend(bytes.iter().enumerate().map(|(i, &b)| (address + i as u64, b)));
+
+        let region = self.data.dependencies.vertex_label(root).unwrap();
+        let mut ret = vec![];
+        let mut todos = vec![];
+
+        for prog in self.code.iter_mut() {
+            let mut reloaded = vec![];
+
+            for ct in prog.call_graph.vertex_labels_mut() {
+                if let &mut CallTarget::Concrete(ref mut func) = ct {
+                    if func.basic_blocks().any(|bb| bb.area.start < end && address < bb.area.end) {
+                        func.reload::<A>(region, configuration.clone())?;
+                        reloaded.push(func.clone());
+                    }
+                }
+            }
+
+            for func in reloaded {
+                if let Some(vx) = prog.find_call_target_by_uuid(func.uuid()) {
+                    let stale = prog.call_graph.out_edges(vx).collect::<Vec<_>>();
+
+                    for e in stale {
+                        prog.call_graph.remove_edge(e);
+                    }
+                }
+
+                ret.push(func.uuid().clone());
+                todos.extend(prog.insert(func));
+            }
+        }
+
+        Ok((ret, todos))
+    }
+
+    /// Returns a copy of `original`, the file this project was loaded from, with all bytes written
+    /// by `patch` put back at their file offsets. Cells changed by `rebase` are not exported.
+    pub fn patched_file(&self, original: &[u8]) -> Result<Vec<u8>> {
+        let mut ret = original.to_vec();
+
+        for (&address, &byte) in self.patches.iter() {
+            let mapping = self.file_mappings.iter().find(|m| m.bound.start <= address && address < m.bound.end);
+            let offset = match mapping {
+                Some(m) => (m.offset + address - m.bound.start) as usize,
+                None => return Err(format!("Patched cell at {:#x} is not part of the file", address).into()),
+            };
+
+            match ret.get_mut(offset) {
+                Some(b) => *b = byte,
+                None => return Err(format!("File offset {:#x} of {:#x} is out of range", offset, address).into()),
+            }
+        }
+
+        Ok(ret)
+    }
+
+    /// Writes the file at `original` with all patches applied to `path`. See `patched_file`.
+    pub fn export(&self, original: &Path, path: &Path) -> Result<()> {
+        let mut bytes = Vec::new();
+
+        File::open(original)?.read_to_end(&mut bytes)?;
+
+        let patched = self.patched_file(&bytes)?;
+
+        File::create(path)?.write_all(&patched)?;
+        Ok(())
     }
 
     /// Returns this project's root Region
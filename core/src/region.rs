@@ -50,10 +50,14 @@
 //! This region is named "undef" and is just 4k of undefined cells
 
 
-use crate::{Bound, Layer, LayerIter, OpaqueLayer, Result};
+use crate::{Bound, Endianess, Layer, LayerIter, OpaqueLayer, Result, Transform};
+use crate::layer::Cell;
 use panopticon_graph_algos::{AdjacencyList, GraphTrait, IncidenceGraphTrait, MutableGraphTrait, VertexListGraphTrait};
 use panopticon_graph_algos::adjacency_list::{AdjacencyListEdgeDescriptor, AdjacencyListVertexDescriptor};
+use serde::{Deserialize, Deserializer};
+use std::cmp::{self, Ordering};
 use std::collections::HashSet;
+use std::mem;
 use std::path::Path;
 use std::sync::Arc;
 
@@ -62,11 +66,121 @@ use std::sync::Arc;
 /// `Region`s are a stack of [`Layer`](../layer/index.html) inside a single address space. The
 /// `Region` is the primary way panopticon handles data. They can be created from files or
 /// in-memory buffers.
-#[derive(Clone,Debug,Serialize,Deserialize)]
+#[derive(Clone,Debug,Serialize)]
 pub struct Region {
     stack: Vec<(Bound, Layer)>,
     name: String,
     size: u64,
+    sections: Vec<Section>,
+    segments: Vec<Segment>,
+    transform: Option<Transform>,
+    /// `flatten()` as indices into `stack`, sorted by address. Used by `read`.
+    #[serde(skip_serializing)]
+    index: Vec<(Bound, usize)>,
+}
+
+/// Serialized fields of a `Region`. The index is rebuilt after deserializing.
+#[derive(Deserialize)]
+#[serde(rename = "Region")]
+struct RegionFields {
+    stack: Vec<(Bound, Layer)>,
+    name: String,
+    size: u64,
+    #[serde(default)]
+    sections: Vec<Section>,
+    #[serde(default)]
+    segments: Vec<Segment>,
+    #[serde(default)]
+    transform: Option<Transform>,
+}
+
+impl<'de> Deserialize<'de> for Region {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> ::std::result::Result<Region, D::Error> {
+        let f = RegionFields::deserialize(deserializer)?;
+        let mut reg = Region {
+            stack: f.stack,
+            name: f.name,
+            size: f.size,
+            sections: f.sections,
+            segments: f.segments,
+            transform: f.transform,
+            index: vec![],
+        };
+
+        reg.reindex();
+        Ok(reg)
+    }
+}
+
+/// Section is writable at runtime (`SHF_WRITE`).
+pub const SECTION_WRITE: u64 = 0x1;
+/// Section occupies memory at runtime (`SHF_ALLOC`).
+pub const SECTION_ALLOC: u64 = 0x2;
+/// Section contains executable code (`SHF_EXECINSTR`).
+pub const SECTION_EXECINSTR: u64 = 0x4;
+
+/// Named part of a `Region`, for example an ELF section like `.text` or `.rodata`.
+///
+/// Sections only describe the layout of a `Region`, they don't change its contents.
+#[derive(Clone,Debug,PartialEq,Eq,Serialize,Deserialize)]
+pub struct Section {
+    /// Section name as found in the binary.
+    pub name: String,
+    /// Part of the `Region` the section occupies.
+    pub bound: Bound,
+    /// Section flags. The `SECTION_*` bits have the same meaning as the ELF `SHF_*` flags.
+    pub flags: u64,
+}
+
+impl Section {
+    /// Returns true if the section contains executable code.
+    pub fn is_executable(&self) -> bool {
+        self.flags & SECTION_EXECINSTR != 0
+    }
+
+    /// Returns true if the section is writable at runtime.
+    pub fn is_writable(&self) -> bool {
+        self.flags & SECTION_WRITE != 0
+    }
+}
+
+/// Segment is executable at runtime (`PF_X`).
+pub const SEGMENT_EXECUTE: u64 = 0x1;
+/// Segment is writable at runtime (`PF_W`).
+pub const SEGMENT_WRITE: u64 = 0x2;
+/// Segment is readable at runtime (`PF_R`).
+pub const SEGMENT_READ: u64 = 0x4;
+
+/// Part of a `Region` that is mapped with the same access rights, for example an ELF program
+/// header, a PE section or a Mach-o segment.
+///
+/// Unlike `Section`s, segments decide what the analysis does: `Function`s are only disassembled
+/// from executable segments.
+#[derive(Clone,Debug,PartialEq,Eq,Serialize,Deserialize)]
+pub struct Segment {
+    /// Segment name, if the file format has one.
+    pub name: Option<String>,
+    /// Part of the `Region` the segment occupies.
+    pub bound: Bound,
+    /// Access rights. The `SEGMENT_*` bits have the same meaning as the ELF `PF_*` flags.
+    pub flags: u64,
+}
+
+impl Segment {
+    /// Returns true if the segment can be executed.
+    pub fn is_executable(&self) -> bool {
+        self.flags & SEGMENT_EXECUTE != 0
+    }
+
+    /// Returns true if the segment is writable at runtime.
+    pub fn is_writable(&self) -> bool {
+        self.flags & SEGMENT_WRITE != 0
+    }
+
+    /// Returns true if the segment is readable at runtime.
+    pub fn is_readable(&self) -> bool {
+        self.flags & SEGMENT_READ != 0
+    }
 }
 
 /// Graph that models overlapping regions.
@@ -111,7 +225,27 @@ impl Region {
     pub fn new(name: String, root: OpaqueLayer) -> Region {
         let l = root.len();
         let b = Layer::Opaque(root);
-        Region { stack: vec![(Bound::new(0, l), b)], name: name, size: l }
+        let mut reg = Region {
+            stack: vec![(Bound::new(0, l), b)],
+            name: name,
+            size: l,
+            sections: Vec::new(),
+            segments: Vec::new(),
+            transform: None,
+            index: vec![],
+        };
+
+        reg.reindex();
+        reg
+    }
+
+    /// Creates a new `Region` called `name`, filled with the result of decoding `data` with
+    /// `transform`.
+    pub fn decode(name: String, transform: Transform, data: &[u8]) -> Result<Region> {
+        let mut reg = Region::wrap(name, transform.apply(data)?);
+
+        reg.transform = Some(transform);
+        Ok(reg)
     }
 
     /// Applies `layer` to the cells inside `area`.
@@ -128,6 +262,9 @@ impl Region {
             }
 
             self.stack.push((b, l));
+
+            let idx = self.stack.len() - 1;
+            self.index_layer(idx);
             true
         } else {
             false
@@ -162,7 +299,7 @@ impl Region {
         ret
     }
 
-    fn add<'a>(a: (Bound, &'a Layer), v: Vec<(Bound, &'a Layer)>) -> Vec<(Bound, &'a Layer)> {
+    fn add<T: Clone>(a: (Bound, T), v: Vec<(Bound, T)>) -> Vec<(Bound, T)> {
         let mut ret = v.iter()
             .fold(
                 Vec::new(), |mut acc, x| {
@@ -177,7 +314,7 @@ impl Region {
                         // a covers start of x
                         let bound = Bound::new(a.0.end, x.0.end);
                         if bound.start < bound.end {
-                            acc.push((bound, x.1));
+                            acc.push((bound, x.1.clone()));
                         }
                         acc
                     } else if a.0.start > x.0.start && a.0.end >= x.0.end {
@@ -185,7 +322,7 @@ impl Region {
                         let bound = Bound::new(x.0.start, a.0.start);
 
                         if bound.start < bound.end {
-                            acc.push((bound, x.1));
+                            acc.push((bound, x.1.clone()));
                         }
                         acc
                     } else {
@@ -193,10 +330,10 @@ impl Region {
                         let bound1 = Bound::new(x.0.start, a.0.start);
                         let bound2 = Bound::new(a.0.end, x.0.end);
                         if bound1.start < bound1.end {
-                            acc.push((bound1, x.1));
+                            acc.push((bound1, x.1.clone()));
                         }
                         if bound2.start < bound2.end {
-                            acc.push((bound2, x.1));
+                            acc.push((bound2, x.1.clone()));
                         }
                         acc
                     }
@@ -206,6 +343,140 @@ impl Region {
         ret
     }
 
+    /// Reads the `Cell`s starting at `addr` into `buf`.
+    ///
+    /// # Returns
+    /// Number of `Cell`s read. Less than `buf.len()` if the end of the `Region` is reached.
+    pub fn read(&self, addr: u64, buf: &mut [Cell]) -> usize {
+        if addr >= self.size {
+            return 0;
+        }
+
+        let len = cmp::min(buf.len() as u64, self.size - addr) as usize;
+        let mut seg = match self.index.binary_search_by(
+            |&(ref b, _)| if b.end <= addr {
+                Ordering::Less
+            } else if b.start > addr {
+                Ordering::Greater
+            } else {
+                Ordering::Equal
+            }
+        ) {
+            Ok(seg) => seg,
+            Err(_) => return 0,
+        };
+        let mut pos = 0;
+
+        while pos < len && seg < self.index.len() {
+            let (ref bound, idx) = self.index[seg];
+            let a = addr + pos as u64;
+            let n = cmp::min(bound.end - a, (len - pos) as u64) as usize;
+            let (ref area, ref layer) = self.stack[idx];
+
+            match *layer {
+                Layer::Opaque(ref o) => o.read(a - area.start, &mut buf[pos..pos + n]),
+                _ => {
+                    for i in 0..n {
+                        buf[pos + i] = self.cell(a + i as u64, idx + 1);
+                    }
+                }
+            }
+
+            pos += n;
+            seg += 1;
+        }
+
+        pos
+    }
+
+    /// Reads a byte at `addr`. Returns `None` if the `Cell` is undefined or outside the `Region`.
+    pub fn read_u8(&self, addr: u64) -> Option<u8> {
+        self.read_uint(addr, 1, Endianess::Little).map(|x| x as u8)
+    }
+
+    /// Reads a 16 bit integer at `addr`. Returns `None` if any `Cell` is undefined or outside the
+    /// `Region`.
+    pub fn read_u16(&self, addr: u64, endianess: Endianess) -> Option<u16> {
+        self.read_uint(addr, 2, endianess).map(|x| x as u16)
+    }
+
+    /// Reads a 32 bit integer at `addr`. Returns `None` if any `Cell` is undefined or outside the
+    /// `Region`.
+    pub fn read_u32(&self, addr: u64, endianess: Endianess) -> Option<u32> {
+        self.read_uint(addr, 4, endianess).map(|x| x as u32)
+    }
+
+    /// Reads a 64 bit integer at `addr`. Returns `None` if any `Cell` is undefined or outside the
+    /// `Region`.
+    pub fn read_u64(&self, addr: u64, endianess: Endianess) -> Option<u64> {
+        self.read_uint(addr, 8, endianess)
+    }
+
+    fn read_uint(&self, addr: u64, len: usize, endianess: Endianess) -> Option<u64> {
+        let mut buf = [None; 8];
+
+        if self.read(addr, &mut buf[0..len]) < len {
+            return None;
+        }
+
+        let mut ret = 0;
+
+        for i in 0..len {
+            let b = match endianess {
+                Endianess::Little => buf[len - 1 - i],
+                Endianess::Big => buf[i],
+            };
+
+            ret = (ret << 8) | b? as u64;
+        }
+
+        Some(ret)
+    }
+
+    /// Value of the `Cell` at `addr` as seen by the `Layer` at `stack[top]`, i.e. with only the
+    /// `Layer`s below `top` applied.
+    fn cell(&self, addr: u64, top: usize) -> Cell {
+        let idx = self.stack[0..top].iter().rposition(|&(ref b, _)| b.start <= addr && addr < b.end)?;
+        let (ref area, ref layer) = self.stack[idx];
+        let offset = addr - area.start;
+
+        match *layer {
+            Layer::Opaque(ref o) => {
+                let mut ret = [None];
+
+                o.read(offset, &mut ret);
+                ret[0]
+            }
+            Layer::Sparse(ref m) => {
+                match m.get(&offset) {
+                    Some(&c) => c,
+                    None => self.cell(addr, idx),
+                }
+            }
+            Layer::KeyStream { ref key, .. } if key.is_empty() => self.cell(addr, idx),
+            Layer::KeyStream { op, ref key } => self.cell(addr, idx).map(|c| op.apply(c, key[(offset % key.len() as u64) as usize])),
+        }
+    }
+
+    /// Adds `stack[idx]` on top of the index used by `read`.
+    fn index_layer(&mut self, idx: usize) {
+        let index = mem::replace(&mut self.index, Vec::new());
+        let mut index = Self::add((self.stack[idx].0.clone(), idx), index);
+
+        index.retain(|&(ref b, _)| b.start < b.end);
+        index.sort_by(|a, b| a.0.start.cmp(&b.0.start));
+        self.index = index;
+    }
+
+    /// Rebuilds the index used by `read` from the `Layer` stack.
+    fn reindex(&mut self) {
+        self.index.clear();
+
+        for idx in 0..self.stack.len() {
+            self.index_layer(idx);
+        }
+    }
+
     /// Vector of all uncovered parts.
     pub fn flatten(&self) -> Vec<(Bound, &Layer)> {
         let mut ret = Vec::new();
@@ -230,6 +501,110 @@ impl Region {
     pub fn name(&self) -> &String {
         &self.name
     }
+
+    /// Moves all `Layer`s except the bottom one and all sections and segments by `delta` cells.
+    ///
+    /// # Returns
+    /// `Err` if a `Layer` would end up outside of `0..self.size()`. The `Region` is unchanged in
+    /// this case.
+    pub fn rebase(&mut self, delta: i64) -> Result<()> {
+        let size = self.size;
+        let shift = |a: u64| if delta < 0 { a.checked_sub((delta as u64).wrapping_neg()) } else { a.checked_add(delta as u64) };
+        let fits = |b: &Bound| match (shift(b.start), shift(b.end)) {
+            (Some(_), Some(end)) => end <= size,
+            _ => false,
+        };
+
+        if let Some(&(ref b, _)) = self.stack.iter().skip(1).find(|&&(ref b, _)| !fits(b)) {
+            return Err(format!("{:?} moved by {} is outside of region {}", b, delta, self.name).into());
+        }
+        if let Some(s) = self.sections.iter().find(|s| !fits(&s.bound)) {
+            return Err(format!("Section {} moved by {} is outside of region {}", s.name, delta, self.name).into());
+        }
+        if let Some(s) = self.segments.iter().find(|s| !fits(&s.bound)) {
+            return Err(format!("Segment {:?} moved by {} is outside of region {}", s.bound, delta, self.name).into());
+        }
+
+        for &mut (ref mut b, _) in self.stack.iter_mut().skip(1) {
+            *b = b.shifted(delta);
+        }
+        for s in self.sections.iter_mut() {
+            s.bound = s.bound.shifted(delta);
+        }
+        for s in self.segments.iter_mut() {
+            s.bound = s.bound.shifted(delta);
+        }
+
+        self.reindex();
+        Ok(())
+    }
+
+    /// Adds a named section to the `Region`.
+    ///
+    /// # Returns
+    /// `false` if the section is outside of `0..self.size()`, `true` otherwise.
+    pub fn add_section(&mut self, section: Section) -> bool {
+        if section.bound.start <= section.bound.end && section.bound.end <= self.size {
+            self.sections.push(section);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// All sections of the `Region` in the order they were added.
+    pub fn sections(&self) -> &Vec<Section> {
+        &self.sections
+    }
+
+    /// Returns the section containing `addr`, if any.
+    pub fn section_at(&self, addr: u64) -> Option<&Section> {
+        self.sections.iter().find(|s| s.bound.start <= addr && addr < s.bound.end)
+    }
+
+    /// Returns the section called `name`, if any.
+    pub fn find_section(&self, name: &str) -> Option<&Section> {
+        self.sections.iter().find(|s| s.name == name)
+    }
+
+    /// Adds a segment to the `Region`. Segments added later take precedence over overlapping ones
+    /// added before.
+    ///
+    /// # Returns
+    /// `false` if the segment is outside of `0..self.size()`, `true` otherwise.
+    pub fn add_segment(&mut self, segment: Segment) -> bool {
+        if segment.bound.start <= segment.bound.end && segment.bound.end <= self.size {
+            self.segments.push(segment);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// All segments of the `Region` in the order they were added.
+    pub fn segments(&self) -> &Vec<Segment> {
+        &self.segments
+    }
+
+    /// Returns the segment containing `addr`, if any.
+    pub fn segment_at(&self, addr: u64) -> Option<&Segment> {
+        self.segments.iter().rev().find(|s| s.bound.start <= addr && addr < s.bound.end)
+    }
+
+    /// Returns true if code at `addr` can be executed. `Region`s without segments, like raw images,
+    /// are executable everywhere. Otherwise `addr` must be inside an executable segment.
+    pub fn is_executable(&self, addr: u64) -> bool {
+        if self.segments.is_empty() {
+            true
+        } else {
+            self.segment_at(addr).map(|s| s.is_executable()).unwrap_or(false)
+        }
+    }
+
+    /// The `Transform` this `Region` was decoded with, if any.
+    pub fn transform(&self) -> Option<Transform> {
+        self.transform
+    }
 }
 
 impl World {
@@ -241,6 +616,33 @@ impl World {
         World { dependencies: g, root: b }
     }
 
+    /// Decodes the `Cell`s inside `area` of `reg` using `transform` and adds the result as a new
+    /// `Region` overlapping them. Fails if any of the `Cell`s is undefined.
+    pub fn unpack(&mut self, reg: RegionRef, area: Bound, transform: Transform) -> Result<RegionRef> {
+        let decoded = {
+            let region = match self.dependencies.vertex_label(reg) {
+                Some(region) => region,
+                None => return Err("Unknown region".into()),
+            };
+
+            if area.start > area.end || area.end > region.size() {
+                return Err(format!("{:?} is outside of region {}", area, region.name()).into());
+            }
+
+            let data = region
+                .iter()
+                .cut(&(area.start..area.end))
+                .collect::<Option<Vec<u8>>>()
+                .ok_or_else(|| format!("Cannot decode undefined cells in {:?}", area))?;
+
+            Region::decode(format!("{}@{:#x}", transform, area.start), transform, &data)?
+        };
+        let new = self.dependencies.add_vertex(decoded);
+
+        self.dependencies.add_edge(area, reg, new);
+        Ok(new)
+    }
+
     /// Vector of all `Region` in `self` and their uncovered area
     pub fn projection(&self) -> Vec<(Bound, RegionRef)> {
         let mut ret = Vec::<(Bound, RegionRef)>::new();
@@ -329,6 +731,124 @@ mod tests {
         assert_eq!(proj, expect);
     }
 
+    #[test]
+    fn sections() {
+        let mut reg = Region::undefined("".to_string(), 0x100);
+        let text = Section { name: ".text".to_string(), bound: Bound::new(0x10, 0x40), flags: SECTION_ALLOC | SECTION_EXECINSTR };
+        let rodata = Section { name: ".rodata".to_string(), bound: Bound::new(0x40, 0x48), flags: SECTION_ALLOC };
+
+        assert!(reg.add_section(text.clone()));
+        assert!(reg.add_section(rodata.clone()));
+        assert!(!reg.add_section(Section { name: ".bss".to_string(), bound: Bound::new(0xf0, 0x110), flags: SECTION_ALLOC | SECTION_WRITE }));
+
+        assert_eq!(reg.section_at(0x10), Some(&text));
+        assert_eq!(reg.section_at(0x40), Some(&rodata));
+        assert_eq!(reg.section_at(0x48), None);
+        assert!(reg.section_at(0x3f).unwrap().is_executable());
+        assert!(!reg.find_section(".rodata").unwrap().is_executable());
+        assert_eq!(reg.sections().len(), 2);
+    }
+
+    #[test]
+    fn segments() {
+        let mut reg = Region::undefined("".to_string(), 0x100);
+        let text = Segment { name: Some("__TEXT".to_string()), bound: Bound::new(0x10, 0x40), flags: SEGMENT_READ | SEGMENT_EXECUTE };
+        let data = Segment { name: None, bound: Bound::new(0x40, 0x80), flags: SEGMENT_READ | SEGMENT_WRITE };
+
+        assert!(reg.is_executable(0x80));
+        assert!(reg.add_segment(text.clone()));
+        assert!(reg.add_segment(data.clone()));
+        assert!(!reg.add_segment(Segment { name: None, bound: Bound::new(0xf0, 0x110), flags: SEGMENT_READ }));
+
+        assert_eq!(reg.segment_at(0x3f), Some(&text));
+        assert_eq!(reg.segment_at(0x40), Some(&data));
+        assert_eq!(reg.segment_at(0x80), None);
+        assert!(reg.is_executable(0x10));
+        assert!(!reg.is_executable(0x40));
+        assert!(!reg.is_executable(0x80));
+        assert!(reg.segment_at(0x50).unwrap().is_writable());
+        assert!(!reg.segment_at(0x20).unwrap().is_writable());
+
+        // later segments take precedence
+        assert!(reg.add_segment(Segment { name: None, bound: Bound::new(0x48, 0x50), flags: SEGMENT_EXECUTE }));
+        assert!(reg.is_executable(0x48));
+        assert!(!reg.segment_at(0x48).unwrap().is_readable());
+
+        assert!(reg.rebase(0x10).is_ok());
+        assert_eq!(reg.segment_at(0x20).map(|s| s.bound.clone()), Some(Bound::new(0x20, 0x50)));
+        assert!(reg.rebase(0x80).is_err());
+    }
+
+    #[test]
+    fn rebase() {
+        let mut reg = Region::undefined("".to_string(), 0x100);
+
+        assert!(reg.cover(Bound::new(0x10, 0x14), Layer::wrap(vec![1, 2, 3, 4])));
+        assert!(reg.add_section(Section { name: ".text".to_string(), bound: Bound::new(0x10, 0x14), flags: SECTION_ALLOC }));
+
+        assert!(reg.rebase(0x20).is_ok());
+        assert_eq!(reg.iter().seek(0x30).take(4).collect::<Vec<_>>(), vec![Some(1), Some(2), Some(3), Some(4)]);
+        assert_eq!(reg.iter().seek(0x10).next(), Some(None));
+        assert_eq!(reg.find_section(".text").unwrap().bound, Bound::new(0x30, 0x34));
+
+        assert!(reg.rebase(-0x40).is_err());
+        assert!(reg.rebase(0xd0).is_err());
+        assert!(reg.rebase(-0x30).is_ok());
+        assert_eq!(reg.iter().next(), Some(Some(1)));
+    }
+
+    #[test]
+    fn read() {
+        let mut reg = Region::wrap("".to_string(), (0..64).collect());
+        let mut sparse = Layer::writable();
+
+        assert!(sparse.write(1, Some(0xff)));
+        assert!(sparse.write(2, None));
+        assert!(reg.cover(Bound::new(8, 16), Layer::undefined(8)));
+        assert!(reg.cover(Bound::new(12, 24), Layer::wrap((100..112).collect())));
+        assert!(reg.cover(Bound::new(20, 30), Layer::xor(vec![0xf0, 0x0f])));
+        assert!(reg.cover(Bound::new(22, 26), sparse));
+        assert!(reg.cover(Bound::new(40, 48), Layer::add(vec![])));
+
+        let mut buf = vec![None; 64];
+
+        assert_eq!(reg.read(0, &mut buf), 64);
+        assert_eq!(buf, reg.iter().collect::<Vec<Cell>>());
+
+        for addr in 0..70 {
+            let mut buf = vec![None; 7];
+            let len = reg.read(addr, &mut buf);
+
+            assert_eq!(&buf[0..len], &reg.iter().seek(addr).take(7).collect::<Vec<Cell>>()[..]);
+        }
+
+        assert_eq!(reg.read_u8(12), Some(100));
+        assert_eq!(reg.read_u8(15), Some(103));
+        assert_eq!(reg.read_u8(11), None);
+        assert_eq!(reg.read_u16(0x30, Endianess::Little), Some(0x3130));
+        assert_eq!(reg.read_u16(0x30, Endianess::Big), Some(0x3031));
+        assert_eq!(reg.read_u32(0x30, Endianess::Little), Some(0x33323130));
+        assert_eq!(reg.read_u64(0x30, Endianess::Big), Some(0x3031323334353637));
+        assert_eq!(reg.read_u16(63, Endianess::Little), None);
+        assert_eq!(reg.read_u16(22, Endianess::Little), Some(0xff00 | (110 ^ 0xf0)));
+        assert_eq!(reg.read_u16(23, Endianess::Little), None);
+        assert_eq!(reg.read_u8(25), Some(25 ^ 0x0f));
+    }
+
+    #[test]
+    fn read_after_rebase_and_deserialize() {
+        let mut reg = Region::undefined("".to_string(), 0x100);
+
+        assert!(reg.cover(Bound::new(0x10, 0x14), Layer::wrap(vec![1, 2, 3, 4])));
+        assert!(reg.rebase(0x20).is_ok());
+        assert_eq!(reg.read_u32(0x30, Endianess::Little), Some(0x04030201));
+        assert_eq!(reg.read_u8(0x10), None);
+
+        let reg: Region = ::serde_cbor::from_slice(&::serde_cbor::to_vec(&reg).unwrap()).unwrap();
+
+        assert_eq!(reg.read_u32(0x30, Endianess::Big), Some(0x01020304));
+    }
+
     #[test]
     fn read_undefined() {
         let r1 = Region::undefined("test".to_string(), 128);
@@ -368,4 +888,21 @@ mod tests {
         assert_eq!(proj[5].0, Bound::new(134, 140));
         assert_eq!(proj[5].1.as_opaque().unwrap().iter().len(), 140);
     }
+
+    #[test]
+    fn unpack() {
+        // "hello" compressed with zlib, preceded by a two byte header
+        let mut world = World::new(Region::wrap("base".to_string(), vec![0xff, 0xff, 0x78, 0x9c, 0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0x07, 0x00, 0x06, 0x2c, 0x02, 0x15]));
+        let root = world.root;
+        let zlib = world.unpack(root, Bound::new(2, 15), Transform::Zlib).unwrap();
+        let reg = world.dependencies.vertex_label(zlib).unwrap();
+
+        assert_eq!(reg.name(), "zlib@0x2");
+        assert_eq!(reg.transform(), Some(Transform::Zlib));
+        assert_eq!(reg.iter().map(|c| c.unwrap()).collect::<Vec<u8>>(), b"hello".to_vec());
+        assert_eq!(world.projection(), vec![(Bound::new(0, 2), root), (Bound::new(0, 5), zlib)]);
+
+        assert!(world.unpack(root, Bound::new(2, 16), Transform::Zlib).is_err());
+        assert!(world.unpack(root, Bound::new(0, 2), Transform::Zlib).is_err());
+    }
 }
@@ -22,6 +22,9 @@
 
 
 use goblin;
+use lzma_rs;
+use minidump;
+use ms_pdb;
 
 use std::borrow::Cow;
 use std::convert::From;
@@ -89,6 +92,24 @@ impl From<goblin::error::Error> for Error {
     }
 }
 
+impl From<lzma_rs::error::Error> for Error {
+    fn from(e: lzma_rs::error::Error) -> Error {
+        Error(Cow::Owned(format!("LZMA error: {}", e)))
+    }
+}
+
+impl From<minidump::Error> for Error {
+    fn from(e: minidump::Error) -> Error {
+        Error(Cow::Owned(format!("Minidump error: {}", e)))
+    }
+}
+
+impl From<ms_pdb::Error> for Error {
+    fn from(e: ms_pdb::Error) -> Error {
+        Error(Cow::Owned(format!("PDB error: {}", e)))
+    }
+}
+
 impl From<serde_cbor::Error> for Error {
     fn from(e: serde_cbor::Error) -> Error {
         Error(Cow::Owned(format!("Serde error: {}", e)))
@@ -0,0 +1,398 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Arithmetic on constants larger than 64 bits.
+//!
+//! `il::execute` hands all operations with an operand or result larger than 64 bits to this
+//! module. Values are stored as vectors of 64 bit words, least significant word first, and are
+//! always truncated to their size. Signed operations use two's complement.
+
+use crate::{Operation, Rvalue};
+use std::cmp::{self, Ordering};
+
+#[derive(Clone,Debug,PartialEq,Eq)]
+struct Bits {
+    words: Vec<u64>,
+    size: usize,
+}
+
+impl Bits {
+    fn zero(size: usize) -> Bits {
+        Bits { words: vec![0; (size + 63) / 64], size: size }
+    }
+
+    fn new(mut words: Vec<u64>, size: usize) -> Bits {
+        words.resize((size + 63) / 64, 0);
+
+        let mut ret = Bits { words: words, size: size };
+        ret.truncate();
+        ret
+    }
+
+    fn from_rvalue(rv: &Rvalue) -> Option<Bits> {
+        match rv {
+            &Rvalue::Constant { value, size } => Some(Bits::new(vec![value], size)),
+            &Rvalue::Wide { ref words, size } => Some(Bits::new(words.clone(), size)),
+            _ => None,
+        }
+    }
+
+    fn into_rvalue(self) -> Rvalue {
+        Rvalue::new_wide(self.words, self.size)
+    }
+
+    fn flag(b: bool) -> Rvalue {
+        Rvalue::Constant { value: if b { 1 } else { 0 }, size: 1 }
+    }
+
+    // clears all bits above `size`
+    fn truncate(&mut self) {
+        if self.size % 64 != 0 {
+            if let Some(w) = self.words.last_mut() {
+                *w &= (1u64 << (self.size % 64)) - 1;
+            }
+        }
+    }
+
+    fn bit(&self, i: usize) -> bool {
+        i < self.size && (self.words[i / 64] >> (i % 64)) & 1 == 1
+    }
+
+    fn is_zero(&self) -> bool {
+        self.words.iter().all(|&w| w == 0)
+    }
+
+    fn is_negative(&self) -> bool {
+        self.size > 0 && self.bit(self.size - 1)
+    }
+
+    fn resize(&self, size: usize) -> Bits {
+        Bits::new(self.words.clone(), size)
+    }
+
+    fn sign_extend(&self, size: usize) -> Bits {
+        let mut ret = self.resize(size);
+
+        if self.is_negative() {
+            for i in self.size..size {
+                ret.words[i / 64] |= 1 << (i % 64);
+            }
+        }
+
+        ret
+    }
+
+    fn zip<F: Fn(u64, u64) -> u64>(&self, other: &Bits, f: F) -> Bits {
+        let other = other.resize(self.size);
+        let words = self.words.iter().zip(other.words.iter()).map(|(&a, &b)| f(a, b)).collect();
+
+        Bits::new(words, self.size)
+    }
+
+    fn not(&self) -> Bits {
+        Bits::new(self.words.iter().map(|&w| !w).collect(), self.size)
+    }
+
+    fn add(&self, other: &Bits) -> Bits {
+        let other = other.resize(self.size);
+        let mut carry = 0u64;
+        let mut words = Vec::with_capacity(self.words.len());
+
+        for (&a, &b) in self.words.iter().zip(other.words.iter()) {
+            let (s1, c1) = a.overflowing_add(b);
+            let (s2, c2) = s1.overflowing_add(carry);
+
+            words.push(s2);
+            carry = (c1 as u64) + (c2 as u64);
+        }
+
+        Bits::new(words, self.size)
+    }
+
+    fn neg(&self) -> Bits {
+        self.not().add(&Bits::new(vec![1], self.size))
+    }
+
+    fn sub(&self, other: &Bits) -> Bits {
+        self.add(&other.resize(self.size).neg())
+    }
+
+    fn mul(&self, other: &Bits) -> Bits {
+        let other = other.resize(self.size);
+        let n = self.words.len();
+        let mut words = vec![0u64; n];
+
+        for i in 0..n {
+            let mut carry = 0u128;
+
+            for j in 0..(n - i) {
+                let t = (self.words[i] as u128) * (other.words[j] as u128) + (words[i + j] as u128) + carry;
+
+                words[i + j] = t as u64;
+                carry = t >> 64;
+            }
+        }
+
+        Bits::new(words, self.size)
+    }
+
+    fn shl(&self, n: usize) -> Bits {
+        let mut ret = Bits::zero(self.size);
+
+        for i in n..self.size {
+            if self.bit(i - n) {
+                ret.words[i / 64] |= 1 << (i % 64);
+            }
+        }
+
+        ret
+    }
+
+    fn shr(&self, n: usize, fill: bool) -> Bits {
+        let mut ret = Bits::zero(self.size);
+
+        for i in 0..self.size {
+            if (i + n < self.size && self.bit(i + n)) || (i + n >= self.size && fill) {
+                ret.words[i / 64] |= 1 << (i % 64);
+            }
+        }
+
+        ret
+    }
+
+    // shift amount, saturated to `usize::MAX`
+    fn amount(&self) -> usize {
+        if self.words.iter().skip(1).any(|&w| w != 0) || self.words[0] > usize::max_value() as u64 {
+            usize::max_value()
+        } else {
+            self.words[0] as usize
+        }
+    }
+
+    fn cmp_unsigned(&self, other: &Bits) -> Ordering {
+        let other = other.resize(self.size);
+
+        for (a, b) in self.words.iter().rev().zip(other.words.iter().rev()) {
+            if a != b {
+                return a.cmp(b);
+            }
+        }
+
+        Ordering::Equal
+    }
+
+    fn cmp_signed(&self, other: &Bits) -> Ordering {
+        let other = other.resize(self.size);
+
+        match (self.is_negative(), other.is_negative()) {
+            (true, false) => Ordering::Less,
+            (false, true) => Ordering::Greater,
+            _ => self.cmp_unsigned(&other),
+        }
+    }
+
+    // unsigned division, None if `other` is zero
+    fn div_rem(&self, other: &Bits) -> Option<(Bits, Bits)> {
+        let other = other.resize(self.size);
+
+        if other.is_zero() {
+            return None;
+        }
+
+        let mut quot = Bits::zero(self.size);
+        let mut rem = Bits::zero(self.size);
+
+        for i in (0..self.size).rev() {
+            rem = rem.shl(1);
+            if self.bit(i) {
+                rem.words[0] |= 1;
+            }
+            if rem.cmp_unsigned(&other) != Ordering::Less {
+                rem = rem.sub(&other);
+                quot.words[i / 64] |= 1 << (i % 64);
+            }
+        }
+
+        Some((quot, rem))
+    }
+
+    fn abs(&self) -> Bits {
+        if self.is_negative() { self.neg() } else { self.clone() }
+    }
+}
+
+fn is_wide(rv: &Rvalue) -> bool {
+    match rv {
+        &Rvalue::Wide { .. } => true,
+        &Rvalue::Constant { size, .. } => size > 64,
+        _ => false,
+    }
+}
+
+/// Executes `op` if one of its operands or its result is larger than 64 bits and all operands are
+/// constants. Returns None otherwise.
+pub fn execute(op: &Operation<Rvalue>) -> Option<Rvalue> {
+    let wide = match op {
+        &Operation::ZeroExtend(sz, ref a) | &Operation::SignExtend(sz, ref a) => sz > 64 || is_wide(a),
+        _ => op.operands().into_iter().any(is_wide),
+    };
+
+    if !wide {
+        return None;
+    }
+
+    let args = op.operands().into_iter().map(Bits::from_rvalue).collect::<Option<Vec<_>>>()?;
+    let ret = match op {
+        &Operation::Add(..) => args[0].add(&args[1]).into_rvalue(),
+        &Operation::Subtract(..) => args[0].sub(&args[1]).into_rvalue(),
+        &Operation::Multiply(..) => args[0].mul(&args[1]).into_rvalue(),
+        &Operation::DivideUnsigned(..) => {
+            match args[0].div_rem(&args[1]) {
+                Some((q, _)) => q.into_rvalue(),
+                None => Rvalue::Undefined,
+            }
+        }
+        &Operation::DivideSigned(..) => {
+            match args[0].abs().div_rem(&args[1].abs()) {
+                Some((q, _)) if args[0].is_negative() != args[1].resize(args[0].size).is_negative() => q.neg().into_rvalue(),
+                Some((q, _)) => q.into_rvalue(),
+                None => Rvalue::Undefined,
+            }
+        }
+        &Operation::Modulo(..) => {
+            match args[0].div_rem(&args[1]) {
+                Some((_, r)) => r.into_rvalue(),
+                None => Rvalue::Undefined,
+            }
+        }
+        &Operation::ShiftLeft(..) => args[0].shl(cmp::min(args[1].amount(), args[0].size)).into_rvalue(),
+        &Operation::ShiftRightUnsigned(..) => args[0].shr(cmp::min(args[1].amount(), args[0].size), false).into_rvalue(),
+        &Operation::ShiftRightSigned(..) => {
+            let neg = args[0].is_negative();
+            args[0].shr(cmp::min(args[1].amount(), args[0].size), neg).into_rvalue()
+        }
+        &Operation::And(..) => args[0].zip(&args[1], |a, b| a & b).into_rvalue(),
+        &Operation::InclusiveOr(..) => args[0].zip(&args[1], |a, b| a | b).into_rvalue(),
+        &Operation::ExclusiveOr(..) => args[0].zip(&args[1], |a, b| a ^ b).into_rvalue(),
+
+        &Operation::Equal(..) => Bits::flag(args[0].cmp_unsigned(&args[1]) == Ordering::Equal),
+        &Operation::LessOrEqualUnsigned(..) => Bits::flag(args[0].cmp_unsigned(&args[1]) != Ordering::Greater),
+        &Operation::LessOrEqualSigned(..) => Bits::flag(args[0].cmp_signed(&args[1]) != Ordering::Greater),
+        &Operation::LessUnsigned(..) => Bits::flag(args[0].cmp_unsigned(&args[1]) == Ordering::Less),
+        &Operation::LessSigned(..) => Bits::flag(args[0].cmp_signed(&args[1]) == Ordering::Less),
+
+        &Operation::ZeroExtend(sz, _) => args[0].resize(sz).into_rvalue(),
+        &Operation::SignExtend(sz, _) => args[0].sign_extend(sz).into_rvalue(),
+        &Operation::Move(_) => args[0].clone().into_rvalue(),
+        &Operation::Select(off, _, _) => {
+            let (a, b) = (&args[0], &args[1]);
+
+            if off + b.size > a.size {
+                Rvalue::Undefined
+            } else {
+                let mut ret = a.clone();
+
+                for i in 0..b.size {
+                    let j = off + i;
+
+                    ret.words[j / 64] &= !(1 << (j % 64));
+                    if b.bit(i) {
+                        ret.words[j / 64] |= 1 << (j % 64);
+                    }
+                }
+
+                ret.into_rvalue()
+            }
+        }
+
+        &Operation::Call(_) |
+        &Operation::Initialize(..) |
+        &Operation::Load(..) |
+        &Operation::Store(..) |
+        &Operation::Phi(_) => return None,
+    };
+
+    Some(ret)
+}
+
+/// Returns `size` bits of `rv` starting at bit `offset`. Returns None if `rv` is not a constant.
+pub fn extract(rv: &Rvalue, size: usize, offset: usize) -> Option<Rvalue> {
+    let bits = Bits::from_rvalue(rv)?;
+    Some(bits.shr(cmp::min(offset, bits.size), false).resize(size).into_rvalue())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::execute;
+
+    fn wide(words: &[u64], size: usize) -> Rvalue {
+        Rvalue::new_wide(words.to_vec(), size)
+    }
+
+    #[test]
+    fn arithmetic() {
+        let max = wide(&[u64::max_value(), u64::max_value()], 128);
+        let one = Rvalue::Constant { value: 1, size: 128 };
+        let two64 = wide(&[0, 1], 128);
+
+        assert_eq!(execute(Operation::Add(wide(&[u64::max_value()], 128), one.clone())), two64);
+        assert_eq!(execute(Operation::Add(max.clone(), one.clone())), Rvalue::Constant { value: 0, size: 128 });
+        assert_eq!(execute(Operation::Subtract(Rvalue::Constant { value: 0, size: 128 }, one.clone())), max);
+        assert_eq!(execute(Operation::Multiply(two64.clone(), two64.clone())), Rvalue::Constant { value: 0, size: 128 });
+        assert_eq!(execute(Operation::Multiply(wide(&[u64::max_value()], 128), wide(&[u64::max_value()], 128))), wide(&[1, u64::max_value() - 1], 128));
+        assert_eq!(execute(Operation::DivideUnsigned(wide(&[6, 6], 128), Rvalue::Constant { value: 3, size: 128 })), wide(&[2, 2], 128));
+        assert_eq!(execute(Operation::Modulo(wide(&[7, 1], 128), Rvalue::Constant { value: 0x10, size: 128 })), Rvalue::Constant { value: 7, size: 128 });
+        assert_eq!(execute(Operation::DivideUnsigned(max.clone(), Rvalue::Constant { value: 0, size: 128 })), Rvalue::Undefined);
+        assert_eq!(execute(Operation::DivideSigned(max.clone(), Rvalue::Constant { value: 1, size: 128 })), max);
+        assert_eq!(
+            execute(Operation::DivideSigned(wide(&[u64::max_value() - 5, u64::max_value()], 128), Rvalue::Constant { value: 2, size: 128 })),
+            wide(&[u64::max_value() - 2, u64::max_value()], 128)
+        );
+    }
+
+    #[test]
+    fn bitwise() {
+        let a = wide(&[0xff00, 0xf0], 128);
+        let b = wide(&[0x0ff0, 0xff], 128);
+
+        assert_eq!(execute(Operation::And(a.clone(), b.clone())), wide(&[0x0f00, 0xf0], 128));
+        assert_eq!(execute(Operation::InclusiveOr(a.clone(), b.clone())), wide(&[0xfff0, 0xff], 128));
+        assert_eq!(execute(Operation::ExclusiveOr(a.clone(), a.clone())), Rvalue::Constant { value: 0, size: 128 });
+        assert_eq!(execute(Operation::ShiftLeft(Rvalue::Constant { value: 1, size: 128 }, Rvalue::Constant { value: 127, size: 128 })), wide(&[0, 1 << 63], 128));
+        assert_eq!(execute(Operation::ShiftRightUnsigned(wide(&[0, 1], 128), Rvalue::Constant { value: 64, size: 128 })), Rvalue::Constant { value: 1, size: 128 });
+        assert_eq!(execute(Operation::ShiftRightSigned(wide(&[0, 1 << 63], 128), Rvalue::Constant { value: 200, size: 128 })), wide(&[u64::max_value(), u64::max_value()], 128));
+        assert_eq!(execute(Operation::ShiftLeft(wide(&[1, 1], 128), wide(&[0, 1], 128))), Rvalue::Constant { value: 0, size: 128 });
+    }
+
+    #[test]
+    fn compare_and_extend() {
+        let neg = wide(&[u64::max_value(), u64::max_value()], 128);
+        let pos = wide(&[0, 1], 128);
+
+        assert_eq!(execute(Operation::LessUnsigned(pos.clone(), neg.clone())), Rvalue::new_bit(1));
+        assert_eq!(execute(Operation::LessSigned(pos.clone(), neg.clone())), Rvalue::new_bit(0));
+        assert_eq!(execute(Operation::LessOrEqualSigned(neg.clone(), neg.clone())), Rvalue::new_bit(1));
+        assert_eq!(execute(Operation::Equal(pos.clone(), neg.clone())), Rvalue::new_bit(0));
+        assert_eq!(execute(Operation::SignExtend(128, Rvalue::new_u8(0x80))), wide(&[u64::max_value() - 0x7f, u64::max_value()], 128));
+        assert_eq!(execute(Operation::ZeroExtend(128, Rvalue::new_u8(0x80))), Rvalue::Constant { value: 0x80, size: 128 });
+        assert_eq!(execute(Operation::ZeroExtend(32, pos.clone())), Rvalue::new_u32(0));
+        assert_eq!(execute(Operation::Select(64, Rvalue::Constant { value: 0, size: 128 }, Rvalue::new_u8(0xff))), wide(&[0, 0xff], 128));
+        assert_eq!(extract(&pos, 8, 60), Some(Rvalue::new_u8(0x10)));
+    }
+}
@@ -17,9 +17,18 @@
  */
 
 extern crate panopticon_core;
+extern crate panopticon_graph_algos;
+extern crate panopticon_amd64;
+extern crate panopticon_avr;
+extern crate panopticon_mos6502;
+extern crate tempdir;
 
-use panopticon_core::loader;
+use panopticon_core::{Bound, CallTarget, DebugVariable, Function, FunctionKind, InlinedSubroutine, Machine, OpaqueLayer, Program, Relocation, Rvalue, loader};
+use panopticon_graph_algos::VertexListGraphTrait;
+use std::fs::{self, File};
+use std::io::Read;
 use std::path::Path;
+use tempdir::TempDir;
 
 #[test]
 fn elf_load_static() {
@@ -27,6 +36,11 @@ fn elf_load_static() {
         Ok((proj, _)) => {
             println!("{:?}", proj);
             assert_eq!(proj.imports.len(), 0);
+            assert_eq!(proj.region().find_section(".text").map(|s| s.bound.clone()), Some(Bound::new(0x4003a0, 0x4003a0 + 0x85e14)));
+            assert_eq!(proj.code[0].symbol_extent(0x4009be), Some(Bound::new(0x4009be, 0x4009be + 21)));
+            assert!(proj.region().is_executable(0x4009be));
+            assert!(!proj.region().is_executable(0x6b0080));
+            assert!(proj.region().segment_at(0x6b0080).unwrap().is_writable());
         }
         Err(error) => {
             println!("{:?}", error);
@@ -35,6 +49,27 @@ fn elf_load_static() {
     }
 }
 
+#[test]
+fn elf_load_mapped() {
+    let (proj, _) = loader::load(Path::new("../test-data/static")).unwrap();
+    let mut bytes = vec![];
+    let mut mapped = 0;
+
+    File::open("../test-data/static").unwrap().read_to_end(&mut bytes).unwrap();
+
+    for &(_, ref layer) in proj.region().stack().iter() {
+        if let Some(&OpaqueLayer::Mapped(ref m)) = layer.as_opaque() {
+            let start = m.offset() as usize;
+
+            assert_eq!(m.as_slice(), &bytes[start..start + m.len() as usize]);
+            mapped += 1;
+        }
+    }
+
+    assert_eq!(mapped, proj.file_mappings.len());
+    assert!(mapped > 0);
+}
+
 #[test]
 fn elf_load_dynamic() {
     match loader::load(Path::new("../test-data/libfoo.so")) {
@@ -71,6 +106,11 @@ fn mach_load_exe() {
         Ok((proj, _)) => {
             println!("{:?}", &proj);
             assert_eq!(proj.imports.len(), 2);
+            assert_eq!(proj.imports.get(&0x100001010).map(|s| s.as_str()), Some("_printf"));
+            assert_eq!(proj.region().segment_at(0x100001010).and_then(|s| s.name.clone()), Some("__DATA".to_string()));
+            assert!(proj.region().is_executable(0x100000f00));
+            assert!(!proj.region().is_executable(0x100001010));
+            assert!(!proj.region().is_executable(0x1000));
         }
         Err(error) => {
             println!("{:?}", error);
@@ -79,6 +119,32 @@ fn mach_load_exe() {
     }
 }
 
+#[test]
+fn mach_function_starts_and_stubs() {
+    let (proj, _) = loader::load(Path::new("../test-data/libbeef.dylib")).unwrap();
+    let starts = proj.code[0]
+        .call_graph
+        .vertex_labels()
+        .filter_map(|ct| match ct {
+            &CallTarget::Todo(Rvalue::Constant { value, .. }, _, _) => Some(value),
+            _ => None,
+        })
+        .collect::<Vec<u64>>();
+
+    assert!(starts.contains(&0xf20));
+    assert!(starts.contains(&0xf60));
+    assert_eq!(starts.iter().filter(|&&a| a == 0xf20).count(), 1);
+
+    // main calls printf through the stub at 0x100000f74
+    let (proj, _) = loader::load(Path::new("../test-data/deadbeef.mach")).unwrap();
+    let stub = proj.code[0].call_graph.vertex_labels().any(|ct| match ct {
+        &CallTarget::Todo(Rvalue::Constant { value: 0x100000f74, .. }, _, _) => true,
+        _ => false,
+    });
+
+    assert!(stub);
+}
+
 #[test]
 fn mach_load_bytes() {
     use std::fs::File;
@@ -101,15 +167,17 @@ fn mach_load_bytes() {
     }
 }
 
-// TODO: add imports into the PE loader
-
 #[test]
 fn load_pe32() {
     let project = loader::load(Path::new("../test-data/test.exe"));
     match project {
-        Ok((proj, _)) => {
+        Ok((proj, machine)) => {
             println!("{:?}", proj);
-            assert_eq!(proj.imports.len(), 0);
+            assert_eq!(machine, Machine::Ia32);
+            assert_eq!(proj.imports.len(), 90);
+            assert_eq!(proj.region().segment_at(0x411000).and_then(|s| s.name.clone()), Some(".text".to_string()));
+            assert!(proj.region().is_executable(0x411000));
+            assert!(!proj.region().is_executable(0x41f000));
         }
         Err(error) => {
             println!("{:?}", error);
@@ -118,13 +186,155 @@ fn load_pe32() {
     }
 }
 
+#[test]
+fn load_pe32_plus() {
+    // hello64.exe is a minimal PE32+ image: main at 0x140001000 calls a helper at 0x140001040, both
+    // listed in .pdata, and loads the address of a string using a DIR64 relocated immediate.
+    let (proj, machine) = loader::load(Path::new("../test-data/hello64.exe")).unwrap();
+    let prog = &proj.code[0];
+    let entries = prog.call_graph
+        .vertex_labels()
+        .filter_map(
+            |ct| match ct {
+                &CallTarget::Todo(Rvalue::Constant { value, .. }, _, _) => Some(value),
+                _ => None,
+            }
+        )
+        .collect::<Vec<_>>();
+
+    assert_eq!(machine, Machine::Amd64);
+    assert_eq!(todos(prog), vec![(0x140001000, "hello64.exe".to_string())]);
+    assert!(entries.contains(&0x140001040));
+    assert_eq!(proj.imports.len(), 2);
+    assert_eq!(proj.imports.get(&0x140002060).map(|x| x.as_str()), Some("KERNEL32.dll!ExitProcess"));
+    assert_eq!(proj.imports.get(&0x140002068).map(|x| x.as_str()), Some("KERNEL32.dll!GetStdHandle"));
+    assert_eq!(proj.relocations, vec![Relocation { address: 0x14000100b, size: 8 }]);
+    assert!(proj.region().is_executable(0x140001000));
+    assert!(!proj.region().is_executable(0x140002000));
+}
+
+#[test]
+fn load_pe32_pdb() {
+    // HelloWorld.pdb is a minimal PDB carrying test.exe's CodeView GUID and age
+    let (proj, _) = loader::load(Path::new("../test-data/test.exe")).unwrap();
+    let prog = &proj.code[0];
+
+    assert!(todos(prog).contains(&(0x416a00, "mainCRTStartup".to_string())));
+    assert_eq!(prog.symbol_extent(0x416a00), Some(Bound::new(0x416a00, 0x416a0f)));
+    // the ILT thunk's public symbol shares its address with the entry point
+    assert!(!todos(prog).iter().any(|&(_, ref name)| name.starts_with("@ILT")));
+    // data symbols are skipped
+    assert!(!todos(prog).iter().any(|&(_, ref name)| name == "___xi_a"));
+}
+
+#[test]
+fn load_pe32_rebase() {
+    let (mut proj, _) = loader::load(Path::new("../test-data/test.exe")).unwrap();
+
+    assert_eq!(proj.relocations.len(), 652);
+    assert_eq!(proj.relocations[0], Relocation { address: 0x41195f, size: 4 });
+
+    proj.rebase(0x1000_0000).unwrap();
+
+    // the relocated pointer now points into the moved image
+    let cell = proj.region().iter().seek(0x1041195f).take(4).map(|x| x.unwrap()).collect::<Vec<u8>>();
+    assert_eq!(cell, vec![0x24, 0xf3, 0x41, 0x10]);
+    assert_eq!(proj.region().iter().seek(0x41195f).next(), Some(None));
+
+    assert!(todos(&proj.code[0]).contains(&(0x104112c6, "test.exe".to_string())));
+    assert!(todos(&proj.code[0]).contains(&(0x10416a00, "mainCRTStartup".to_string())));
+    assert!(proj.comments.contains_key(&("base".to_string(), 0x104112c6)));
+    assert_eq!(proj.imports.len(), 90);
+    assert!(proj.imports.keys().all(|&a| a >= 0x10400000));
+}
+
+#[test]
+fn pe32_rebase_functions() {
+    let (mut proj, _) = loader::load(Path::new("../test-data/test.exe")).unwrap();
+    let func = Function::new::<panopticon_amd64::Amd64>(0x411940, proj.region(), None, panopticon_amd64::Mode::Protected).unwrap();
+
+    proj.code[0].call_graph.add_vertex(CallTarget::Concrete(func));
+    // the pointer loaded at 0x41195e is no longer relocated and must stay as it is
+    proj.relocations.retain(|r| r.address != 0x41195f);
+    proj.rebase(0x1000_0000).unwrap();
+
+    let func = proj.code[0].functions().find(|f| f.start() == 0x10411940).unwrap();
+    let constants = |addr: u64| {
+        func.basic_blocks()
+            .flat_map(|bb| bb.mnemonics.iter())
+            .filter(|m| m.area.start == addr)
+            .flat_map(|m| m.operands.iter())
+            .filter_map(
+                |op| match op {
+                    &Rvalue::Constant { value, .. } => Some(value),
+                    _ => None,
+                }
+            )
+            .collect::<Vec<u64>>()
+    };
+
+    assert_eq!(constants(0x1041195e), vec![0x41f324]);
+    assert_eq!(constants(0x10411963), vec![0x104113e8]);
+    assert_eq!(constants(0x10411968), vec![0x1041ad80]);
+}
+
 #[test]
 fn load_pe32_dll() {
     let project = loader::load(Path::new("../test-data/libbeef.dll"));
     match project {
         Ok((proj, _)) => {
             println!("{:?}", proj);
-            assert_eq!(proj.imports.len(), 0);
+            assert_eq!(proj.imports.len(), 12);
+            assert_eq!(proj.imports.get(&0x61a48074).map(|x| x.as_str()), Some("cygwin1.dll!__cxa_atexit"));
+            assert_eq!(proj.imports.get(&0x61a480a0).map(|x| x.as_str()), Some("KERNEL32.dll!GetModuleHandleA"));
+            assert_eq!(proj.code[0].imports.len(), 12);
+        }
+        Err(error) => {
+            println!("{:?}", error);
+            assert!(false);
+        }
+    }
+}
+
+fn fat_deadbeef() -> Vec<u8> {
+    use std::fs::File;
+    use std::io::Read;
+
+    let mut thin = Vec::new();
+    let mut fd = File::open(Path::new("../test-data/deadbeef.mach")).unwrap();
+    fd.read_to_end(&mut thin).unwrap();
+
+    let be = |x: u32| vec![(x >> 24) as u8, (x >> 16) as u8, (x >> 8) as u8, x as u8];
+    let mut fat = Vec::new();
+
+    // fat_header + one fat_arch for x86_64, slice aligned to 4k
+    fat.extend(be(0xcafebabe));
+    fat.extend(be(1));
+    fat.extend(be(0x01000007));
+    fat.extend(be(0x80000003));
+    fat.extend(be(0x1000));
+    fat.extend(be(thin.len() as u32));
+    fat.extend(be(12));
+    fat.resize(0x1000, 0);
+    fat.extend(thin);
+    fat
+}
+
+#[test]
+fn mach_fat_arches() {
+    let bytes = fat_deadbeef();
+    let arches = loader::fat_arches(&bytes).unwrap();
+
+    assert_eq!(arches.len(), 1);
+    assert_eq!(arches[0].cputype, 0x01000007);
+    assert_eq!(arches[0].machine, Some(Machine::Amd64));
+    assert_eq!(arches[0].offset, 0x1000);
+
+    let slice = &bytes[arches[0].offset..arches[0].offset + arches[0].size];
+    match loader::load_mach(slice, 0, "deadbeef".to_owned()) {
+        Ok((proj, machine)) => {
+            assert_eq!(machine, Machine::Amd64);
+            assert_eq!(proj.imports.len(), 2);
         }
         Err(error) => {
             println!("{:?}", error);
@@ -132,3 +342,410 @@ fn load_pe32_dll() {
         }
     }
 }
+
+#[test]
+fn mach_fat_export() {
+    use std::fs::File;
+    use std::io::{Read, Write};
+
+    let bytes = fat_deadbeef();
+    let tmpdir = TempDir::new("test-panop").unwrap();
+    let path = tmpdir.path().join("deadbeef.fat");
+    let target = tmpdir.path().join("patched.fat");
+
+    File::create(&path).unwrap().write_all(&bytes).unwrap();
+
+    let (mut proj, _) = loader::load_arch(&path, Machine::Amd64).unwrap();
+
+    // file mappings are relative to the fat file, not to the x86_64 slice
+    assert!(proj.file_mappings.iter().all(|m| m.offset >= 0x1000));
+
+    proj.patch::<panopticon_amd64::Amd64>(0x100000f74, &[0x90], panopticon_amd64::Mode::Long).unwrap();
+    proj.export(&path, &target).unwrap();
+
+    let mut patched = vec![];
+    File::open(&target).unwrap().read_to_end(&mut patched).unwrap();
+
+    let mut expected = bytes.clone();
+    expected[0x1000 + 0xf74] = 0x90;
+    assert_eq!(patched, expected);
+}
+
+#[test]
+fn mach_stub_lazy_pointer() {
+    let mut bytes = fat_deadbeef();
+
+    // the pointer comes from the indirect symbol table, not from decoding the stub
+    bytes[0x1000 + 0xf74..0x1000 + 0xf7a].copy_from_slice(&[0x90; 6]);
+
+    let (proj, _) = loader::load_mach(&bytes, 0x1000, "deadbeef".to_owned()).unwrap();
+
+    assert_eq!(proj.code[0].stubs.get(&0x100000f74), Some(&("_printf".to_string(), 0x100001010)));
+}
+
+#[test]
+fn mach_stub_kind() {
+    let bytes = fat_deadbeef();
+    let arches = loader::fat_arches(&bytes).unwrap();
+    let slice = &bytes[arches[0].offset..arches[0].offset + arches[0].size];
+    let (mut proj, _) = loader::load_mach(slice, 0, "deadbeef".to_owned()).unwrap();
+
+    // jmp [rip+0x96] through the lazy symbol pointer of printf
+    assert_eq!(proj.code[0].stubs.get(&0x100000f74), Some(&("_printf".to_string(), 0x100001010)));
+
+    let func = Function::new::<panopticon_amd64::Amd64>(0x100000f74, proj.region(), None, panopticon_amd64::Mode::Long).unwrap();
+    proj.code[0].insert(func);
+
+    let func = proj.code[0].find_function_by(|f| f.start() == 0x100000f74).unwrap();
+    match func.kind() {
+        &FunctionKind::Stub { ref name, plt_address } => {
+            assert_eq!(name, "_printf");
+            assert_eq!(plt_address, 0x100001010);
+        }
+        kind => panic!("stub is {:?}", kind),
+    }
+    assert_eq!(func.name, "_printf@plt");
+}
+
+#[test]
+fn machine_from_str() {
+    assert_eq!("amd64".parse::<Machine>().ok(), Some(Machine::Amd64));
+    assert_eq!("x86_64".parse::<Machine>().ok(), Some(Machine::Amd64));
+    assert_eq!("i386".parse::<Machine>().ok(), Some(Machine::Ia32));
+    assert_eq!("AVR".parse::<Machine>().ok(), Some(Machine::Avr));
+    assert_eq!("6502".parse::<Machine>().ok(), Some(Machine::Mos6502));
+    assert!("sparc".parse::<Machine>().is_err());
+}
+
+fn todos(prog: &Program) -> Vec<(u64, String)> {
+    let mut ret = prog.call_graph
+        .vertex_labels()
+        .filter_map(
+            |ct| match ct {
+                &CallTarget::Todo(Rvalue::Constant { value, .. }, Some(ref name), _) => Some((value, name.clone())),
+                _ => None,
+            }
+        )
+        .collect::<Vec<_>>();
+    ret.sort();
+    ret
+}
+
+#[test]
+fn archive_load() {
+    match loader::load(Path::new("../test-data/libfoobar.a")) {
+        Ok((proj, machine)) => {
+            assert_eq!(machine, Machine::Amd64);
+            assert_eq!(proj.name, "libfoobar.a");
+            assert_eq!(proj.code.len(), 2);
+            assert_eq!(proj.code[0].name, "foo.o");
+            assert_eq!(proj.code[1].name, "bar.o");
+            assert_eq!(todos(&proj.code[0]), vec![(0x1000, "foo_add".to_string()), (0x1004, "foo_mul".to_string())]);
+            assert_eq!(todos(&proj.code[1]), vec![(0x2000, "bar".to_string())]);
+        }
+        Err(error) => {
+            println!("{:?}", error);
+            assert!(false);
+        }
+    }
+}
+
+#[test]
+fn elf_load_relocatable() {
+    match loader::load(Path::new("../test-data/bar.o")) {
+        Ok((proj, machine)) => {
+            assert_eq!(machine, Machine::Amd64);
+            assert_eq!(proj.code.len(), 1);
+            assert_eq!(todos(&proj.code[0]), vec![(0x1000, "bar".to_string())]);
+            assert_eq!(proj.imports.len(), 2);
+            assert_eq!(proj.imports.get(&0x1030).map(|x| x.as_str()), Some("printf"));
+            assert_eq!(proj.imports.get(&0x1038).map(|x| x.as_str()), Some("foo_add"));
+            assert_eq!(symbolic(&proj.code[0]), vec!["foo_add".to_string(), "printf".to_string()]);
+
+            let text = proj.region().iter().seek(0x1000).take(0x22).map(|x| x.unwrap()).collect::<Vec<u8>>();
+
+            // mov edi, .rodata.str1.1
+            assert_eq!(&text[0x06..0x0a], &[0x22, 0x10, 0x00, 0x00]);
+            // call printf
+            assert_eq!(&text[0x10..0x14], &[0x1c, 0x00, 0x00, 0x00]);
+            // call foo_add
+            assert_eq!(&text[0x1c..0x20], &[0x18, 0x00, 0x00, 0x00]);
+
+            let sec = proj.region().section_at(0x1010).unwrap();
+            assert_eq!(sec.name, ".text");
+            assert_eq!(sec.bound, Bound::new(0x1000, 0x1022));
+            assert!(sec.is_executable());
+            assert!(!sec.is_writable());
+            assert_eq!(proj.region().find_section(".rodata.str1.1").map(|s| s.bound.start), Some(0x1022));
+            assert_eq!(proj.code[0].symbol_extent(0x1000), Some(Bound::new(0x1000, 0x1022)));
+        }
+        Err(error) => {
+            println!("{:?}", error);
+            assert!(false);
+        }
+    }
+}
+
+fn symbolic(prog: &Program) -> Vec<String> {
+    let mut ret = prog.call_graph
+        .vertex_labels()
+        .filter_map(
+            |ct| match ct {
+                &CallTarget::Symbolic(ref name, _) => Some(name.clone()),
+                _ => None,
+            }
+        )
+        .collect::<Vec<_>>();
+    ret.sort();
+    ret
+}
+
+#[test]
+fn elf_load_relocatable_rel() {
+    // rel.o uses SHT_REL with implicit addends: `call ext_func` in .text and
+    // `.quad ext_data + 0x123456789` in .data
+    let (proj, _) = loader::load(Path::new("../test-data/rel.o")).unwrap();
+
+    assert_eq!(proj.imports.get(&0x1010).map(|x| x.as_str()), Some("ext_func"));
+    assert_eq!(proj.imports.get(&0x1018).map(|x| x.as_str()), Some("ext_data"));
+    assert_eq!(symbolic(&proj.code[0]), vec!["ext_data".to_string(), "ext_func".to_string()]);
+
+    let text = proj.region().iter().seek(0x1000).take(0xe).map(|x| x.unwrap()).collect::<Vec<u8>>();
+
+    // call ext_func
+    assert_eq!(&text[0x01..0x05], &[0x0b, 0x00, 0x00, 0x00]);
+    // the 64 bit addend does not fit into 32 bits
+    assert_eq!(&text[0x06..0x0e], &[0xa1, 0x77, 0x45, 0x23, 0x01, 0x00, 0x00, 0x00]);
+}
+
+#[test]
+fn raw_load_com() {
+    let proj = loader::load_raw(Path::new("../test-data/ia32.com"), Machine::Ia32, 0x100, &[0x100]).unwrap();
+
+    assert_eq!(proj.name, "ia32.com");
+    assert_eq!(proj.code.len(), 1);
+    assert_eq!(proj.region().size(), 0x1_0000_0000);
+    assert!(proj.region().iter().seek(0xff).next().unwrap().is_none());
+    assert!(proj.region().iter().seek(0x100).next().unwrap().is_some());
+    assert_eq!(proj.code[0].call_graph.num_vertices(), 1);
+}
+
+#[test]
+fn raw_load_avr_vectors() {
+    let mcu = panopticon_avr::Mcu::atmega103();
+    let mut proj = loader::load_raw(Path::new("../test-data/avr-all-opcodes.bin"), Machine::Avr, 0, &[]).unwrap();
+
+    assert!(todos(&proj.code[0]).is_empty());
+    loader::seed_entry_points::<panopticon_avr::Avr>(&mut proj, &mcu).unwrap();
+
+    let entries = todos(&proj.code[0]);
+    assert_eq!(entries.len(), mcu.int_vec.len());
+    assert_eq!(entries[0], (0, "RESET".to_string()));
+    assert_eq!(proj.comments.get(&("base".to_string(), 0)).map(|x| x.as_str()), Some("MCU Reset Interrupt"));
+}
+
+#[test]
+fn ihex_parse() {
+    use panopticon_core::Region;
+
+    let text = ":10000000000102030405060708090A0B0C0D0E0F78
+:10001000101112131415161718191A1B1C1D1E1F68
+:020000040001F9
+:02002000AABB79
+:0400000500000010E7
+:00000001FF
+";
+    let mut reg = Region::undefined("Flash".to_string(), 0x2_0000);
+    let entry = loader::parse_ihex(text, &mut reg).unwrap();
+
+    assert_eq!(entry, Some(0x10));
+    // both records at 0 are merged, the one at 0x10020 stands alone
+    assert_eq!(reg.stack().len(), 3);
+    assert_eq!(reg.iter().seek(0x1f).next(), Some(Some(0x1f)));
+    assert_eq!(reg.iter().seek(0x20).next(), Some(None));
+    assert_eq!(reg.iter().seek(0x10020).take(2).collect::<Vec<_>>(), vec![Some(0xaa), Some(0xbb)]);
+}
+
+#[test]
+fn ihex_bad_checksum() {
+    use panopticon_core::Region;
+
+    let text = ":10000000000102030405060708090A0B0C0D0E0F78\n:10001000101112131415161718191A1B1C1D1E1F69\n";
+    let mut reg = Region::undefined("Flash".to_string(), 0x2_0000);
+    let err = loader::parse_ihex(text, &mut reg).err().unwrap();
+
+    assert_eq!(format!("{}", err), "2: checksum mismatch");
+}
+
+#[test]
+fn srec_parse() {
+    use panopticon_core::Region;
+
+    let text = "S0060000686472BB
+S10B01000001020304050607D7
+S10B010808090A0B0C0D0E0F8F
+S205010000AA4F
+S9030100FB
+";
+    let mut reg = Region::undefined("RAM".to_string(), 0x2_0000);
+    let entry = loader::parse_srec(text, &mut reg).unwrap();
+
+    assert_eq!(entry, Some(0x100));
+    assert_eq!(reg.stack().len(), 3);
+    assert_eq!(reg.iter().seek(0x100).take(16).collect::<Vec<_>>(), (0..16).map(Some).collect::<Vec<_>>());
+    assert_eq!(reg.iter().seek(0x10000).next(), Some(Some(0xaa)));
+    assert!(loader::parse_srec("S10B01000001020304050607D8\n", &mut reg).is_err());
+    assert!(loader::parse_srec("S\u{e9}0B0100\n", &mut reg).is_err());
+}
+
+#[test]
+fn c64_load_prg() {
+    let (proj, machine) = loader::load(Path::new("../test-data/hello.prg")).unwrap();
+
+    assert_eq!(machine, Machine::Mos6502);
+    assert_eq!(proj.region().size(), 0x1_0000);
+    assert_eq!(proj.region().iter().seek(0x801).next(), Some(Some(0x0b)));
+    // entry point from the BASIC SYS line
+    assert_eq!(todos(&proj.code[0]), vec![(2061, "hello.prg".to_string())]);
+}
+
+#[test]
+fn nes_load_ines() {
+    let (mut proj, machine) = loader::load(Path::new("../test-data/hello.nes")).unwrap();
+
+    assert_eq!(machine, Machine::Mos6502);
+    assert!(todos(&proj.code[0]).is_empty());
+    loader::seed_entry_points::<panopticon_mos6502::Mos>(&mut proj, &panopticon_mos6502::Variant::mos6502()).unwrap();
+    // single bank is mirrored
+    assert_eq!(proj.region().iter().seek(0x8000).next(), Some(Some(0x78)));
+    assert_eq!(proj.region().iter().seek(0xc000).next(), Some(Some(0x78)));
+    assert_eq!(proj.comments.get(&("base".to_string(), 0xc000)).map(|x| x.as_str()), Some("Reset routine"));
+    assert_eq!(
+        todos(&proj.code[0]),
+        vec![
+            (0xc000, "RESET".to_string()),
+            (0xc010, "NMI".to_string()),
+            (0xc020, "IRQ/BRK".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn atari_load_xex() {
+    let (proj, machine) = loader::load(Path::new("../test-data/hello.xex")).unwrap();
+
+    assert_eq!(machine, Machine::Mos6502);
+    assert_eq!(proj.region().iter().seek(0x2000).next(), Some(Some(0xa9)));
+    assert_eq!(todos(&proj.code[0]), vec![(0x2000, "RUNAD".to_string())]);
+}
+
+#[test]
+fn mos6502_detect_by_magic() {
+    let tmpdir = TempDir::new("test-panop").unwrap();
+    let xex = tmpdir.path().join("hello.bin");
+    let prg = tmpdir.path().join("hello.dat");
+
+    fs::copy("../test-data/hello.xex", &xex).unwrap();
+    fs::copy("../test-data/hello.prg", &prg).unwrap();
+
+    // Atari executables start with $FFFF
+    let (proj, machine) = loader::load(&xex).unwrap();
+    assert_eq!(machine, Machine::Mos6502);
+    assert_eq!(todos(&proj.code[0]), vec![(0x2000, "RUNAD".to_string())]);
+
+    // C64 programs have no magic
+    assert!(loader::load(&prg).is_err());
+}
+
+#[test]
+fn elf_load_dwarf() {
+    let (proj, machine) = loader::load(Path::new("../test-data/dwarf")).unwrap();
+    let prog = &proj.code[0];
+
+    assert_eq!(machine, Machine::Amd64);
+    // add and sum_squares have no symbol table entries
+    assert!(todos(prog).contains(&(0x401000, "add".to_string())));
+    assert!(todos(prog).contains(&(0x40101a, "sum_squares".to_string())));
+
+    let add = &prog.debug_info[&0x401000];
+    assert_eq!(add.bound, Bound::new(0x401000, 0x40101a));
+    assert_eq!(
+        add.parameters,
+        vec![
+            DebugVariable { name: "a".to_string(), location: Some("RSP-28".to_string()) },
+            DebugVariable { name: "b".to_string(), location: Some("RSP-32".to_string()) },
+        ]
+    );
+    assert_eq!(add.locals, vec![DebugVariable { name: "sum".to_string(), location: Some("RSP-12".to_string()) }]);
+
+    let sum_squares = &prog.debug_info[&0x40101a];
+    assert_eq!(sum_squares.locals.iter().map(|v| v.name.as_str()).collect::<Vec<_>>(), vec!["acc", "i"]);
+    assert_eq!(
+        sum_squares.inlined,
+        vec![InlinedSubroutine { name: Some("square".to_string()), bound: Some(Bound::new(0x401037, 0x40103d)) }]
+    );
+}
+
+#[test]
+fn elf_load_stripped_eh_frame() {
+    // stripped copy of `dwarf`: only the unwind tables are left to find add and sum_squares
+    let (proj, _) = loader::load(Path::new("../test-data/stripped")).unwrap();
+    let prog = &proj.code[0];
+    let starts = prog.call_graph
+        .vertex_labels()
+        .filter_map(|ct| match ct {
+            &CallTarget::Todo(Rvalue::Constant { value, .. }, _, _) => Some(value),
+            _ => None,
+        })
+        .collect::<Vec<u64>>();
+
+    assert!(starts.contains(&0x401000));
+    assert!(starts.contains(&0x40101a));
+    assert_eq!(starts.iter().filter(|&&a| a == 0x401051).count(), 1);
+    assert_eq!(prog.symbol_extent(0x40101a), Some(Bound::new(0x40101a, 0x401051)));
+}
+
+fn crash_pc(prog: &Program) -> Option<u64> {
+    prog.call_graph
+        .vertex_labels()
+        .filter_map(|ct| match ct {
+            &CallTarget::Todo(Rvalue::Constant { value, .. }, None, _) => Some(value),
+            _ => None,
+        })
+        .next()
+}
+
+#[test]
+fn elf_load_core() {
+    // static binary dereferencing NULL with RBX set to 0x1122334455667788
+    let (proj, machine) = loader::load(Path::new("../test-data/crash.core")).unwrap();
+    let text = proj.region().section_at(0x401000).unwrap();
+    let header = proj.region().iter().seek(0x400000).take(4).map(|x| x.unwrap()).collect::<Vec<u8>>();
+    let comment = &proj.comments[&("base".to_string(), 0x40100b)];
+
+    assert_eq!(machine, Machine::Amd64);
+    assert_eq!(header, b"\x7fELF".to_vec());
+    assert_eq!(text.name, "/tmp/core/crash");
+    assert_eq!(text.bound, Bound::new(0x401000, 0x402000));
+    assert!(text.is_executable());
+    assert!(!proj.region().section_at(0x400000).unwrap().is_executable());
+    assert_eq!(crash_pc(&proj.code[0]), Some(0x40100b));
+    assert!(comment.starts_with("SIGSEGV in thread 9966\n"));
+    assert!(comment.contains("RBX=0x1122334455667788"));
+    assert!(comment.contains("RSP=0x7fff7de85b68"));
+}
+
+#[test]
+fn load_minidump() {
+    // thread 0x200 faults at 0x140001003 in crash.exe, thread 0x100 is at 0x140001000
+    let (proj, machine) = loader::load(Path::new("../test-data/crash.dmp")).unwrap();
+    let code = proj.region().iter().seek(0x140001000).take(7).map(|x| x.unwrap()).collect::<Vec<u8>>();
+
+    assert_eq!(machine, Machine::Amd64);
+    assert_eq!(code, vec![0x48, 0x31, 0xc0, 0x48, 0x8b, 0x00, 0xc3]);
+    assert_eq!(proj.region().find_section("C:\\crash.exe").map(|s| s.bound.clone()), Some(Bound::new(0x140000000, 0x140002000)));
+    assert_eq!(crash_pc(&proj.code[0]), Some(0x140001003));
+    assert!(proj.comments[&("base".to_string(), 0x140001003)].starts_with("EXCEPTION_ACCESS_VIOLATION (0xc0000005) in thread 512\n"));
+    assert!(proj.comments[&("base".to_string(), 0x140001003)].contains("RBX=0xdeadbeef"));
+    assert!(proj.comments[&("base".to_string(), 0x140001000)].starts_with("Thread 256\n"));
+}
@@ -17,9 +17,17 @@
  */
 
 extern crate panopticon_core;
+extern crate panopticon_graph_algos;
+extern crate panopticon_mos6502;
+extern crate tempdir;
 
-use panopticon_core::Project;
+use panopticon_core::{CallTarget, Function, Project, Relocation, loader};
+use panopticon_graph_algos::{AdjacencyMatrixGraphTrait, MutableGraphTrait};
+use panopticon_mos6502::{Mos, Variant};
+use std::fs::File;
+use std::io::Read;
 use std::path::Path;
+use tempdir::TempDir;
 
 #[test]
 fn project_open() {
@@ -27,3 +35,73 @@ fn project_open() {
 
     assert!(maybe_project.ok().is_some());
 }
+
+#[test]
+fn patch_and_export() {
+    // lda #1; sta $d020; rts at 0x80d, file offset 0xe
+    let path = Path::new("../test-data/hello.prg");
+    let (mut proj, _) = loader::load(path).unwrap();
+    let func = Function::new::<Mos>(0x80d, proj.region(), None, Variant::mos6502()).unwrap();
+    let uuid = func.uuid().clone();
+
+    proj.code[0].call_graph.add_vertex(CallTarget::Concrete(func));
+
+    // replace the store with NOPs
+    assert_eq!(proj.patch::<Mos>(0x80f, &[0xea, 0xea, 0xea], Variant::mos6502()).unwrap(), (vec![uuid], vec![]));
+    assert_eq!(proj.patch::<Mos>(0x801, &[0x0b], Variant::mos6502()).unwrap(), (vec![], vec![]));
+
+    let opcodes = proj.code[0]
+        .find_function_by_uuid(&uuid)
+        .unwrap()
+        .basic_blocks()
+        .flat_map(|bb| bb.mnemonics.iter().map(|m| m.opcode.clone()).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+
+    assert_eq!(opcodes, vec!["lda", "nop", "nop", "nop", "rts"]);
+
+    let tmpdir = TempDir::new("test-panop").unwrap();
+    let target = tmpdir.path().join("patched.prg");
+    let mut original = vec![];
+    let mut patched = vec![];
+
+    proj.export(path, &target).unwrap();
+    File::open(path).unwrap().read_to_end(&mut original).unwrap();
+    File::open(&target).unwrap().read_to_end(&mut patched).unwrap();
+
+    original[0x10..0x13].copy_from_slice(&[0xea, 0xea, 0xea]);
+    assert_eq!(patched, original);
+
+    // relocation fixups are not exported
+    proj.relocations.push(Relocation { address: 0x801, size: 2 });
+    proj.rebase(0x100).unwrap();
+    proj.export(path, &target).unwrap();
+    patched.clear();
+    File::open(&target).unwrap().read_to_end(&mut patched).unwrap();
+    assert_eq!(patched, original);
+
+    // zero page is not backed by the file
+    proj.patch::<Mos>(0x10, &[0x00], Variant::mos6502()).unwrap();
+    assert!(proj.export(path, &target).is_err());
+}
+
+#[test]
+fn patch_adds_calls() {
+    let path = Path::new("../test-data/hello.prg");
+    let (mut proj, _) = loader::load(path).unwrap();
+    let func = Function::new::<Mos>(0x80d, proj.region(), None, Variant::mos6502()).unwrap();
+    let uuid = func.uuid().clone();
+
+    proj.code[0].call_graph.add_vertex(CallTarget::Concrete(func));
+
+    // replace the store with jsr $0900
+    let (reloaded, todos) = proj.patch::<Mos>(0x80f, &[0x20, 0x00, 0x09], Variant::mos6502()).unwrap();
+
+    assert_eq!(reloaded, vec![uuid]);
+    assert_eq!(todos.len(), 1);
+
+    let prog = &proj.code[0];
+    let from = prog.find_call_target_by_uuid(&uuid).unwrap();
+    let to = prog.find_call_target_by_uuid(&todos[0]).unwrap();
+
+    assert!(prog.call_graph.edge(from, to).is_some());
+}
@@ -19,7 +19,7 @@
 extern crate panopticon_core;
 extern crate tempdir;
 
-use panopticon_core::{Bound, Layer, Region};
+use panopticon_core::{Bound, Layer, OpaqueLayer, Project, Region};
 use std::fs::File;
 use std::io::Write;
 use std::path::Path;
@@ -73,3 +73,31 @@ fn read_one_layer() {
         }
     }
 }
+
+#[test]
+fn mapped_layer_snapshot() {
+    let tmpdir = TempDir::new("test-panop").unwrap();
+    let p1 = tmpdir.path().join("image");
+    let p2 = tmpdir.path().join("snapshot.panop");
+
+    File::create(&p1).unwrap().write_all(b"Hello, World").unwrap();
+
+    let mut r1 = Region::undefined("test".to_string(), 128);
+    let layer = OpaqueLayer::open(&p1).unwrap();
+
+    match layer {
+        OpaqueLayer::Mapped(ref m) => assert_eq!(m.slice(7, 5).map(|x| x.as_slice().to_vec()), Some(b"World".to_vec())),
+        _ => assert!(false),
+    }
+    assert!(r1.cover(Bound::new(16, 28), Layer::Opaque(layer)));
+    Project::new("test".to_string(), r1).snapshot(&p2).unwrap();
+
+    let proj = Project::open(&p2).unwrap();
+    let cells = proj.region().iter().cut(&(16..28)).map(|c| c.unwrap()).collect::<Vec<u8>>();
+
+    assert_eq!(cells, b"Hello, World".to_vec());
+    drop(proj);
+
+    File::create(&p1).unwrap().write_all(b"Hello, Moon!").unwrap();
+    assert!(Project::open(&p2).is_err());
+}
@@ -19,7 +19,8 @@
 //! Collection of data flow algorithms.
 //!
 //! This module contains algorithms to convert RREIL code into SSA form. Aside from SSA form this
-//! module implements functions to compute liveness sets and basic reverse data flow information.
+//! module implements functions to compute liveness sets and basic reverse data flow information
+//! as well as a peephole optimizer that simplifies RREIL code before SSA conversion.
 
 extern crate panopticon_core;
 extern crate panopticon_graph_algos;
@@ -29,3 +30,6 @@ pub use crate::liveness::{liveness, liveness_sets};
 
 mod ssa;
 pub use crate::ssa::{flag_operations, ssa_convertion, type_check};
+
+mod simplify;
+pub use crate::simplify::simplify;
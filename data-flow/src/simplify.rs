@@ -0,0 +1,491 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Peephole optimizer for RREIL code.
+//!
+//! Simplification is done in two passes over each basic block. The first one propagates constants
+//! and copies, folds operations with constant operands using `il::execute` and applies algebraic
+//! identities like `x + 0 = x` or `x ^ x = 0`. Known values are forgotten after each call. The
+//! second one removes assignments that are never read. Because the values of registers are visible
+//! to the caller only assignments to flags (one bit variables) that are dead according to
+//! `liveness` and assignments that are overwritten in the same basic block are removed.
+//!
+//! Statements are never moved from one mnemonic to another, a mnemonic may end up without any
+//! statements though.
+
+use crate::liveness;
+use panopticon_core::{ControlFlowRef, ControlFlowTarget, Function, Guard, Lvalue, Operation, Rvalue, Statement, execute};
+use panopticon_graph_algos::{GraphTrait, IncidenceGraphTrait, MutableGraphTrait, VertexListGraphTrait};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+
+type Key = (Cow<'static, str>, Option<usize>);
+
+/// Simplifies the RREIL code of all basic blocks in `func`. Should be called before SSA
+/// conversion.
+pub fn simplify(func: &mut Function) {
+    let vxs = func.cfg().vertices().collect::<Vec<ControlFlowRef>>();
+
+    for &vx in vxs.iter() {
+        if let Some(&mut ControlFlowTarget::Resolved(ref mut bb)) = func.cfg_mut().vertex_label_mut(vx) {
+            let mut values = HashMap::<Key, Rvalue>::new();
+
+            for mne in bb.mnemonics.iter_mut() {
+                let stmts = mne.instructions.drain(..).collect::<Vec<_>>();
+
+                for stmt in stmts {
+                    if let Some(stmt) = propagate(stmt, &mut values) {
+                        mne.instructions.push(stmt);
+                    }
+                }
+            }
+        }
+    }
+
+    let liveout = liveness(func);
+    // callees may read any variable
+    let mut vars = HashSet::<Cow<'static, str>>::new();
+
+    for stmt in func.statements() {
+        if let Lvalue::Variable { ref name, .. } = stmt.assignee {
+            vars.insert(name.clone());
+        }
+        for rv in stmt.op.operands() {
+            if let &Rvalue::Variable { ref name, .. } = rv {
+                vars.insert(name.clone());
+            }
+        }
+    }
+
+    for &vx in vxs.iter() {
+        let mut live = liveout.get(&vx).cloned().unwrap_or_else(HashSet::new);
+
+        for e in func.cfg().out_edges(vx) {
+            if let Some(&Guard::Predicate { flag: Rvalue::Variable { ref name, .. }, .. }) = func.cfg().edge_label(e) {
+                live.insert(name.clone());
+            }
+        }
+
+        if let Some(&mut ControlFlowTarget::Resolved(ref mut bb)) = func.cfg_mut().vertex_label_mut(vx) {
+            let mut killed = HashSet::<Cow<'static, str>>::new();
+
+            for mne in bb.mnemonics.iter_mut().rev() {
+                let mut stmts = mne.instructions.drain(..).rev().filter(|stmt| !is_dead(stmt, &vars, &mut live, &mut killed)).collect::<Vec<_>>();
+
+                stmts.reverse();
+                mne.instructions = stmts;
+
+                for rv in mne.operands.iter() {
+                    if let &Rvalue::Variable { ref name, .. } = rv {
+                        live.insert(name.clone());
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Rewrites `stmt` using the known variable values in `values` and updates them afterwards.
+/// Returns None if the statement has no effect.
+fn propagate(mut stmt: Statement, values: &mut HashMap<Key, Rvalue>) -> Option<Statement> {
+    // mov a, a
+    if let Statement { op: Operation::Move(Rvalue::Variable { ref name, ref subscript, offset: 0, size }), assignee: Lvalue::Variable { name: ref n, subscript: ref s, size: sz } } = stmt {
+        if name == n && subscript == s && size == sz {
+            return None;
+        }
+    }
+
+    if let Operation::Phi(_) = stmt.op {
+        // phi arguments refer to values from other basic blocks
+    } else {
+        for rv in stmt.op.operands_mut() {
+            let new = match rv {
+                &mut Rvalue::Constant { value, size } => Some(Rvalue::new_wide(vec![value], size)),
+                &mut Rvalue::Variable { ref name, ref subscript, offset, size } => {
+                    values.get(&(name.clone(), *subscript)).and_then(|val| val.extract(size, offset).ok())
+                }
+                _ => None,
+            };
+
+            if let Some(new) = new {
+                *rv = new;
+            }
+        }
+
+        if let (Some(size), true) = (stmt.assignee.size(), stmt.sanity_check().is_ok()) {
+            stmt.op = fold(stmt.op, size);
+        }
+    }
+
+    // the callee may overwrite any register
+    if let Operation::Call(_) = stmt.op {
+        values.clear();
+    }
+
+    if let Lvalue::Variable { ref name, ref subscript, size } = stmt.assignee {
+        let key = (name.clone(), *subscript);
+
+        values.remove(&key);
+        values.retain(|_, v| if let &mut Rvalue::Variable { ref name, ref subscript, .. } = v { *name != key.0 || *subscript != key.1 } else { true });
+
+        match stmt.op {
+            Operation::Move(ref rv @ Rvalue::Constant { .. }) |
+            Operation::Move(ref rv @ Rvalue::Wide { .. }) |
+            Operation::Move(ref rv @ Rvalue::Variable { .. }) if rv.size() == Some(size) => {
+                let is_self = match rv {
+                    &Rvalue::Variable { ref name, ref subscript, .. } => *name == key.0 && *subscript == key.1,
+                    _ => false,
+                };
+
+                if !is_self {
+                    values.insert(key, rv.clone());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Some(stmt)
+}
+
+/// Replaces `op` by a simpler operation computing the same value. `size` is the size of the
+/// result.
+fn fold(op: Operation<Rvalue>, size: usize) -> Operation<Rvalue> {
+    let is_const = |rv: &Rvalue| match rv {
+        &Rvalue::Constant { .. } | &Rvalue::Wide { .. } => true,
+        _ => false,
+    };
+    let foldable = match op {
+        Operation::Move(_) | Operation::Call(_) | Operation::Initialize(_, _) | Operation::Load(_, _, _, _) | Operation::Store(_, _, _, _, _) |
+        Operation::Phi(_) => false,
+        _ => op.operands().into_iter().all(|rv| is_const(rv)),
+    };
+
+    if foldable {
+        match execute(op.clone()) {
+            Rvalue::Undefined => {}
+            rv => return Operation::Move(rv),
+        }
+    }
+
+    let zero = Rvalue::Constant { value: 0, size: size };
+    let is_zero = |rv: &Rvalue| match rv {
+        &Rvalue::Constant { value: 0, .. } => true,
+        _ => false,
+    };
+    let is_one = |rv: &Rvalue| match rv {
+        &Rvalue::Constant { value: 1, .. } => true,
+        _ => false,
+    };
+    let is_ones = |rv: &Rvalue| match rv {
+        &Rvalue::Constant { value, size } => size > 0 && size <= 64 && value == u64::MAX >> (64 - size),
+        &Rvalue::Wide { ref words, size } => words.iter().enumerate().all(|(i, &w)| if (i + 1) * 64 <= size { w == u64::MAX } else { w == u64::MAX >> (64 - size % 64) }),
+        _ => false,
+    };
+    // undefined values are not equal to themselves
+    let same = |a: &Rvalue, b: &Rvalue| a == b && *a != Rvalue::Undefined;
+
+    match op {
+        Operation::Add(a, b) => {
+            if is_zero(&b) {
+                Operation::Move(a)
+            } else if is_zero(&a) {
+                Operation::Move(b)
+            } else {
+                Operation::Add(a, b)
+            }
+        }
+        Operation::Subtract(a, b) => {
+            if is_zero(&b) {
+                Operation::Move(a)
+            } else if same(&a, &b) {
+                Operation::Move(zero)
+            } else {
+                Operation::Subtract(a, b)
+            }
+        }
+        Operation::Multiply(a, b) => {
+            if is_one(&b) {
+                Operation::Move(a)
+            } else if is_one(&a) {
+                Operation::Move(b)
+            } else if is_zero(&a) || is_zero(&b) {
+                Operation::Move(zero)
+            } else {
+                Operation::Multiply(a, b)
+            }
+        }
+        Operation::DivideUnsigned(a, b) => if is_one(&b) { Operation::Move(a) } else { Operation::DivideUnsigned(a, b) },
+        Operation::DivideSigned(a, b) => if is_one(&b) { Operation::Move(a) } else { Operation::DivideSigned(a, b) },
+        Operation::Modulo(a, b) => if is_one(&b) { Operation::Move(zero) } else { Operation::Modulo(a, b) },
+        Operation::ShiftLeft(a, b) => {
+            if is_zero(&b) {
+                Operation::Move(a)
+            } else if is_zero(&a) {
+                Operation::Move(zero)
+            } else {
+                Operation::ShiftLeft(a, b)
+            }
+        }
+        Operation::ShiftRightUnsigned(a, b) => {
+            if is_zero(&b) {
+                Operation::Move(a)
+            } else if is_zero(&a) {
+                Operation::Move(zero)
+            } else {
+                Operation::ShiftRightUnsigned(a, b)
+            }
+        }
+        Operation::ShiftRightSigned(a, b) => {
+            if is_zero(&b) {
+                Operation::Move(a)
+            } else if is_zero(&a) {
+                Operation::Move(zero)
+            } else {
+                Operation::ShiftRightSigned(a, b)
+            }
+        }
+        Operation::And(a, b) => {
+            if is_zero(&a) || is_zero(&b) {
+                Operation::Move(zero)
+            } else if is_ones(&b) || same(&a, &b) {
+                Operation::Move(a)
+            } else if is_ones(&a) {
+                Operation::Move(b)
+            } else {
+                Operation::And(a, b)
+            }
+        }
+        Operation::InclusiveOr(a, b) => {
+            if is_ones(&a) {
+                Operation::Move(a)
+            } else if is_ones(&b) {
+                Operation::Move(b)
+            } else if is_zero(&b) || same(&a, &b) {
+                Operation::Move(a)
+            } else if is_zero(&a) {
+                Operation::Move(b)
+            } else {
+                Operation::InclusiveOr(a, b)
+            }
+        }
+        Operation::ExclusiveOr(a, b) => {
+            if is_zero(&b) {
+                Operation::Move(a)
+            } else if is_zero(&a) {
+                Operation::Move(b)
+            } else if same(&a, &b) {
+                Operation::Move(zero)
+            } else {
+                Operation::ExclusiveOr(a, b)
+            }
+        }
+        Operation::Equal(a, b) => if same(&a, &b) { Operation::Move(Rvalue::new_bit(1)) } else { Operation::Equal(a, b) },
+        Operation::LessOrEqualUnsigned(a, b) => {
+            if same(&a, &b) {
+                Operation::Move(Rvalue::new_bit(1))
+            } else {
+                Operation::LessOrEqualUnsigned(a, b)
+            }
+        }
+        Operation::LessOrEqualSigned(a, b) => {
+            if same(&a, &b) {
+                Operation::Move(Rvalue::new_bit(1))
+            } else {
+                Operation::LessOrEqualSigned(a, b)
+            }
+        }
+        Operation::LessUnsigned(a, b) => if same(&a, &b) { Operation::Move(Rvalue::new_bit(0)) } else { Operation::LessUnsigned(a, b) },
+        Operation::LessSigned(a, b) => if same(&a, &b) { Operation::Move(Rvalue::new_bit(0)) } else { Operation::LessSigned(a, b) },
+        Operation::ZeroExtend(sz, a) => if a.size() == Some(sz) { Operation::Move(a) } else { Operation::ZeroExtend(sz, a) },
+        Operation::SignExtend(sz, a) => if a.size() == Some(sz) { Operation::Move(a) } else { Operation::SignExtend(sz, a) },
+        // select of a value that covers the whole variable
+        Operation::Select(0, a, b) => if a.size() == b.size() { Operation::Move(b) } else { Operation::Select(0, a, b) },
+        op => op,
+    }
+}
+
+/// Returns true if `stmt` can be removed because its result is never read. Otherwise updates the
+/// set of `live` variables and the variables `killed` later in the basic block. Calls may read all
+/// `vars` of the function.
+fn is_dead(stmt: &Statement, vars: &HashSet<Cow<'static, str>>, live: &mut HashSet<Cow<'static, str>>, killed: &mut HashSet<Cow<'static, str>>) -> bool {
+    let pure = match stmt.op {
+        Operation::Call(_) | Operation::Store(_, _, _, _, _) => false,
+        _ => true,
+    };
+
+    match stmt.assignee {
+        Lvalue::Undefined if pure => return true,
+        Lvalue::Variable { ref name, size, .. } if pure && !live.contains(name) && (size == 1 || killed.contains(name)) => return true,
+        Lvalue::Variable { ref name, .. } => {
+            live.remove(name);
+            killed.insert(name.clone());
+        }
+        Lvalue::Undefined => {}
+    }
+
+    if let Operation::Call(_) = stmt.op {
+        live.extend(vars.iter().cloned());
+        killed.clear();
+    }
+
+    for rv in stmt.op.operands() {
+        if let &Rvalue::Variable { ref name, .. } = rv {
+            live.insert(name.clone());
+        }
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use panopticon_core::{BasicBlock, ControlFlowGraph, Mnemonic, Region, parse_statements};
+    use panopticon_graph_algos::MutableGraphTrait;
+
+    fn mnemonic(addr: u64, text: &str) -> Mnemonic {
+        let stmts = parse_statements(text).unwrap();
+        Mnemonic::new(addr..addr + 1, format!("mne{}", addr), "".to_string(), vec![].iter(), stmts.iter()).unwrap()
+    }
+
+    fn listing(func: &Function, vx: ControlFlowRef) -> Vec<String> {
+        match func.cfg().vertex_label(vx) {
+            Some(&ControlFlowTarget::Resolved(ref bb)) => bb.mnemonics.iter().map(|mne| mne.instructions.iter().map(|s| format!("{}", s)).collect::<Vec<_>>().join("; ")).collect(),
+            _ => unreachable!(),
+        }
+    }
+
+    fn function(cfg: ControlFlowGraph, entry: ControlFlowRef) -> Function {
+        let mut func = Function::undefined(0, None, &Region::undefined("ram".to_owned(), 100), None);
+
+        *func.cfg_mut() = cfg;
+        func.set_entry_point_ref(entry);
+        func
+    }
+
+    #[test]
+    fn propagate_and_fold() {
+        let mut cfg = ControlFlowGraph::new();
+        let bb = BasicBlock::from_vec(
+            vec![
+                mnemonic(0, "mov a:32, 0x10:32\nadd b:32, a:32, 0x2:32\nmov c:32, x:32"),
+                mnemonic(1, "mov d:32, c:32\nadd e:32, d:32, 0x0:32\nxor f:32, y:32, y:32"),
+                mnemonic(2, "mov x:32, 0x1:32\nmul g:32, d:32, 0x1:32\nselect_0 h:32, d:32, b:32\nmov h:32, h:32"),
+                mnemonic(3, "load_ram/le/32 i:32, e:32\nconvert_32 j:32, i:32\nstore_ram/le/32 ?, b:32, d:8/8"),
+            ]
+        );
+        let vx = cfg.add_vertex(ControlFlowTarget::Resolved(bb));
+        let mut func = function(cfg, vx);
+
+        simplify(&mut func);
+
+        assert_eq!(
+            listing(&func, vx),
+            vec![
+                "mov a:32, 0x10:32; mov b:32, 0x12:32; mov c:32, x:32",
+                "mov d:32, x:32; mov e:32, x:32; mov f:32, 0x0:32",
+                "mov x:32, 0x1:32; mov g:32, d:32; mov h:32, 0x12:32",
+                "load_ram/le/32 i:32, e:32; mov j:32, i:32; store_ram/le/32 ?, 0x12:32, d:8/8",
+            ]
+        );
+    }
+
+    #[test]
+    fn calls_clobber_values() {
+        let mut cfg = ControlFlowGraph::new();
+        let bb = BasicBlock::from_vec(
+            vec![
+                mnemonic(0, "mov a:32, 0x10:32\nmov t:32, 0x20:32"),
+                mnemonic(1, "call ?, t:32"),
+                mnemonic(2, "add b:32, a:32, 0x2:32\nmov c:32, t:32"),
+            ]
+        );
+        let vx = cfg.add_vertex(ControlFlowTarget::Resolved(bb));
+        let mut func = function(cfg, vx);
+
+        simplify(&mut func);
+
+        assert_eq!(
+            listing(&func, vx),
+            vec![
+                "mov a:32, 0x10:32; mov t:32, 0x20:32",
+                "call ?, 0x20:32",
+                "add b:32, a:32, 0x2:32; mov c:32, t:32",
+            ]
+        );
+    }
+
+    #[test]
+    fn calls_read_arguments() {
+        let mut cfg = ControlFlowGraph::new();
+        let bb = BasicBlock::from_vec(
+            vec![
+                mnemonic(0, "mov edi:32, 0x1:32"),
+                mnemonic(1, "call ?, f:32"),
+                mnemonic(2, "mov edi:32, 0x2:32"),
+            ]
+        );
+        let vx = cfg.add_vertex(ControlFlowTarget::Resolved(bb));
+        let mut func = function(cfg, vx);
+
+        simplify(&mut func);
+
+        assert_eq!(
+            listing(&func, vx),
+            vec![
+                "mov edi:32, 0x1:32",
+                "call ?, f:32",
+                "mov edi:32, 0x2:32",
+            ]
+        );
+    }
+
+    #[test]
+    fn dead_flags() {
+        let mut cfg = ControlFlowGraph::new();
+        let bb0 = BasicBlock::from_vec(
+            vec![
+                mnemonic(0, "add a:32, a:32, b:32\ncmpeq ZF:1, a:32, 0x0:32\ncmplu CF:1, a:32, b:32\nmov t:32, a:32"),
+                mnemonic(1, "sub a:32, a:32, 0x1:32\ncmpeq ZF:1, a:32, 0x0:32\ncmplu CF:1, a:32, 0x1:32\nmov t:32, b:32\nmov ?, t:32"),
+            ]
+        );
+        let bb1 = BasicBlock::from_vec(vec![mnemonic(2, "add b:32, b:32, 0x1:32\nmov ZF:1, 0x0:1")]);
+        let bb2 = BasicBlock::from_vec(vec![mnemonic(3, "call ?, t:32\ncmplu CF:1, t:32, 0x1:32")]);
+        let v0 = cfg.add_vertex(ControlFlowTarget::Resolved(bb0));
+        let v1 = cfg.add_vertex(ControlFlowTarget::Resolved(bb1));
+        let v2 = cfg.add_vertex(ControlFlowTarget::Resolved(bb2));
+        let g = Guard::from_flag(&Rvalue::Variable { name: Cow::Borrowed("ZF"), subscript: None, offset: 0, size: 1 }).unwrap();
+
+        cfg.add_edge(g.clone(), v0, v1);
+        cfg.add_edge(g.negation(), v0, v2);
+        let mut func = function(cfg, v0);
+
+        simplify(&mut func);
+
+        assert_eq!(
+            listing(&func, v0),
+            vec![
+                "add a:32, a:32, b:32",
+                "sub a:32, a:32, 0x1:32; cmpeq ZF:1, a:32, 0x0:32; mov t:32, b:32",
+            ]
+        );
+        assert_eq!(listing(&func, v1), vec!["add b:32, b:32, 0x1:32"]);
+        assert_eq!(listing(&func, v2), vec!["call ?, t:32"]);
+    }
+}
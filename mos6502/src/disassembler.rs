@@ -16,7 +16,7 @@
  * along with this program.  If not, see <http://www.gnu.org/licenses/>.
  */
 
-use panopticon_core::{Architecture, Guard, Lvalue, Match, Region, Result, Rvalue, State, Statement};
+use panopticon_core::{Architecture, Endianess, Guard, Lvalue, Match, Region, Result, Rvalue, State, Statement};
 use std::borrow::Cow;
 use crate::syntax;
 
@@ -28,7 +28,6 @@ impl Architecture for Mos {
     type Configuration = Variant;
 
     fn prepare(reg: &Region, _: &Self::Configuration) -> Result<Vec<(&'static str, u64, &'static str)>> {
-        let i = reg.iter();
         let iv = vec![
             ("NMI", 0xfffa, "NMI vector"),
             ("RESET", 0xfffc, "Reset routine"),
@@ -37,15 +36,8 @@ impl Architecture for Mos {
         let mut ret = vec![];
 
         for v in iv {
-            let mut j = i.clone();
-
-            j.seek(v.1);
-            let maybe_lo = j.next();
-            let maybe_hi = j.next();
-            if let (Some(Some(hi)), Some(Some(lo))) = (maybe_hi, maybe_lo) {
-                let addr = ((hi as u64) << 8) | (lo as u64);
-
-                ret.push((v.0, addr, v.2))
+            if let Some(addr) = reg.read_u16(v.1, Endianess::Little) {
+                ret.push((v.0, addr as u64, v.2))
             }
         }
 
@@ -55,8 +47,11 @@ impl Architecture for Mos {
     fn decode(reg: &Region, addr: u64, cfg: &Self::Configuration) -> Result<Match<Self>> {
         info!("disass @ {:x}", addr);
         let disass = syntax::disassembler();
+        // opcode and at most two operand bytes
+        let mut cells = [None; 3];
+        let len = reg.read(addr, &mut cells);
 
-        if let Some(st) = disass.next_match(&mut reg.iter().seek(addr), addr, cfg.clone()) {
+        if let Some(st) = disass.next_match(&mut cells[0..len].iter().cloned(), addr, cfg.clone()) {
             info!("    res: {:?}", st);
             Ok(st.into())
         } else {
@@ -74,6 +74,19 @@ impl ControlFlowLayout {
 
         let (vertices, edges, edges_rev) = Self::flatten_cflow_graph(func);
 
+        // list the source-level parameters next to the entry point
+        let mut comments = comments.clone();
+        if let Some(info) = func.debug_info() {
+            if !info.parameters.is_empty() {
+                let params = info.parameters
+                    .iter()
+                    .map(|p| match p.location { Some(ref loc) => format!("{} @ {}", p.name, loc), None => p.name.clone() })
+                    .collect::<Vec<_>>();
+                comments.entry(func.start()).or_insert(params.join(", "));
+            }
+        }
+        let comments = &comments;
+
         if vertices.is_empty() {
             println!("{} is empty", func.uuid());
             return Box::new(
@@ -452,6 +465,16 @@ impl ControlFlowLayout {
                     data: s,
                 }
             }
+            &Rvalue::Wide { .. } => {
+                let s = rv.hex_digits().unwrap_or_default();
+
+                BasicBlockOperand {
+                    kind: "constant",
+                    display: s.clone(),
+                    alt: "".to_string(),
+                    data: s,
+                }
+            }
             &Rvalue::Variable { ref name, subscript, .. } => {
                 let data = if let Some(subscript) = subscript {
                     format!("{}_{}", *name, subscript)
@@ -564,6 +587,11 @@ impl ControlFlowLayout {
                                     }
                                 )
                             }
+                            Some(rv @ Rvalue::Wide { .. }) => {
+                                let op = Self::rvalue_to_operand(&rv, false, None);
+
+                                Some(BasicBlockOperand { kind: "pointer", data: "".to_string(), ..op })
+                            }
                             Some(Rvalue::Variable { ref name, .. }) => {
                                 Some(
                                     BasicBlockOperand {
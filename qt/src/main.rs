@@ -27,6 +27,7 @@ extern crate panopticon_data_flow;
 extern crate panopticon_graph_algos;
 extern crate panopticon_amd64;
 extern crate panopticon_avr;
+extern crate panopticon_mos6502;
 extern crate libc;
 extern crate uuid;
 extern crate cassowary;
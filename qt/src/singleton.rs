@@ -129,6 +129,7 @@ impl Panopticon {
         use panopticon_core::{CallTarget, Machine};
         use panopticon_amd64 as amd64;
         use panopticon_avr as avr;
+        use panopticon_mos6502 as mos6502;
         use panopticon_analysis::pipeline;
         use futures::Stream;
         use std::ffi::CString;
@@ -154,15 +155,20 @@ impl Panopticon {
                 Ok(())
             }
         } else if let Ok((mut proj, machine)) = loader::load(&Path::new(&path)) {
+            if machine == Machine::Mos6502 {
+                // iNES images carry their entry points in the interrupt vectors
+                loader::seed_entry_points::<mos6502::Mos>(&mut proj, &mos6502::Variant::mos6502())?;
+            }
             let maybe_prog = proj.code.pop();
             let reg = proj.region().clone();
 
             if let Some(prog) = maybe_prog {
                 let prog = ::std::sync::Arc::new(prog);
                 let pipe = match machine {
-                    Machine::Avr => pipeline::<avr::Avr>(prog, reg.clone(), avr::Mcu::atmega103()),
-                    Machine::Ia32 => pipeline::<amd64::Amd64>(prog, reg.clone(), amd64::Mode::Protected),
-                    Machine::Amd64 => pipeline::<amd64::Amd64>(prog, reg.clone(), amd64::Mode::Long),
+                    Machine::Avr => pipeline::<avr::Avr>(prog, reg.clone(), avr::Mcu::atmega103(), false),
+                    Machine::Ia32 => pipeline::<amd64::Amd64>(prog, reg.clone(), amd64::Mode::Protected, false),
+                    Machine::Amd64 => pipeline::<amd64::Amd64>(prog, reg.clone(), amd64::Mode::Long, false),
+                    Machine::Mos6502 => pipeline::<mos6502::Mos>(prog, reg.clone(), mos6502::Variant::mos6502(), false),
                 };
                 self.region = Some(reg);
 